@@ -0,0 +1,86 @@
+/*
+ * A C interface to jrep's notebook search. Link with libjrep (built from jrep-ffi with
+ * `cargo build --release`).
+ *
+ * Strings passed in must be NUL-terminated UTF-8. Everything returned belongs to the object
+ * it came from and stays valid until that object is freed. Functions that can fail return
+ * NULL (or a negative number) and leave a message for jrep_last_error().
+ */
+#ifndef JREP_H
+#define JREP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* What to search for and where in each notebook */
+typedef struct JrepOptions JrepOptions;
+
+/* All the matches from one search, and the notebooks that could not be searched */
+typedef struct JrepResults JrepResults;
+
+typedef struct JrepMatch {
+    /* The notebook the match is in */
+    const char *path;
+    /* The index of the cell, or -1 for a match in the notebook metadata */
+    int64_t cell;
+    /* "code", "markdown", "raw", or "notebook" for the notebook metadata */
+    const char *cell_type;
+    /* Which part of the cell the match is in: "source", "metadata", or the output type */
+    const char *piece;
+    /* The line that matched, without its newline */
+    const char *line;
+    /* The 0-based index of the line in the cell source or output */
+    size_t line_number;
+    /* JSON Pointer to the matched string in the notebook file */
+    const char *pointer;
+    /* The MIME type of the output data the line is in, or NULL if it is not in output data */
+    const char *mime_type;
+    /* Nonzero unless the match is in output data that is not text (e.g. an image) */
+    int is_text;
+    /* The byte ranges of `line` that matched, as n_spans pairs of start and end */
+    size_t n_spans;
+    const size_t *spans;
+    /* The 1-based line of the notebook file the match is on with "--file-lines", otherwise -1 */
+    int64_t file_line;
+    /* The tags of the cell */
+    size_t n_tags;
+    const char *const *tags;
+} JrepMatch;
+
+/* Options parsed from command line arguments, exactly as the jrep program would parse them
+ * (without the program name), e.g. {"-i", "--cell-type", "code", "read_csv"}. The pattern is
+ * the first argument that is not an option; put "--" before it if it may start with "-". */
+JrepOptions *jrep_options_new(const char *const *args, size_t n_args);
+void jrep_options_free(JrepOptions *options);
+
+/* Search notebooks, and directories whose notebooks are searched (with their subdirectories if
+ * the options include "--recursive"). Returns NULL only if no notebooks were found. */
+JrepResults *jrep_search_paths(const JrepOptions *options, const char *const *paths, size_t n_paths);
+
+/* Search a notebook that is already in memory, given `len` bytes of the JSON text of its file.
+ * `name` is used as the path of the matches. Returns NULL if it is not a valid notebook. */
+JrepResults *jrep_search_buffer(const JrepOptions *options, const char *name, const char *data, size_t len);
+
+size_t jrep_results_len(const JrepResults *results);
+/* The i-th match, or NULL if i is out of range */
+const JrepMatch *jrep_results_get(const JrepResults *results, size_t i);
+
+/* Notebooks that could not be read or parsed are skipped; these are the messages about them */
+size_t jrep_results_n_errors(const JrepResults *results);
+const char *jrep_results_error(const JrepResults *results, size_t i);
+
+void jrep_results_free(JrepResults *results);
+
+/* The message of the last error on this thread, or NULL if there has not been one. Valid until
+ * the next call into jrep on this thread. */
+const char *jrep_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,307 @@
+//! A C interface to jrep
+//!
+//! The functions are described in `include/jrep.h`. Options are made from command line
+//! arguments with [`SearchOptions::from_args`], so they mean the same as for the program, and
+//! searches go through a [`Searcher`]. Each search copies its matches into C strings owned by
+//! the `JrepResults` it returns, so the `JrepMatch`es can point into them until it is freed.
+//! A match whose text has a NUL byte in it cannot be given as a C string without moving its
+//! spans, so it is listed in the errors of the results instead. Every exported function
+//! catches panics, which must not unwind into C, and reports them as errors.
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString, OsStr};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use jrep_core::{Match, SearchOptions, Searcher};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error<E: ToString>(error: E) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message(&error.to_string())));
+}
+
+/// Runs `f`, returning `failed` and leaving an error for `jrep_last_error` if it panics
+fn catch_panic<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let reason = payload.downcast_ref::<&str>().copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown reason");
+        set_last_error(format!("jrep panicked: {}", reason));
+        failed
+    })
+}
+
+/// `s` as a C string, or an error naming `what` it is if it has a NUL byte in it
+fn c_string(s: &str, what: &str) -> Result<CString, String> {
+    CString::new(s).map_err(|e| format!("The {} has a NUL byte at {}", what, e.nul_position()))
+}
+
+/// The error message `s` as a C string, with any NUL bytes in it escaped as `\0`
+fn message(s: &str) -> CString {
+    CString::new(s.replace('\0', "\\0")).expect("NUL bytes were escaped")
+}
+
+/// # Safety
+/// `s` must be null or point to a NUL-terminated string that outlives `'a`
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("The {} is NULL", what));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| format!("The {} is not valid UTF-8", what))
+}
+
+/// # Safety
+/// `args` must be null only if `n` is 0, and otherwise point to `n` pointers that each meet
+/// the requirements of `str_arg`
+unsafe fn str_args<'a>(args: *const *const c_char, n: usize, what: &str) -> Result<Vec<&'a str>, String> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    if args.is_null() {
+        return Err(format!("The {}s are NULL", what));
+    }
+    std::slice::from_raw_parts(args, n).iter().map(|&s| str_arg(s, what)).collect()
+}
+
+/// The options of `jrep_options_new`
+pub struct JrepOptions(Searcher);
+
+#[repr(C)]
+pub struct JrepMatch {
+    path: *const c_char,
+    cell: i64,
+    cell_type: *const c_char,
+    piece: *const c_char,
+    line: *const c_char,
+    line_number: usize,
+    pointer: *const c_char,
+    mime_type: *const c_char,
+    is_text: c_int,
+    n_spans: usize,
+    spans: *const usize,
+    file_line: i64,
+    n_tags: usize,
+    tags: *const *const c_char
+}
+
+/// The strings and spans that a `JrepMatch` points to
+struct OwnedMatch {
+    path: CString,
+    cell_type: CString,
+    piece: CString,
+    line: CString,
+    pointer: CString,
+    mime_type: Option<CString>,
+    spans: Vec<usize>,
+    // Only kept for as long as `tag_ptrs` points into it
+    #[allow(dead_code)]
+    tags: Vec<CString>,
+    tag_ptrs: Vec<*const c_char>
+}
+
+impl TryFrom<&Match> for OwnedMatch {
+    type Error = String;
+
+    fn try_from(m: &Match) -> Result<Self, String> {
+        let tags = m.tags.iter().map(|t| c_string(t, "tag")).collect::<Result<Vec<_>, _>>()?;
+        let tag_ptrs = tags.iter().map(|t| t.as_ptr()).collect();
+        Ok(Self{
+            path: c_string(&m.path.to_string_lossy(), "path")?,
+            cell_type: c_string(&m.cell_type, "cell type")?,
+            piece: c_string(&m.piece, "piece")?,
+            line: c_string(m.line.trim_end_matches(['\n', '\r']), "matched line")?,
+            pointer: c_string(&m.pointer, "pointer")?,
+            mime_type: m.mime_type.as_deref().map(|t| c_string(t, "MIME type")).transpose()?,
+            spans: m.match_positions.iter().flat_map(|&(start, end)| [start, end]).collect(),
+            tags,
+            tag_ptrs
+        })
+    }
+}
+
+/// The results of a search. The `JrepMatch`es point into `owned`, whose strings and spans
+/// are on the heap, so they do not move even though the vectors holding them might.
+pub struct JrepResults {
+    // Never read, only kept for as long as `matches` points into it
+    #[allow(dead_code)]
+    owned: Vec<OwnedMatch>,
+    matches: Vec<JrepMatch>,
+    errors: Vec<CString>
+}
+
+impl JrepResults {
+    fn new(found: &[Match], mut errors: Vec<String>) -> Self {
+        let mut kept = Vec::new();
+        let mut owned = Vec::new();
+        for m in found {
+            match OwnedMatch::try_from(m) {
+                Ok(o) => {
+                    kept.push(m);
+                    owned.push(o);
+                },
+                Err(e) => errors.push(format!("{}: the match at {} is left out: {}",
+                    m.path.to_string_lossy(), m.pointer, e))
+            }
+        }
+        let matches = owned.iter().zip(kept).map(|(o, m)| JrepMatch{
+            path: o.path.as_ptr(),
+            cell: m.cell.map_or(-1, |c| c as i64),
+            cell_type: o.cell_type.as_ptr(),
+            piece: o.piece.as_ptr(),
+            line: o.line.as_ptr(),
+            line_number: m.line_number,
+            pointer: o.pointer.as_ptr(),
+            mime_type: o.mime_type.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+            is_text: m.is_text as c_int,
+            n_spans: o.spans.len() / 2,
+            spans: o.spans.as_ptr(),
+            file_line: m.file_line.map_or(-1, |l| l as i64),
+            n_tags: o.tag_ptrs.len(),
+            tags: o.tag_ptrs.as_ptr()
+        }).collect();
+        let errors = errors.iter().map(|e| message(e)).collect();
+        Self{owned, matches, errors}
+    }
+}
+
+/// # Safety
+/// `args` must point to `n_args` NUL-terminated strings (or may be NULL if `n_args` is 0)
+#[no_mangle]
+pub unsafe extern "C" fn jrep_options_new(args: *const *const c_char, n_args: usize) -> *mut JrepOptions {
+    catch_panic(ptr::null_mut(), || {
+        let opts = str_args(args, n_args, "argument")
+            .and_then(|args| SearchOptions::from_args(args).map_err(|e| e.to_string()));
+        match opts {
+            Ok(opts) => Box::into_raw(Box::new(JrepOptions(Searcher::new(opts)))),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `options` must be NULL or come from `jrep_options_new`, and not have been freed already
+#[no_mangle]
+pub unsafe extern "C" fn jrep_options_free(options: *mut JrepOptions) {
+    catch_panic((), || {
+        if !options.is_null() {
+            drop(Box::from_raw(options));
+        }
+    })
+}
+
+/// # Safety
+/// `options` must come from `jrep_options_new`, and `paths` must point to `n_paths`
+/// NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn jrep_search_paths(options: *const JrepOptions, paths: *const *const c_char, n_paths: usize) -> *mut JrepResults {
+    catch_panic(ptr::null_mut(), || {
+        let searched = options.as_ref().ok_or_else(|| String::from("The options are NULL"))
+            .and_then(|JrepOptions(searcher)| {
+                let paths = str_args(paths, n_paths, "path")?;
+                let mut found = Vec::new();
+                let mut errors = Vec::new();
+                for m in searcher.search(&paths).map_err(|e| e.to_string())? {
+                    match m {
+                        Ok(m) => found.push(m),
+                        Err(e) => errors.push(e.to_string())
+                    }
+                }
+                Ok(JrepResults::new(&found, errors))
+            });
+        match searched {
+            Ok(results) => Box::into_raw(Box::new(results)),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `options` must come from `jrep_options_new`, `name` must be a NUL-terminated string, and
+/// `data` must point to `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn jrep_search_buffer(options: *const JrepOptions, name: *const c_char, data: *const c_char, len: usize) -> *mut JrepResults {
+    catch_panic(ptr::null_mut(), || {
+        let searched = options.as_ref().ok_or_else(|| String::from("The options are NULL"))
+            .and_then(|JrepOptions(searcher)| {
+                let name = str_arg(name, "name")?;
+                if data.is_null() {
+                    return Err(String::from("The data is NULL"));
+                }
+                let data = std::str::from_utf8(std::slice::from_raw_parts(data as *const u8, len))
+                    .map_err(|_| String::from("The data is not valid UTF-8"))?;
+                let found = searcher.search_text(OsStr::new(name), data).map_err(|e| e.to_string())?;
+                Ok(JrepResults::new(&found, Vec::new()))
+            });
+        match searched {
+            Ok(results) => Box::into_raw(Box::new(results)),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `results` must come from one of the search functions, and not have been freed already
+#[no_mangle]
+pub unsafe extern "C" fn jrep_results_len(results: *const JrepResults) -> usize {
+    catch_panic(0, || {
+        results.as_ref().map_or(0, |r| r.matches.len())
+    })
+}
+
+/// # Safety
+/// As for `jrep_results_len`
+#[no_mangle]
+pub unsafe extern "C" fn jrep_results_get(results: *const JrepResults, i: usize) -> *const JrepMatch {
+    catch_panic(ptr::null(), || {
+        results.as_ref().and_then(|r| r.matches.get(i)).map_or(ptr::null(), |m| m as *const JrepMatch)
+    })
+}
+
+/// # Safety
+/// As for `jrep_results_len`
+#[no_mangle]
+pub unsafe extern "C" fn jrep_results_n_errors(results: *const JrepResults) -> usize {
+    catch_panic(0, || {
+        results.as_ref().map_or(0, |r| r.errors.len())
+    })
+}
+
+/// # Safety
+/// As for `jrep_results_len`
+#[no_mangle]
+pub unsafe extern "C" fn jrep_results_error(results: *const JrepResults, i: usize) -> *const c_char {
+    catch_panic(ptr::null(), || {
+        results.as_ref().and_then(|r| r.errors.get(i)).map_or(ptr::null(), |e| e.as_ptr())
+    })
+}
+
+/// # Safety
+/// `results` must be NULL or come from one of the search functions, and not have been freed already
+#[no_mangle]
+pub unsafe extern "C" fn jrep_results_free(results: *mut JrepResults) {
+    catch_panic((), || {
+        if !results.is_null() {
+            drop(Box::from_raw(results));
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn jrep_last_error() -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+    })
+}
@@ -0,0 +1,138 @@
+//! Python bindings for jrep
+//!
+//! The module has one function, `search`, which runs a [`jrep::Searcher`] over the given paths
+//! and returns every match as a Python `Match` object. The options are given as a dict of
+//! jrep's long command line options, so they always mean the same as they do for the program.
+
+use std::path::PathBuf;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyTuple};
+
+create_exception!(jrep, JrepError, PyException, "Invalid search options, or a notebook that could not be searched");
+
+/// One match found by `search`
+#[pyclass(get_all, frozen, module = "jrep")]
+struct Match {
+    path: String,
+    cell: Option<usize>,
+    cell_id: Option<String>,
+    cell_type: String,
+    execution_count: Option<usize>,
+    tags: Vec<String>,
+    piece: String,
+    section: Option<String>,
+    line: String,
+    line_number: usize,
+    file_line: Option<usize>,
+    /// Character (code point) offsets into `line`, as Python indexes strings
+    match_positions: Vec<(usize, usize)>,
+    is_text: bool,
+    pointer: String,
+    mime_type: Option<String>
+}
+
+impl From<jrep::Match> for Match {
+    fn from(m: jrep::Match) -> Self {
+        Self{
+            path: m.path.to_string_lossy().into_owned(),
+            cell: m.cell,
+            cell_id: m.cell_id,
+            cell_type: m.cell_type,
+            execution_count: m.execution_count,
+            tags: m.tags,
+            piece: m.piece,
+            section: m.section,
+            match_positions: char_positions(&m.line, &m.match_positions),
+            line: m.line,
+            line_number: m.line_number,
+            file_line: m.file_line,
+            is_text: m.is_text,
+            pointer: m.pointer,
+            mime_type: m.mime_type
+        }
+    }
+}
+
+/// Convert the byte ranges of `line` that jrep gives to ranges of characters, so that slicing
+/// the Python string with them gives the matched text
+fn char_positions(line: &str, positions: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let to_chars = |byte: usize| line.get(..byte).map_or(byte, |before| before.chars().count());
+    positions.iter().map(|&(start, stop)| (to_chars(start), to_chars(stop))).collect()
+}
+
+#[pymethods]
+impl Match {
+    fn __repr__(&self) -> String {
+        let cell = self.cell.map(|c| c.to_string()).unwrap_or_else(|| String::from("None"));
+        format!("Match(path={:?}, cell={}, piece={:?}, line={:?})", self.path, cell, self.piece, self.line.trim_end())
+    }
+}
+
+/// The command line arguments for the options dict: `{"cell_type": ["code"], "ignore_case": True}`
+/// is `--cell-type code --ignore-case`
+fn option_args(options: Option<&Bound<'_, PyDict>>) -> PyResult<Vec<String>> {
+    let mut args = Vec::new();
+    for (name, value) in options.into_iter().flat_map(|opts| opts.iter()) {
+        let flag = format!("--{}", name.extract::<String>()?.replace('_', "-"));
+        // Checked before anything else since Python's bools are also ints
+        if let Ok(b) = value.downcast::<PyBool>() {
+            if b.is_true() {
+                args.push(flag);
+            }
+        }else if value.is_none() {
+            continue;
+        }else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+            for v in value.try_iter()? {
+                args.push(flag.clone());
+                args.push(v?.str()?.to_string());
+            }
+        }else{
+            args.push(flag);
+            args.push(value.str()?.to_string());
+        }
+    }
+    Ok(args)
+}
+
+/// Search the notebooks in `paths` (files, or directories whose notebooks are searched) for
+/// `pattern` and return a list of Match objects. `options` is a dict of jrep's long command
+/// line options, with "_" in place of "-"; True turns on an option that takes no value, and
+/// a list gives an option several times. Raises JrepError if the options are invalid or no
+/// notebooks are found, or if a notebook cannot be searched, unless `ignore_errors` is true.
+#[pyfunction]
+#[pyo3(signature = (paths, pattern, options = None, ignore_errors = false))]
+fn search(py: Python<'_>, paths: Vec<PathBuf>, pattern: &str, options: Option<&Bound<'_, PyDict>>, ignore_errors: bool) -> PyResult<Vec<Match>> {
+    let mut args = option_args(options)?;
+    args.push(String::from("--"));
+    args.push(String::from(pattern));
+
+    // The search does not touch any Python objects, so other Python threads can run meanwhile
+    let result = py.allow_threads(|| -> Result<Vec<jrep::Match>, jrep::RunErr> {
+        let searcher = jrep::Searcher::new(jrep::SearchOptions::from_args(args)?);
+        let mut matches = Vec::new();
+        for m in searcher.search(&paths)? {
+            match m {
+                Ok(m) => matches.push(m),
+                Err(_) if ignore_errors => {},
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(matches)
+    });
+    match result {
+        Ok(matches) => Ok(matches.into_iter().map(Match::from).collect()),
+        Err(e) => Err(JrepError::new_err(e.to_string()))
+    }
+}
+
+/// Search Jupyter notebooks with jrep
+#[pymodule]
+#[pyo3(name = "jrep")]
+fn jrep_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(search, m)?)?;
+    m.add_class::<Match>()?;
+    m.add("JrepError", m.py().get_type::<JrepError>())?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Removing terminal escape sequences from cell outputs (on unless `--no-strip-ansi`)
+//!
+//! IPython colors tracebacks and many libraries color their console output, so the saved
+//! text is full of sequences like `ESC[0;31m`. These split words apart (so "KeyError"
+//! would not match `\x1b[0;31mKeyError`) and would change the terminal's colors if
+//! printed. The outputs are stripped once after parsing, so matching, highlighting, and
+//! printing all see the same text.
+
+use std::borrow::Cow;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Remove ANSI escape sequences from `text`. Only allocates if there is something to remove.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains(ESC) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(strip(text, None))
+}
+
+/// `text` without its escape sequences, adding the (stripped offset, original offset) of the
+/// end of each one to `boundaries` if given
+fn strip(text: &str, mut boundaries: Option<&mut Vec<(usize, usize)>>) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if c != ESC {
+            stripped.push(c);
+            continue;
+        }
+
+        match chars.next().map(|(_, c)| c) {
+            // Control sequence (colors, cursor movement): parameters and intermediate
+            // bytes, ended by a byte in '@'..='~'
+            Some('[') => {
+                for (_, c) in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            },
+            // Operating system command (e.g. hyperlinks, window titles): ended by BEL or ESC \
+            Some(']') => {
+                while let Some((_, c)) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }else if c == ESC && chars.peek().map(|&(_, c)| c) == Some('\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+            // Other escapes are ESC plus a single character
+            _ => {}
+        }
+        if let Some(boundaries) = boundaries.as_mut() {
+            let end = chars.peek().map_or(text.len(), |&(i, _)| i);
+            boundaries.push((stripped.len(), end));
+        }
+    }
+    stripped
+}
+
+/// A line without its escape sequences, along with where they were, so that byte offsets in
+/// the stripped text can be mapped back to the original line (as for `NormalizedLine`)
+pub struct StrippedLine<'a> {
+    text: Cow<'a, str>,
+    // (stripped offset, original offset) pairs after each escape sequence. Empty if there
+    // were none.
+    boundaries: Vec<(usize, usize)>
+}
+
+impl<'a> StrippedLine<'a> {
+    pub fn new(line: &'a str) -> Self {
+        if !line.contains(ESC) {
+            return Self::unchanged(line);
+        }
+        let mut boundaries = Vec::new();
+        let text = strip(line, Some(&mut boundaries));
+        Self{text: Cow::Owned(text), boundaries}
+    }
+
+    /// The line as it is, for text that is not stripped
+    pub fn unchanged(line: &'a str) -> Self {
+        Self{text: Cow::Borrowed(line), boundaries: Vec::new()}
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Convert a (start, stop) span in the stripped text to the span in the original line.
+    /// Escape sequences inside the span are part of it, but those just before or after it
+    /// are not.
+    pub fn to_original(&self, span: (usize, usize)) -> (usize, usize) {
+        let (start, stop) = span;
+        let orig_start = match self.boundaries.iter().rev().find(|&&(stripped, _)| stripped <= start) {
+            Some(&(stripped, orig)) => orig + start - stripped,
+            None => start
+        };
+        let orig_stop = match self.boundaries.iter().rev().find(|&&(stripped, _)| stripped < stop) {
+            Some(&(stripped, orig)) => orig + stop - stripped,
+            None => stop
+        };
+        (orig_start, orig_stop.max(orig_start))
+    }
+}
+
+/// Strip `text` in place, leaving it untouched if it has no escape sequences.
+pub fn strip_ansi_in_place(text: &mut String) {
+    if let Cow::Owned(stripped) = strip_ansi(text) {
+        *text = stripped;
+    }
+}
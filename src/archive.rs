@@ -0,0 +1,174 @@
+//! Searching the notebooks inside archives without extracting them
+//!
+//! A notebook in an archive is named by the archive's path, a '!', and its path inside the
+//! archive, e.g. "handouts.zip!week1/intro.ipynb", and found with an [`ArchiveMember`] saying
+//! where it is. Reading it (see `read_notebook_data`) opens the archive and reads only that
+//! entry into memory, which is then searched just like a notebook piped to stdin.
+//! Zip archives can go straight to the entry. Tar archives have no index, so finding an
+//! entry means decompressing everything before it; instead, the notebooks in a tar archive
+//! are read into memory while it is listed, in one pass, and each one's text is kept with it.
+
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use flate2::read::GzDecoder;
+use crate::{FoundNotebook, RunErr, SearchOptions, Source};
+
+/// Separates the archive's path from the path inside it
+const MEMBER_SEPARATOR: char = '!';
+
+/// Where a notebook in an archive is
+#[derive(Debug)]
+pub struct ArchiveMember {
+    archive: PathBuf,
+    inner: String,
+    /// The text of a notebook in a tar archive, read while listing it
+    data: Option<String>
+}
+
+/// The kinds of archives jrep can search
+enum Kind {
+    Zip,
+    Tar,
+    TarGz
+}
+
+impl Kind {
+    /// The kind of archive `path` is by its extension, ignoring case
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Kind::Zip)
+        }else if name.ends_with(".tar") {
+            Some(Kind::Tar)
+        }else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Kind::TarGz)
+        }else{
+            None
+        }
+    }
+}
+
+/// Whether `path` has the extension of an archive jrep can search
+pub fn is_archive(path: &Path) -> bool {
+    Kind::of(path).is_some()
+}
+
+/// The notebooks in the archive at `path` that would be searched if it were a directory (see
+/// `walk::keeps_member`), in the order they are stored
+pub fn list_notebooks(path: &Path, opts: &SearchOptions) -> Result<Vec<FoundNotebook>, RunErr> {
+    let member = |inner: &str, data: Option<String>| FoundNotebook{
+        name: member_name(path, inner),
+        source: Source::Archive(ArchiveMember{archive: path.to_path_buf(), inner: inner.to_string(), data})
+    };
+    let is_notebook = |inner: &Path| crate::walk::keeps_member(inner, opts);
+
+    let mut found = Vec::new();
+    match Kind::of(path) {
+        Some(Kind::Zip) => {
+            let mut zip = open_zip(path)?;
+            for i in 0..zip.len() {
+                let entry = zip.by_index_raw(i).map_err(|e| archive_error(path, e))?;
+                // Entries whose names would lead outside the archive (e.g. "../x.ipynb") are skipped
+                if entry.is_file() && entry.enclosed_name().is_some_and(|inner| is_notebook(&inner)) {
+                    found.push(member(entry.name(), None));
+                }
+            }
+        },
+        Some(Kind::Tar) | Some(Kind::TarGz) => {
+            let mut tar = open_tar(path)?;
+            for entry in tar.entries().map_err(|e| archive_error(path, e))? {
+                let mut entry = entry.map_err(|e| archive_error(path, e))?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let inner = entry.path().map_err(|e| archive_error(path, e))?.into_owned();
+                // Names that are not UTF-8 could not be looked up again in a zip archive, so are
+                // skipped in tar archives too
+                if let Some(inner_str) = inner.to_str().filter(|_| is_enclosed(&inner) && is_notebook(&inner)) {
+                    // One that is not text is left to fail when it is searched, like any other
+                    let mut data = String::new();
+                    let data = entry.read_to_string(&mut data).ok().map(|_| data);
+                    found.push(member(inner_str, data));
+                }
+            }
+        },
+        None => return Err(RunErr{msg: format!("{:?} is not an archive", path)})
+    }
+    Ok(found)
+}
+
+/// The name of the notebook at `inner` in the archive at `path`
+fn member_name(path: &Path, inner: &str) -> OsString {
+    let mut name = OsString::from(path);
+    name.push(MEMBER_SEPARATOR.to_string());
+    name.push(inner);
+    name
+}
+
+/// The name of the notebook `member`, with the archive's path renamed by `show` (see
+/// `FoundNotebook::rename_path`)
+pub fn renamed(member: &ArchiveMember, show: &dyn Fn(&Path) -> OsString) -> OsString {
+    member_name(Path::new(&show(&member.archive)), &member.inner)
+}
+
+/// The text of a notebook in an archive
+pub fn read_member(member: &ArchiveMember) -> io::Result<Cow<'_, str>> {
+    if let Some(data) = &member.data {
+        return Ok(Cow::Borrowed(data));
+    }
+    let (path, inner) = (member.archive.as_path(), member.inner.as_str());
+    let invalid = |e: RunErr| io::Error::new(io::ErrorKind::InvalidData, e.msg);
+    let mut data = String::new();
+    match Kind::of(path) {
+        Some(Kind::Zip) => {
+            let mut zip = open_zip(path).map_err(invalid)?;
+            let mut entry = zip.by_name(inner).map_err(|e| invalid(archive_error(path, e)))?;
+            entry.read_to_string(&mut data)?;
+        },
+        Some(Kind::Tar) | Some(Kind::TarGz) => {
+            // Not read while listing because it is not text, so this will fail too, with the reason
+            let mut tar = open_tar(path).map_err(invalid)?;
+            let mut entries = tar.entries()?;
+            let mut entry = loop {
+                match entries.next() {
+                    Some(entry) => {
+                        let entry = entry?;
+                        if entry.path()? == Path::new(inner) {
+                            break entry;
+                        }
+                    },
+                    None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} is not in the archive {:?}", inner, path)))
+                }
+            };
+            entry.read_to_string(&mut data)?;
+        },
+        None => return Err(invalid(RunErr{msg: format!("{:?} is not an archive", path)}))
+    }
+    Ok(Cow::Owned(data))
+}
+
+/// Whether `inner` stays inside the archive when extracted
+fn is_enclosed(inner: &Path) -> bool {
+    inner.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+fn open_zip(path: &Path) -> Result<zip::ZipArchive<File>, RunErr> {
+    let file = File::open(path).map_err(|e| RunErr{msg: format!("Could not open the archive {:?}: {}", path, e)})?;
+    zip::ZipArchive::new(file).map_err(|e| archive_error(path, e))
+}
+
+fn open_tar(path: &Path) -> Result<tar::Archive<Box<dyn Read>>, RunErr> {
+    let file = BufReader::new(File::open(path).map_err(|e| RunErr{msg: format!("Could not open the archive {:?}: {}", path, e)})?);
+    let reader: Box<dyn Read> = match Kind::of(path) {
+        Some(Kind::TarGz) => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn archive_error<E: std::fmt::Display>(path: &Path, e: E) -> RunErr {
+    RunErr{msg: format!("Could not read the archive {:?}: {}", path, e)}
+}
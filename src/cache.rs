@@ -0,0 +1,153 @@
+//! The cache of parsed notebooks, so that searching the same notebooks again skips parsing them
+//!
+//! Each notebook searched is saved, parsed and without the output data that is not text, to its
+//! own file in the user's cache directory ($XDG_CACHE_HOME/jrep, or ~/.cache/jrep). The next
+//! search of that notebook reads the much smaller cached copy instead, as long as the notebook's
+//! modification time and size have not changed. If they have but its content is the same (e.g.
+//! after a checkout), the cached copy is still used, and only its time and size are updated.
+//!
+//! The cache is only used with `--cache`, since the cached copies keep the notebooks' text
+//! outputs, which may hold anything the notebooks printed. Only the user can read the cache
+//! directory. When a search starts, the least recently used notebooks are deleted until the
+//! cache is no larger than `MAX_CACHE_SIZE`.
+//!
+//! Unlike the index of `--build-index`, nothing has to be done to fill the cache, but each
+//! notebook still has to be looked up on disk; the index is faster for a fixed collection.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::index::{self, FileStamp};
+use crate::{rewrite, Notebook, RunErr, SearchOptions};
+
+/// Changes whenever the format of the cached notebooks changes, so that old ones are ignored
+const CACHE_VERSION: u32 = 2;
+
+/// The most space the cached notebooks may take up at the start of a search, in bytes
+const MAX_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    version: u32,
+    /// The absolute path of the notebook, in case two paths give the same file name
+    path: PathBuf,
+    stamp: FileStamp,
+    hash: u64,
+    notebook: Notebook
+}
+
+/// The directory the cache is kept in, or `None` if there is no cache directory for this user
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()).map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").filter(|d| !d.is_empty()).map(|d| Path::new(&d).join(".cache")))?;
+    Some(base.join("jrep"))
+}
+
+pub(crate) struct NotebookCache {
+    dir: PathBuf
+}
+
+impl NotebookCache {
+    /// The cache in `dir`, after deleting the least recently used notebooks in it if there are
+    /// too many
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        let _ = prune(&dir, MAX_CACHE_SIZE);
+        Self{dir}
+    }
+
+    /// The parsed notebook file at `path`, named `filename`, from the cache if it is there and
+    /// up to date, otherwise read and parsed and then saved to the cache. Returns `None` if the cache
+    /// cannot be used for this search, in which case the caller should read the notebook
+    /// itself. Problems with the cache are never errors; the notebook is just not cached.
+    pub(crate) fn notebook(&self, filename: &OsString, path: &Path, opts: &SearchOptions) -> Option<Result<Notebook, RunErr>> {
+        // A notebook that is only readable with --lenient must not be cached for a search
+        // without it, and parsing leniently again is what prints its warnings
+        if !index::has_what_search_needs(opts) || opts.lenient {
+            return None;
+        }
+        let path = std::path::absolute(path).ok()?;
+        let entry_path = self.dir.join(format!("{:016x}.json", index::content_hash(path.as_os_str().as_encoded_bytes())));
+        Some(self.notebook_at(filename, path, &entry_path, opts))
+    }
+
+    fn notebook_at(&self, filename: &OsString, path: PathBuf, entry_path: &Path, opts: &SearchOptions) -> Result<Notebook, RunErr> {
+        let stamp = FileStamp::of(&path)?;
+        let old_entry = fs::read_to_string(entry_path).ok()
+            .and_then(|data| serde_json::from_str::<CacheEntry>(&data).ok())
+            .filter(|entry| entry.version == CACHE_VERSION && entry.path == path);
+        let old_entry = match old_entry {
+            Some(entry) if entry.stamp == stamp => {
+                // The modification time is when the entry was last used, for `prune`
+                let _ = fs::File::options().write(true).open(entry_path).and_then(|f| f.set_modified(SystemTime::now()));
+                return Ok(entry.notebook);
+            },
+            other => other
+        };
+
+        let data = fs::read_to_string(&path)?;
+        let hash = index::content_hash(data.as_bytes());
+        let notebook = match old_entry {
+            Some(entry) if entry.hash == hash => entry.notebook,
+            _ => {
+                let kept_types: Vec<String> = crate::TEXT_OUTPUT_DATA_TYPES.iter().map(|t| String::from(*t)).collect();
+                crate::notebook::parse_notebook_keeping(&data, filename, Some(&kept_types), opts)?
+            }
+        };
+        let entry = CacheEntry{version: CACHE_VERSION, path, stamp, hash, notebook};
+        let _ = self.save(entry_path, &entry);
+        Ok(entry.notebook)
+    }
+
+    fn save(&self, entry_path: &Path, entry: &CacheEntry) -> Result<(), RunErr> {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        // The cached outputs may hold secrets, so other users must not be able to read them
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&self.dir)?;
+        rewrite::write_atomically(entry_path, &serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+}
+
+/// Delete the least recently used notebooks in the cache directory until the rest take up no
+/// more than `max_size` bytes
+fn prune(dir: &Path, max_size: u64) -> io::Result<()> {
+    let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(dir)?.filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), e.path()))
+        })
+        .collect();
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    if total <= max_size {
+        return Ok(());
+    }
+    entries.sort_unstable();
+    for (_, size, path) in entries {
+        if total <= max_size {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+    Ok(())
+}
+
+/// Delete everything in the cache directory, returning how many notebooks were cached
+pub fn clear(dir: &Path) -> io::Result<usize> {
+    let n_entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(Result::ok)
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .count(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e)
+    };
+    fs::remove_dir_all(dir)?;
+    Ok(n_entries)
+}
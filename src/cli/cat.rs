@@ -0,0 +1,123 @@
+//! The `--cat` mode, which prints notebooks to the terminal instead of searching them
+//!
+//! Each cell gets the same header as with --group-cells, followed by its source, indented.
+//! Outputs follow the source: printed text as it is, results and displays as their text
+//! (preferring text/plain), errors as their traceback, and anything else (images, widgets,
+//! HTML without a plain version) as a placeholder naming its MIME types. If jrep was
+//! compiled with syntect and colors are on, the source of code cells is highlighted.
+
+use std::io::{self, Write};
+use termcolor::{ColorSpec, StandardStream};
+#[cfg(feature = "syntect")]
+use termcolor::WriteColor;
+use jrep::{Cell, MatchContext, Notebook, Output};
+use super::{printer, CliOptions};
+#[cfg(feature = "syntect")]
+use super::highlight::SyntaxHighlighter;
+
+pub struct NotebookRenderer {
+    out: StandardStream,
+    #[cfg(feature = "syntect")]
+    highlighter: Option<SyntaxHighlighter>
+}
+
+impl NotebookRenderer {
+    pub fn new(opts: &CliOptions) -> Self {
+        let out = StandardStream::stdout(opts.color_choice);
+        Self{
+            #[cfg(feature = "syntect")]
+            highlighter: if out.supports_color() { Some(SyntaxHighlighter::new()) } else { None },
+            out
+        }
+    }
+
+    /// Print the cells of `nb` that pass the cell filters (--cells, --cell-type, etc.)
+    pub fn print_notebook(&mut self, filename: &std::ffi::OsString, nb: &Notebook, opts: &CliOptions) -> io::Result<()> {
+        let title = match nb.language() {
+            Some(lang) => format!("{} ({})", filename.to_string_lossy(), lang),
+            None => filename.to_string_lossy().into_owned()
+        };
+        printer::write_styled(&mut self.out, &title, &opts.colors.path)?;
+        writeln!(self.out)?;
+
+        for icell in opts.search.searched_cells(nb) {
+            let cell = &nb.cells[icell];
+            writeln!(self.out)?;
+            let ctx = MatchContext{filename, cell, icell: Some(icell), piece: "source", language: nb.language(), section: None};
+            printer::write_styled(&mut self.out, &printer::cell_header(&ctx, opts), &opts.colors.cell)?;
+            writeln!(self.out)?;
+            self.print_source(&ctx)?;
+            for outp in cell.outputs.iter().flatten() {
+                self.print_output(outp, cell)?;
+            }
+        }
+        self.out.flush()
+    }
+
+    #[cfg_attr(not(feature = "syntect"), allow(unused_variables))]
+    fn print_source(&mut self, ctx: &MatchContext) -> io::Result<()> {
+        for (iline, line) in ctx.cell.source.iter().enumerate() {
+            let line = line.trim_end_matches(['\n', '\r']);
+            #[cfg(feature = "syntect")]
+            let tokens = self.highlighter.as_mut().and_then(|h| h.line_tokens(ctx, iline));
+            #[cfg(not(feature = "syntect"))]
+            let tokens = None;
+            write!(self.out, "    ")?;
+            printer::write_highlighted(&mut self.out, line, 0..line.len(), tokens)?;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    fn print_output(&mut self, outp: &Output, cell: &Cell) -> io::Result<()> {
+        let (label, lines) = match outp.output_type.as_str() {
+            "stream" => (outp.name.clone().unwrap_or_else(|| String::from("stream")), outp.text.clone().unwrap_or_default()),
+            "error" => {
+                let label = format!("error: {}", outp.ename.as_deref().unwrap_or("?"));
+                // Each frame of the traceback may have several lines
+                let lines = outp.traceback.iter().flatten().flat_map(|frame| frame.lines().map(String::from)).collect();
+                (label, lines)
+            },
+            _ => {
+                let label = match (outp.output_type.as_str(), cell.execution_count) {
+                    ("execute_result", Some(n)) => format!("out [{}]", n),
+                    ("execute_result", None) => String::from("out"),
+                    _ => String::from("display")
+                };
+                (label, data_lines(outp))
+            }
+        };
+
+        let mut header_spec = ColorSpec::new();
+        header_spec.set_dimmed(true);
+        write!(self.out, "  ")?;
+        printer::write_styled(&mut self.out, &format!("--- {} ---", label), &header_spec)?;
+        writeln!(self.out)?;
+        for line in lines.iter() {
+            writeln!(self.out, "    {}", line.trim_end_matches(['\n', '\r']))?;
+        }
+        Ok(())
+    }
+}
+
+/// The text to show for an execute_result or display_data output: a placeholder naming the
+/// types that are not text (e.g. "[image/png]"), if any, then the first text type in the
+/// order of TEXT_OUTPUT_DATA_TYPES (so text/plain if there is one)
+fn data_lines(outp: &Output) -> Vec<String> {
+    let data = match &outp.data {
+        Some(data) => data,
+        None => return Vec::new()
+    };
+    let mut lines = Vec::new();
+    let mut other_types: Vec<&str> = data.keys().map(|k| k.as_str()).filter(|t| !jrep::is_text(t)).collect();
+    if !other_types.is_empty() {
+        other_types.sort_unstable();
+        lines.push(format!("[{}]", other_types.join(", ")));
+    }
+    match jrep::TEXT_OUTPUT_DATA_TYPES.iter().find_map(|t| data.get(*t)) {
+        Some(serde_json::Value::String(s)) => lines.extend(s.lines().map(String::from)),
+        Some(serde_json::Value::Array(text)) => lines.extend(text.iter().filter_map(|l| l.as_str()).map(String::from)),
+        _ => {}
+    }
+    lines
+}
@@ -0,0 +1,95 @@
+//! Customizable colors for the `--colors` option
+//!
+//! Each spec has the form `{type}:{attribute}:{value}`, following ripgrep. The type is
+//! the part of the output to style (`match`, `path`, or `cell`), the attribute is `fg`,
+//! `bg`, or `style`, and the value is a color or style name. `{type}:none` clears every
+//! setting for that type, so that e.g. `match:none` followed by `match:style:underline`
+//! underlines matches without coloring them. Later specs override earlier ones.
+
+use termcolor::{Color, ColorSpec};
+use jrep::RunErr;
+
+#[derive(Clone)]
+pub struct OutputColors {
+    /// The matched text itself
+    pub matched: ColorSpec,
+    /// Notebook file names
+    pub path: ColorSpec,
+    /// Cell headers and the cell/line information before each match
+    pub cell: ColorSpec
+}
+
+impl Default for OutputColors {
+    fn default() -> Self {
+        let mut matched = ColorSpec::new();
+        matched.set_fg(Some(Color::Red)).set_bold(true);
+        Self{matched, path: ColorSpec::new(), cell: ColorSpec::new()}
+    }
+}
+
+impl OutputColors {
+    /// Start from the default colors and apply each spec in order.
+    pub fn from_specs<'a, I: Iterator<Item = &'a str>>(specs: I) -> Result<Self, RunErr> {
+        let mut colors = Self::default();
+        for spec in specs {
+            colors.apply(spec)?;
+        }
+        Ok(colors)
+    }
+
+    fn apply(&mut self, spec: &str) -> Result<(), RunErr> {
+        let invalid = |why: &str| RunErr::from(format!("Invalid --colors spec '{}': {}", spec, why));
+        let parts: Vec<&str> = spec.split(':').collect();
+
+        let style = match parts[0] {
+            "match" => &mut self.matched,
+            "path" => &mut self.path,
+            "cell" => &mut self.cell,
+            _ => return Err(invalid("the type must be 'match', 'path', or 'cell'"))
+        };
+
+        match parts[1..] {
+            ["none"] => *style = ColorSpec::new(),
+            ["fg", value] => { style.set_fg(Some(parse_color(value).ok_or_else(|| invalid("unknown color"))?)); },
+            ["bg", value] => { style.set_bg(Some(parse_color(value).ok_or_else(|| invalid("unknown color"))?)); },
+            ["style", value] => match value {
+                "bold" => { style.set_bold(true); },
+                "nobold" => { style.set_bold(false); },
+                "underline" => { style.set_underline(true); },
+                "nounderline" => { style.set_underline(false); },
+                "italic" => { style.set_italic(true); },
+                "noitalic" => { style.set_italic(false); },
+                _ => return Err(invalid("the style must be one of bold, underline, italic, or those prefixed with 'no'"))
+            },
+            [_, _] => return Err(invalid("the attribute must be 'fg', 'bg', or 'style'")),
+            _ => return Err(invalid("expected {type}:{attribute}:{value} or {type}:none"))
+        }
+        Ok(())
+    }
+}
+
+/// A color name, optionally prefixed with "bright-", or an ANSI color number from 0 to 255
+fn parse_color(name: &str) -> Option<Color> {
+    if let Ok(n) = name.parse::<u8>() {
+        return Some(Color::Ansi256(n));
+    }
+
+    let (bright, base) = match name.strip_prefix("bright-") {
+        Some(base) => (true, base),
+        None => (false, name)
+    };
+    let (color, number) = match base {
+        "black" => (Color::Black, 0),
+        "red" => (Color::Red, 1),
+        "green" => (Color::Green, 2),
+        "yellow" => (Color::Yellow, 3),
+        "blue" => (Color::Blue, 4),
+        "magenta" => (Color::Magenta, 5),
+        "cyan" => (Color::Cyan, 6),
+        "white" => (Color::White, 7),
+        _ => return None
+    };
+    // termcolor's "intense" flag would brighten the foreground and background together,
+    // so bright colors are given by number instead: they are the next eight after the basic ones.
+    Some(if bright { Color::Ansi256(number + 8) } else { color })
+}
@@ -0,0 +1,121 @@
+//! The `--dupes` mode, which finds cells copied between notebooks
+//!
+//! Each cell's source is normalized (whitespace at the ends of lines and runs of spaces
+//! inside them are ignored, as are blank lines), and cells with the same normalized source
+//! are grouped. With `--similarity`, groups whose sources share enough of their lines are
+//! merged too, so that a helper with one line changed still turns up. Only groups that
+//! span more than one notebook are reported, since the point is to find code that has
+//! been pasted around rather than repeated within one notebook.
+
+use std::collections::{BTreeMap, HashSet};
+use serde::Serialize;
+use jrep::{Notebook, SearchOptions};
+
+#[derive(Serialize, Clone)]
+pub struct CellLocation {
+    pub path: String,
+    pub cell: usize
+}
+
+#[derive(Serialize)]
+pub struct DuplicateGroup {
+    pub cells: Vec<CellLocation>,
+    /// Whether every cell in the group has the same normalized source
+    pub identical: bool,
+    /// The first line of the source, to recognize it by
+    pub first_line: String
+}
+
+/// Collects the cells of every notebook, then groups them in `groups`
+pub struct DupeFinder {
+    min_lines: usize,
+    /// Normalized source to the cells that have it, sorted so the output is stable
+    sources: BTreeMap<Vec<String>, Vec<CellLocation>>
+}
+
+impl DupeFinder {
+    pub fn new(min_lines: usize) -> Self {
+        Self{min_lines, sources: BTreeMap::new()}
+    }
+
+    /// Add the cells of `nb` that pass the cell filters and have at least the minimum number of lines
+    pub fn add_notebook(&mut self, path: &str, nb: &Notebook, opts: &SearchOptions) {
+        for icell in opts.searched_cells(nb) {
+            let cell = &nb.cells[icell];
+            let normalized: Vec<String> = cell.source.iter()
+                .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|line| !line.is_empty())
+                .collect();
+            if normalized.len() >= self.min_lines {
+                self.sources.entry(normalized).or_default().push(CellLocation{path: String::from(path), cell: icell});
+            }
+        }
+    }
+
+    /// The groups of duplicated cells that appear in more than one notebook. If `similarity`
+    /// is given, sources whose sets of lines have at least that Jaccard similarity are grouped
+    /// together as well (this compares every pair of distinct sources, so it is slower).
+    pub fn groups(self, similarity: Option<f64>) -> Vec<DuplicateGroup> {
+        let sources: Vec<(Vec<String>, Vec<CellLocation>)> = self.sources.into_iter().collect();
+
+        // Union-find over the distinct sources
+        let mut parent: Vec<usize> = (0..sources.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        if let Some(threshold) = similarity {
+            let line_sets: Vec<HashSet<&str>> = sources.iter()
+                .map(|(lines, _)| lines.iter().map(String::as_str).collect())
+                .collect();
+            for i in 0..sources.len() {
+                for j in i+1..sources.len() {
+                    if jaccard(&line_sets[i], &line_sets[j]) >= threshold {
+                        let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                        parent[rj] = ri;
+                    }
+                }
+            }
+        }
+
+        let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for i in 0..sources.len() {
+            let r = root(&mut parent, i);
+            members.entry(r).or_default().push(i);
+        }
+
+        members.into_values().filter_map(|isources| {
+            let cells: Vec<CellLocation> = isources.iter().flat_map(|&i| sources[i].1.iter().cloned()).collect();
+            let n_files = cells.iter().map(|c| c.path.as_str()).collect::<HashSet<_>>().len();
+            if n_files < 2 {
+                return None;
+            }
+            let first_line = sources[isources[0]].0.first().cloned().unwrap_or_default();
+            Some(DuplicateGroup{cells, identical: isources.len() == 1, first_line})
+        }).collect()
+    }
+}
+
+fn jaccard(a: &HashSet<&str>, b: &HashSet<&str>) -> f64 {
+    let n_both = a.intersection(b).count();
+    let n_either = a.len() + b.len() - n_both;
+    if n_either == 0 { 1.0 } else { n_both as f64 / n_either as f64 }
+}
+
+/// Print the groups, each as a summary line, the first line of the source, and the cells
+pub fn print_groups(groups: &[DuplicateGroup]) {
+    for (i, group) in groups.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let n_files = group.cells.iter().map(|c| c.path.as_str()).collect::<HashSet<_>>().len();
+        let kind = if group.identical { "identical" } else { "similar" };
+        println!("{} {} cells in {} notebooks: {}", group.cells.len(), kind, n_files, group.first_line);
+        for c in group.cells.iter() {
+            println!("  {}: cell {}", c.path, c.cell);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Writing the source of matching code cells to a script for `--extract-script`
+//!
+//! This wraps the printer for the chosen output format, so the search results are printed
+//! as usual; on the side, the first match in each code cell writes that cell's source to the
+//! script, under a comment saying which notebook and cell it came from. IPython magics and
+//! shell escapes (lines starting with `%` or `!`) are commented out in Python cells so that
+//! the script can still be parsed by linters and editors.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use termcolor::WriteColor;
+use jrep::{MatchContext, MatchedLine, Printer, SearchOptions};
+
+pub struct ScriptExtractor<'a> {
+    inner: Box<dyn Printer + 'a>,
+    script: BufWriter<File>,
+    /// The cells of the current notebook already written to the script
+    written: HashSet<usize>
+}
+
+impl<'a> ScriptExtractor<'a> {
+    pub fn new(inner: Box<dyn Printer + 'a>, path: &std::ffi::OsStr) -> io::Result<Self> {
+        Ok(Self{inner, script: BufWriter::new(File::create(path)?), written: HashSet::new()})
+    }
+
+    fn write_cell(&mut self, ctx: &MatchContext, icell: usize) -> io::Result<()> {
+        let comment = comment_prefix(ctx.language);
+        writeln!(self.script, "{} {}, cell {}", comment, ctx.filename.to_string_lossy(), icell)?;
+        let is_python = ctx.language.is_none_or(|lang| lang.eq_ignore_ascii_case("python"));
+        for line in ctx.cell.source.iter() {
+            let line = line.trim_end_matches(['\n', '\r']);
+            if is_python && (line.starts_with('%') || line.starts_with('!')) {
+                writeln!(self.script, "# {}", line)?;
+            }else{
+                writeln!(self.script, "{}", line)?;
+            }
+        }
+        writeln!(self.script)
+    }
+}
+
+impl Printer for ScriptExtractor<'_> {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, opts: &SearchOptions) -> io::Result<()> {
+        if let Some(icell) = ctx.icell {
+            if ctx.cell.cell_type == "code" && self.written.insert(icell) {
+                self.write_cell(ctx, icell)?;
+            }
+        }
+        self.inner.print_match(ctx, m, opts)
+    }
+
+    fn end_file(&mut self, filename: &std::ffi::OsString, n_matches: usize, opts: &SearchOptions) -> io::Result<()> {
+        self.written.clear();
+        self.inner.end_file(filename, n_matches, opts)
+    }
+
+    fn finish(&mut self, opts: &SearchOptions) -> io::Result<()> {
+        self.script.flush()?;
+        self.inner.finish(opts)
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.inner.writer()
+    }
+}
+
+/// The line comment marker for a kernel language; "#" for Python, R, Julia, and anything unknown
+fn comment_prefix(language: Option<&str>) -> &'static str {
+    match language.map(|l| l.to_ascii_lowercase()).as_deref() {
+        Some("javascript" | "typescript" | "rust" | "c" | "c++" | "java" | "scala" | "go" | "kotlin" | "c#" | "f#" | "swift") => "//",
+        Some("sql" | "haskell" | "lua") => "--",
+        Some("matlab" | "octave") => "%",
+        _ => "#"
+    }
+}
@@ -0,0 +1,69 @@
+//! Syntax highlighting of code cells for the `--syntax-highlight` option
+//!
+//! Highlighting a single line out of context goes wrong inside multi-line strings and
+//! comments, so the whole source of a cell is highlighted the first time one of its
+//! lines is printed, and kept until a match in a different cell is printed.
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use termcolor::{Color, ColorSpec};
+use jrep::MatchContext;
+use super::printer::Token;
+
+pub struct SyntaxHighlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+    // The file and cell index of the cached cell, and the tokens of each of its lines
+    cache: Option<(std::ffi::OsString, Option<usize>, Vec<Vec<Token>>)>
+}
+
+impl SyntaxHighlighter {
+    pub fn new() -> Self {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let mut themes = ThemeSet::load_defaults();
+        // This theme is always included in syntect's defaults
+        let theme = themes.themes.remove("base16-ocean.dark").unwrap();
+        Self{syntaxes, theme, cache: None}
+    }
+
+    /// The tokens of line `iline` of the source of the cell in `ctx`, or `None` if the
+    /// cell is not a code cell or its language is not known.
+    pub fn line_tokens(&mut self, ctx: &MatchContext, iline: usize) -> Option<&[Token]> {
+        if ctx.cell.cell_type != "code" || ctx.piece != "source" {
+            return None;
+        }
+
+        let is_cached = matches!(&self.cache, Some((f, i, _)) if f == ctx.filename && *i == ctx.icell);
+        if !is_cached {
+            let lines = self.highlight_cell(ctx)?;
+            self.cache = Some((ctx.filename.clone(), ctx.icell, lines));
+        }
+        self.cache.as_ref().and_then(|(_, _, lines)| lines.get(iline)).map(|t| t.as_slice())
+    }
+
+    fn highlight_cell(&self, ctx: &MatchContext) -> Option<Vec<Vec<Token>>> {
+        let syntax = self.syntaxes.find_syntax_by_token(ctx.language?)?;
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+
+        let mut lines = Vec::with_capacity(ctx.cell.source.len());
+        for line in ctx.cell.source.iter() {
+            // A failure here means the syntax definition itself is broken, so give up on the cell
+            let regions = highlighter.highlight_line(line, &self.syntaxes).ok()?;
+            let mut tokens = Vec::with_capacity(regions.len());
+            let mut start = 0;
+            for (style, text) in regions {
+                let mut spec = ColorSpec::new();
+                let fg = style.foreground;
+                spec.set_fg(Some(Color::Rgb(fg.r, fg.g, fg.b)))
+                    .set_bold(style.font_style.contains(FontStyle::BOLD))
+                    .set_italic(style.font_style.contains(FontStyle::ITALIC))
+                    .set_underline(style.font_style.contains(FontStyle::UNDERLINE));
+                tokens.push((spec, start..start + text.len()));
+                start += text.len();
+            }
+            lines.push(tokens);
+        }
+        Some(lines)
+    }
+}
@@ -0,0 +1,109 @@
+//! Notebook hygiene checks for the `--lint` mode
+//!
+//! Each rule has an ID (such as `not-executed`) that is printed with every problem it finds
+//! and that `--lint-rule` and `--lint-ignore` take to choose which rules run. Rules either
+//! look at one cell at a time or at the notebook as a whole; the per-cell rules only see
+//! the cells that pass the cell filters (--cell-type, --cells, --tag, etc.).
+
+use serde::Serialize;
+use jrep::{Cell, Notebook, RunErr, SearchOptions};
+
+/// The rule IDs and what each one checks, in the order they are run
+pub const RULES: [(&str, &str); 5] = [
+    ("no-kernelspec", "the notebook has no kernelspec in its metadata"),
+    ("out-of-order", "a code cell has a lower execution count than one above it"),
+    ("not-executed", "a code cell with source has never been run"),
+    ("empty-cell", "a cell has no source, or only whitespace"),
+    ("large-output", "a cell's outputs are larger than --max-output-kb"),
+];
+
+pub struct LintConfig {
+    rules: Vec<&'static str>,
+    max_output_bytes: usize
+}
+
+impl LintConfig {
+    /// Run the rules in `select` (all of them if empty) except those in `ignore`. Unknown
+    /// rule IDs are an error, so that a typo does not silently turn a rule off.
+    pub fn new<'a>(select: impl Iterator<Item=&'a str>, ignore: impl Iterator<Item=&'a str>, max_output_kb: usize) -> Result<Self, RunErr> {
+        let select = select.map(rule_id).collect::<Result<Vec<_>, _>>()?;
+        let ignore = ignore.map(rule_id).collect::<Result<Vec<_>, _>>()?;
+        let rules = RULES.iter()
+            .map(|&(id, _)| id)
+            .filter(|id| select.is_empty() || select.contains(id))
+            .filter(|id| !ignore.contains(id))
+            .collect();
+        Ok(Self{rules, max_output_bytes: max_output_kb * 1024})
+    }
+
+    fn runs(&self, rule: &str) -> bool {
+        self.rules.contains(&rule)
+    }
+}
+
+fn rule_id(id: &str) -> Result<&'static str, RunErr> {
+    RULES.iter().map(|&(rule, _)| rule).find(|&rule| rule == id).ok_or_else(|| {
+        let known: Vec<&str> = RULES.iter().map(|&(rule, _)| rule).collect();
+        RunErr::from(format!("Unknown lint rule '{}' (the rules are: {})", id, known.join(", ")))
+    })
+}
+
+/// One problem found in a notebook. `cell` is None for problems with the notebook as a whole.
+#[derive(Serialize)]
+pub struct Finding {
+    pub path: String,
+    pub rule: &'static str,
+    pub cell: Option<usize>,
+    pub message: String
+}
+
+/// Run the configured rules on one notebook
+pub fn lint_notebook(path: &str, nb: &Notebook, config: &LintConfig, opts: &SearchOptions) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut report = |rule: &'static str, cell: Option<usize>, message: String| {
+        if config.runs(rule) {
+            findings.push(Finding{path: String::from(path), rule, cell, message});
+        }
+    };
+
+    if nb.metadata.kernelspec.is_none() {
+        report("no-kernelspec", None, String::from("no kernelspec in the notebook metadata"));
+    }
+
+    // The highest execution count so far, and the cell it was in
+    let mut last_exec: Option<(usize, usize)> = None;
+    for icell in opts.searched_cells(nb) {
+        let cell = &nb.cells[icell];
+
+        let blank = is_blank(cell);
+        if blank {
+            report("empty-cell", Some(icell), format!("empty {} cell", cell.cell_type));
+        }
+
+        if cell.cell_type == "code" {
+            match (cell.execution_count, last_exec) {
+                (Some(n), Some((last, last_cell))) if n <= last => {
+                    report("out-of-order", Some(icell), format!("execution count {} is not after {} in cell {}", n, last, last_cell));
+                },
+                (Some(n), _) => last_exec = Some((n, icell)),
+                (None, _) if !blank => report("not-executed", Some(icell), String::from("code cell has not been run")),
+                (None, _) => {}
+            }
+        }
+
+        let output_bytes: usize = cell.outputs.iter().flatten()
+            .flat_map(super::nbstats::output_sizes)
+            .map(|(_, n)| n)
+            .sum();
+        if output_bytes > config.max_output_bytes {
+            report("large-output", Some(icell), format!("outputs are {} (limit {})",
+                super::nbstats::format_bytes(output_bytes), super::nbstats::format_bytes(config.max_output_bytes)));
+        }
+    }
+
+    findings
+}
+
+fn is_blank(cell: &Cell) -> bool {
+    cell.source.iter().all(|line| line.trim().is_empty())
+}
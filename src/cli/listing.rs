@@ -0,0 +1,62 @@
+//! The `--list-types` mode, which prints what is in each notebook instead of searching it
+//!
+//! For each cell this gives its index, type, execution count, and outputs, with the
+//! MIME types of the data in each output, followed by how many outputs had each type.
+//! Those are the values `--cell-type`, `--output-kind`, and `--output-type` take.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use jrep::{Notebook, Output, SearchOptions};
+
+/// Print the table of cells and outputs for one notebook. Only cells that pass the cell
+/// filters (--cell-type, --cells, --tag, etc.) are listed.
+pub fn print_types(filename: &OsString, nb: &Notebook, opts: &SearchOptions) {
+    let mut rows = vec![[String::from("cell"), String::from("type"), String::from("exec"), String::from("outputs")]];
+    // How many outputs contain each data type, sorted so the summary is the same every time
+    let mut type_counts: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for icell in opts.searched_cells(nb) {
+        let cell = &nb.cells[icell];
+
+        let outputs: Vec<String> = cell.outputs.iter().flatten().map(describe_output).collect();
+        for outp in cell.outputs.iter().flatten() {
+            for dtype in outp.data.iter().flat_map(|d| d.keys()) {
+                *type_counts.entry(dtype).or_insert(0) += 1;
+            }
+        }
+        let exec = cell.execution_count.map(|n| n.to_string()).unwrap_or_default();
+        rows.push([icell.to_string(), cell.cell_type.clone(), exec, outputs.join("; ")]);
+    }
+
+    match nb.language() {
+        Some(lang) => println!("{} ({})", filename.to_string_lossy(), lang),
+        None => println!("{}", filename.to_string_lossy())
+    }
+    let widths: Vec<usize> = (0..3).map(|i| rows.iter().map(|r| r[i].len()).max().unwrap_or(0)).collect();
+    for row in rows.iter() {
+        let line = format!("  {:w0$}  {:w1$}  {:w2$}  {}", row[0], row[1], row[2], row[3], w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+        println!("{}", line.trim_end());
+    }
+
+    if !type_counts.is_empty() {
+        let counts: Vec<String> = type_counts.iter().map(|(t, n)| format!("{} ({})", t, n)).collect();
+        println!("  output types: {}", counts.join(", "));
+    }
+}
+
+/// e.g. "stream (stdout)", "error (KeyError)", or "display_data (image/png, text/plain)"
+fn describe_output(outp: &Output) -> String {
+    let detail = match outp.output_type.as_str() {
+        "stream" => outp.name.clone(),
+        "error" => outp.ename.clone(),
+        _ => outp.data.as_ref().map(|data| {
+            let mut types: Vec<&str> = data.keys().map(|k| k.as_str()).collect();
+            types.sort_unstable();
+            types.join(", ")
+        })
+    };
+    match detail {
+        Some(detail) if !detail.is_empty() => format!("{} ({})", outp.output_type, detail),
+        _ => outp.output_type.clone()
+    }
+}
@@ -0,0 +1,362 @@
+//! The command line interface: parsing the arguments, and searching or doing whatever else they
+//! ask for with the library
+
+mod cat;
+mod colors;
+mod dupes;
+mod extract;
+#[cfg(feature = "syntect")]
+mod highlight;
+mod lint;
+mod listing;
+mod modes;
+mod nbstats;
+mod open;
+mod options;
+mod parallel;
+mod printer;
+mod report;
+mod rpc;
+mod serve;
+mod template;
+mod toc;
+mod validate;
+
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use termcolor::ColorChoice;
+use jrep::{FoundNotebook, Notebook, Printer, Replacement, RunErr, SearchOptions, Searched, Source, STDIN_PATH};
+use options::CliOptions;
+use printer::OutputFormat;
+
+// Still to implement:
+//  * Command line interface (probably use `clap`)
+//      - x Source only/certain output types only
+//      - x Color/no color. Default to color unless stdout is redirected
+//      - Count only (no matching)
+//      - x Include cell number/cell execution count/line in cell
+//      - x Case insensitive
+//      - x Invert matching
+//      - x With filename/without filename
+//      - x Multiple files
+//      - Recursive/include by glob pattern
+//      - Maybe context lines/print whole cell?
+//  * x Limiting to certain output types
+//  * x Binary output match/no match
+//  * Counting matches
+//  * x Printing cell information
+//  * x Case insensitivity
+//  * x Iterating over multiple files
+//  * Recursive searching
+//  * x Alternate mode that prints out the type of each cell and of each output, so that users
+//    can figure out what output types they have more easily.
+
+/// Run jrep with the arguments this process was started with, then exit
+pub fn run() {
+    let (mut paths, opts) = match parse_clargs() {
+        Ok((p,o)) => (p,o),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(exitcode::USAGE);
+        }
+    };
+
+    if opts.clear_cache {
+        std::process::exit(modes::clear_cache());
+    }
+    if let Some(target) = opts.search.remote().filter(|_| opts.remote_jrep) {
+        std::process::exit(target.run_jrep(remote_args(&opts)));
+    }
+    if opts.list_files {
+        paths.iter_mut().for_each(|nb| nb.rename_path(|path| opts.path_style.show(path)));
+        std::process::exit(modes::print_files(&paths, &opts));
+    }
+    if opts.validate {
+        std::process::exit(modes::validate_notebooks(&paths));
+    }
+    if opts.list_types {
+        std::process::exit(modes::list_notebook_types(&paths, &opts));
+    }
+    if opts.notebook_stats {
+        std::process::exit(modes::print_notebook_stats(&paths, &opts));
+    }
+    if let Some(config) = &opts.lint {
+        std::process::exit(modes::lint_notebooks(&paths, config, &opts));
+    }
+    if opts.cat {
+        std::process::exit(modes::cat_notebooks(&paths, &opts));
+    }
+    if opts.toc {
+        std::process::exit(modes::print_tocs(&paths, &opts));
+    }
+    if opts.dupes {
+        std::process::exit(modes::print_dupes(&paths, &opts));
+    }
+    if opts.build_index {
+        std::process::exit(modes::build_index(&paths, &opts));
+    }
+    #[cfg(feature = "tantivy")]
+    if opts.fulltext {
+        std::process::exit(modes::fulltext_search(&opts));
+    }
+    if opts.rpc {
+        std::process::exit(rpc::run(&paths, &opts));
+    }
+    if opts.serve {
+        std::process::exit(serve::serve(&paths, opts));
+    }
+    if let Some(template) = &opts.replace {
+        let rep = Replacement{template, expand: true, outputs: opts.replace_outputs};
+        let edit = |data: &str, nb: &Notebook| jrep::replace_in_notebook(data, nb, &rep, &opts.search);
+        std::process::exit(modes::rewrite_notebooks(&paths, edit, "replacement(s)", None, &opts));
+    }
+    if let Some(out_dir) = &opts.redact_dir {
+        let rep = Replacement{template: &opts.mask, expand: false, outputs: true};
+        let edit = |data: &str, nb: &Notebook| jrep::replace_in_notebook(data, nb, &rep, &opts.search);
+        std::process::exit(modes::rewrite_notebooks(&paths, edit, "replacement(s)", Some(Path::new(out_dir)), &opts));
+    }
+    if opts.strip_outputs {
+        let edit = |data: &str, nb: &Notebook| jrep::strip_outputs(data, nb, opts.strip_all, &opts.search);
+        std::process::exit(modes::rewrite_notebooks(&paths, edit, "cell(s) stripped", None, &opts));
+    }
+
+    // Only the results are printed with the paths as --abs-paths or --relative-to asks
+    paths.iter_mut().for_each(|nb| nb.rename_path(|path| opts.path_style.show(path)));
+    let start_time = Instant::now();
+    let mut stats = SearchStats::default();
+    let mut printer = match printer::make_printer(&opts) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Could not open the output file or script: {}", e);
+            std::process::exit(exitcode::CANTCREAT);
+        }
+    };
+    let mut to_open = Vec::new();
+    // Errors are listed after the results rather than mixed in with them
+    let mut file_errors = Vec::new();
+    let mut timed_out = Vec::new();
+    // Returns the number of matches in the notebook
+    let mut finish_file = |notebook: &FoundNotebook, result: Result<Searched<usize>, RunErr>, printer: &mut dyn Printer| -> usize {
+        let filename = &notebook.name;
+        let result = match result {
+            Ok(Searched::Done(n)) => printer.end_file(filename, n, &opts.search).map(|_| n).map_err(RunErr::from),
+            // Filtered notebooks are left out of the output and statistics entirely
+            Ok(Searched::Filtered) => return 0,
+            Ok(Searched::TooLarge) => {
+                stats.files_too_large += 1;
+                return 0;
+            },
+            Ok(Searched::TimedOut) => {
+                stats.files_timed_out += 1;
+                timed_out.push(filename.clone());
+                return 0;
+            },
+            Err(e) => Err(e)
+        };
+        match result {
+            Ok(n) => {
+                stats.add_file(n);
+                // Only files can be opened in an editor
+                if let Source::Local(path) = &notebook.source {
+                    if n > 0 && to_open.len() < opts.open_count.unwrap_or(0) {
+                        to_open.push(path.clone());
+                    }
+                }
+                n
+            },
+            Err(e) => {
+                file_errors.push((filename.clone(), e));
+                stats.files_with_errors += 1;
+                0
+            }
+        }
+    };
+    // With --max-results, how many more matches may be printed. The notebook that reaches the
+    // limit is still searched to the end, but only the matches up to the limit are printed
+    // and counted, and no more notebooks are searched after it.
+    let mut remaining = opts.max_results.unwrap_or(usize::MAX);
+    // A notebook that runs out of time may already have printed some matches, so with a
+    // timeout the matches are always recorded first, even with a single thread
+    if opts.search.threads() > 1 || opts.search.timeout_per_file().is_some() {
+        parallel::search_notebooks(&paths, &opts.search, opts.search.threads(), !opts.unordered, |i, result| {
+            let result = result.and_then(|recorded| match recorded {
+                Searched::Done(nb) => {
+                    let mut limited = printer::LimitedPrinter::new(printer.as_mut(), remaining);
+                    nb.replay(&paths[i].name, &mut limited, &opts.search).map(|n| Searched::Done(n.min(remaining))).map_err(RunErr::from)
+                },
+                other => Ok(other.map(|_| 0))
+            });
+            remaining -= finish_file(&paths[i], result, printer.as_mut());
+            remaining > 0
+        });
+    }else{
+        for notebook in paths.iter() {
+            let mut limited = printer::LimitedPrinter::new(printer.as_mut(), remaining);
+            let result = jrep::search_notebook(notebook, &opts.search, &mut limited).map(|r| r.map(|n| n.min(remaining)));
+            remaining -= finish_file(notebook, result, printer.as_mut());
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+    if let Err(e) = printer.finish(&opts.search) {
+        eprintln!("Error writing results: {}", e);
+        std::process::exit(exitcode::IOERR);
+    }
+
+    if opts.show_stats {
+        stats.elapsed = start_time.elapsed();
+        if let Err(e) = stats.print(printer.writer(), &opts) {
+            eprintln!("Error writing results: {}", e);
+            std::process::exit(exitcode::IOERR);
+        }
+    }
+
+    if !file_errors.is_empty() && !opts.no_messages {
+        eprintln!("{} notebook(s) could not be searched:", file_errors.len());
+        for (filename, e) in file_errors.iter() {
+            eprintln!("  {:?}: {}", filename, e);
+        }
+    }
+    if !timed_out.is_empty() && !opts.no_messages {
+        eprintln!("{} notebook(s) were skipped for taking longer than --timeout-per-file:", timed_out.len());
+        for filename in timed_out.iter() {
+            eprintln!("  {:?}", filename);
+        }
+    }
+
+    if let Err(e) = open::open_notebooks(&to_open) {
+        eprintln!("{}", e);
+        std::process::exit(exitcode::UNAVAILABLE);
+    }
+}
+
+/// The arguments for jrep on the --remote host: the ones this jrep was given, less --remote.
+/// Its output comes back through a pipe, so it is told to color it if this jrep would.
+fn remote_args(opts: &CliOptions) -> Vec<OsString> {
+    let mut args = Vec::new();
+    let mut given = std::env::args_os().skip(1);
+    while let Some(arg) = given.next() {
+        if arg == "--remote" {
+            given.next();
+        }else if arg != "--search-locally" && !arg.to_string_lossy().starts_with("--remote=") {
+            args.push(arg);
+        }
+    }
+    if !args.iter().any(|arg| arg.to_string_lossy().starts_with("--color")) && opts.color_choice != ColorChoice::Never {
+        args.insert(0, OsString::from("--color=always"));
+    }
+    args
+}
+
+fn parse_clargs() -> Result<(Vec<FoundNotebook>, CliOptions), RunErr> {
+    let yml = clap::load_yaml!("../clargs.yml");
+    let clargs = clap::App::from_yaml(yml).version(clap::crate_version!()).get_matches();
+
+    let search = SearchOptions::from_args(std::env::args_os().skip(1))?;
+    let mut opts = CliOptions::from_arg_matches(&clargs, search)?;
+    // --fulltext searches everything in the index and --clear-cache only deletes the cache,
+    // so there is no need to look for notebooks
+    if opts.fulltext || opts.clear_cache {
+        return Ok((Vec::new(), opts));
+    }
+    // Nor if jrep on the --remote host will find and search them
+    if opts.search.remote().is_some_and(|target| !opts.search_locally && target.has_jrep()) {
+        opts.remote_jrep = true;
+        return Ok((Vec::new(), opts));
+    }
+
+    let listed = match clargs.value_of_os("files_from") {
+        Some(source) => read_path_list(source)?,
+        None => Vec::new()
+    };
+    let mut paths_raw = options::path_args(&clargs);
+    paths_raw.extend(listed.iter().map(OsString::as_os_str));
+    if clargs.value_of_os("files_from") == Some(OsStr::new(STDIN_PATH)) && paths_raw.contains(&OsStr::new(STDIN_PATH)) {
+        return Err(RunErr::from("Cannot read both the list of paths (--files-from -) and a notebook (-) from stdin"));
+    }
+
+    // Requests to --rpc can give their own paths, so it is fine if there are no notebooks here
+    if opts.rpc {
+        return Ok((jrep::find_notebooks(&paths_raw, &opts.search).unwrap_or_default(), opts));
+    }
+    let paths = jrep::find_notebooks(&paths_raw, &opts.search)?;
+    Ok((paths, opts))
+}
+
+/// The paths listed in `source` for --files-from: a file, or stdin if it is "-". The paths
+/// are separated by NUL bytes if there are any (as from `find -print0` or `git ls-files -z`),
+/// otherwise one per line. Empty lines are skipped.
+fn read_path_list(source: &OsStr) -> Result<Vec<OsString>, RunErr> {
+    let mut data = Vec::new();
+    let read = if source == "-" {
+        io::stdin().lock().read_to_end(&mut data)
+    }else{
+        File::open(source).and_then(|mut f| f.read_to_end(&mut data))
+    };
+    read.map_err(|e| RunErr::from(format!("Could not read the list of paths from {:?}: {}", source, e)))?;
+
+    let separator = if data.contains(&0) { b'\0' } else { b'\n' };
+    Ok(data.split(|&b| b == separator)
+        .map(|p| if separator == b'\n' { p.strip_suffix(b"\r").unwrap_or(p) } else { p })
+        .filter(|p| !p.is_empty())
+        .map(os_string_from_bytes)
+        .collect())
+}
+
+/// On Unix, paths are taken as raw bytes so that names which are not valid UTF-8 still work
+fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    #[cfg(unix)]
+    return <OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(bytes).to_os_string();
+    #[cfg(not(unix))]
+    return OsString::from(String::from_utf8_lossy(bytes).into_owned());
+}
+
+/// Summary of a whole search, printed at the end with --stats
+#[derive(Default)]
+struct SearchStats {
+    files_searched: usize,
+    files_with_matches: usize,
+    total_matches: usize,
+    files_with_errors: usize,
+    files_too_large: usize,
+    files_timed_out: usize,
+    elapsed: Duration
+}
+
+impl SearchStats {
+    fn add_file(&mut self, n_matches: usize) {
+        self.files_searched += 1;
+        self.total_matches += n_matches;
+        if n_matches > 0 {
+            self.files_with_matches += 1;
+        }
+    }
+
+    fn print(&self, out: &mut dyn std::io::Write, opts: &CliOptions) -> std::io::Result<()> {
+        let too_large = match opts.search.max_filesize() {
+            Some(max_size) => format!("{} files skipped for being larger than {}\n", self.files_too_large, nbstats::format_bytes(max_size as usize)),
+            None => String::new()
+        };
+        let timed_out = match opts.search.timeout_per_file() {
+            Some(timeout) => format!("{} files skipped for taking longer than {} seconds\n", self.files_timed_out, timeout.as_secs_f64()),
+            None => String::new()
+        };
+        let summary = format!(
+            "{} matches\n{} files contained matches\n{} files searched\n{} files could not be searched due to errors\n{}{}{:.3} seconds elapsed",
+            self.total_matches, self.files_with_matches, self.files_searched, self.files_with_errors, too_large, timed_out, self.elapsed.as_secs_f64()
+        );
+
+        // Keep machine-readable output parseable by sending the summary to stderr instead
+        if let OutputFormat::Standard = opts.output_format {
+            writeln!(out, "\n{}", summary)?;
+            out.flush()
+        }else{
+            eprintln!("{}", summary);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! The modes that do something other than search the notebooks, e.g. --toc and --replace
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use jrep::index::NotebookIndex;
+#[cfg(feature = "tantivy")]
+use jrep::fulltext::{self, Hit};
+use jrep::{cache, parse_notebook, FoundNotebook, Notebook, Output, RunErr, Source};
+use super::printer::{self, OutputFormat};
+use super::{cat, dupes, lint, listing, nbstats, toc, validate, CliOptions};
+
+/// Print statistics about each notebook (--notebook-stats) instead of searching, followed by
+/// the totals if there is more than one. Returns the exit code: DATAERR if any notebook could
+/// not be read.
+pub fn print_notebook_stats(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut all_stats = Vec::new();
+    let mut total = nbstats::NotebookStats::default();
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        match notebook.parse(&opts.search) {
+            Ok(nb) => {
+                let stats = nbstats::NotebookStats::new(filename.to_string_lossy().into_owned(), &nb, &opts.search);
+                total.add(&stats);
+                all_stats.push(stats);
+            },
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    match opts.output_format {
+        OutputFormat::Json => {
+            let doc = serde_json::json!({"notebooks": all_stats, "total": total});
+            println!("{}", doc);
+        },
+        OutputFormat::JsonLines => {
+            for stats in all_stats.iter() {
+                println!("{}", serde_json::to_string(stats).unwrap_or_default());
+            }
+        },
+        _ => {
+            for (i, stats) in all_stats.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                stats.print(stats.path.as_deref().unwrap_or_default());
+            }
+            if all_stats.len() > 1 {
+                println!();
+                total.print(&format!("Total ({} notebooks)", all_stats.len()));
+            }
+        }
+    }
+
+    if n_errors > 0 { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Check each notebook against the lint rules (--lint) instead of searching, printing one line
+/// per problem. Returns the exit code: DATAERR if any problems were found or any notebook could
+/// not be read.
+pub fn lint_notebooks(paths: &[FoundNotebook], config: &lint::LintConfig, opts: &CliOptions) -> exitcode::ExitCode {
+    let mut all_findings = Vec::new();
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        match notebook.parse(&opts.search) {
+            Ok(nb) => all_findings.extend(lint::lint_notebook(&filename.to_string_lossy(), &nb, config, &opts.search)),
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    match opts.output_format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&all_findings).unwrap_or_default()),
+        OutputFormat::JsonLines => {
+            for finding in all_findings.iter() {
+                println!("{}", serde_json::to_string(finding).unwrap_or_default());
+            }
+        },
+        _ => {
+            for finding in all_findings.iter() {
+                match finding.cell {
+                    Some(icell) => println!("{}: cell {}: {} [{}]", finding.path, icell, finding.message, finding.rule),
+                    None => println!("{}: {} [{}]", finding.path, finding.message, finding.rule)
+                }
+            }
+        }
+    }
+
+    if !all_findings.is_empty() && !opts.no_messages {
+        eprintln!("{} problem(s) found", all_findings.len());
+    }
+    if n_errors > 0 || !all_findings.is_empty() { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Change each notebook with `edit` and write the changed notebooks back (--replace and
+/// --strip-outputs), or write every notebook, changed or not, to a copy under `out_dir`
+/// (--redact). `edit` returns the new text and how many `changes` it made, which are printed
+/// for each notebook. Returns the exit code: DATAERR if any notebook could not be changed,
+/// CANTCREAT if one could not be written.
+pub fn rewrite_notebooks<F>(paths: &[FoundNotebook], edit: F, changes: &str, out_dir: Option<&Path>, opts: &CliOptions) -> exitcode::ExitCode
+where F: Fn(&str, &Notebook) -> Result<(String, usize), RunErr> {
+    let mut exit_code = exitcode::OK;
+    let mut n_total = 0;
+    let mut n_files = 0;
+    for notebook in paths {
+        let (filename, path) = match &notebook.source {
+            Source::Local(path) => (&notebook.name, path),
+            _ => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: Only notebook files can be changed", notebook.name);
+                }
+                exit_code = exitcode::DATAERR;
+                continue;
+            }
+        };
+        let changed = fs::read_to_string(path).map_err(RunErr::from).and_then(|data| {
+            if !notebook.is_jupyter_format() {
+                return Err(RunErr::from("Only Jupyter notebooks can be changed"));
+            }
+            let nb = parse_notebook(&data)?;
+            edit(&data, &nb)
+        });
+        let (new_data, n) = match changed {
+            Ok(changed) => changed,
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                exit_code = exitcode::DATAERR;
+                continue;
+            }
+        };
+        let dest = match out_dir {
+            Some(dir) => jrep::copy_path(dir, path),
+            None if n == 0 => continue,
+            None => path.clone()
+        };
+        // A copy that would land on the original (e.g. `--redact .`) must not overwrite it
+        let same_file = matches!((fs::canonicalize(path), fs::canonicalize(&dest)), (Ok(a), Ok(b)) if a == b);
+        if out_dir.is_some() && same_file {
+            eprintln!("Not writing the redacted copy of {:?}, since it would replace the original", filename);
+            exit_code = exitcode::CANTCREAT;
+            continue;
+        }
+
+        if !opts.dry_run {
+            let written = match dest.parent() {
+                Some(parent) if out_dir.is_some() => fs::create_dir_all(parent),
+                _ => Ok(())
+            }.and_then(|_| jrep::write_atomically(&dest, &new_data));
+            if let Err(e) = written {
+                eprintln!("Could not write {:?}: {}", dest, e);
+                exit_code = exitcode::CANTCREAT;
+                continue;
+            }
+        }
+        match out_dir {
+            Some(_) => println!("{} -> {}: {} {}", filename.to_string_lossy(), dest.display(), n, changes),
+            None => println!("{}: {} {}", filename.to_string_lossy(), n, changes)
+        }
+        n_total += n;
+        n_files += 1;
+    }
+
+    if opts.dry_run {
+        println!("{} {} in {} notebook(s) (dry run, nothing was written)", n_total, changes, n_files);
+    }
+    exit_code
+}
+
+/// Print each notebook (--cat) instead of searching, separated by blank lines. Returns the
+/// exit code: DATAERR if any notebook could not be read.
+pub fn cat_notebooks(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut renderer = cat::NotebookRenderer::new(opts);
+    let mut n_printed = 0;
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        let printed = notebook.parse(&opts.search).and_then(|mut nb| {
+            if opts.strip_ansi {
+                nb.cells.iter_mut().flat_map(|c| c.outputs.iter_mut().flatten()).for_each(Output::strip_ansi);
+            }
+            if n_printed > 0 {
+                println!();
+            }
+            Ok(renderer.print_notebook(filename, &nb, opts)?)
+        });
+        match printed {
+            Ok(()) => n_printed += 1,
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    if n_errors > 0 { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Print the outline of each notebook's headings (--toc) instead of searching, as text or,
+/// with --json or --json-lines, as JSON. Returns the exit code: DATAERR if any notebook could
+/// not be read.
+pub fn print_tocs(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut all_headings = Vec::new();
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        match notebook.parse(&opts.search) {
+            Ok(nb) => all_headings.push((filename, toc::notebook_headings(&nb, &opts.search))),
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    let as_json = |filename: &std::ffi::OsString, headings: &[toc::Heading]| {
+        serde_json::json!({"path": filename.to_string_lossy(), "headings": headings})
+    };
+    match opts.output_format {
+        OutputFormat::Json => {
+            let docs: Vec<_> = all_headings.iter().map(|(f, h)| as_json(f, h)).collect();
+            println!("{}", serde_json::Value::Array(docs));
+        },
+        OutputFormat::JsonLines => {
+            for (filename, headings) in all_headings.iter() {
+                println!("{}", as_json(filename, headings));
+            }
+        },
+        _ => {
+            for (i, (filename, headings)) in all_headings.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                toc::print_toc(filename, headings);
+            }
+        }
+    }
+
+    if n_errors > 0 { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Find cells duplicated across notebooks (--dupes) instead of searching, and print them as
+/// text or, with --json or --json-lines, as JSON. Returns the exit code: DATAERR if any
+/// notebook could not be read.
+pub fn print_dupes(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut finder = dupes::DupeFinder::new(opts.dupes_min_lines);
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        match notebook.parse(&opts.search) {
+            Ok(nb) => finder.add_notebook(&filename.to_string_lossy(), &nb, &opts.search),
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    let groups = finder.groups(opts.dupes_similarity);
+    match opts.output_format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&groups).unwrap_or_default()),
+        OutputFormat::JsonLines => {
+            for group in groups.iter() {
+                println!("{}", serde_json::to_string(group).unwrap_or_default());
+            }
+        },
+        _ => dupes::print_groups(&groups)
+    }
+
+    if n_errors > 0 { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Delete the cache of parsed notebooks (--clear-cache) instead of searching. Returns the exit
+/// code: CANTCREAT if the cache could not be deleted.
+pub fn clear_cache() -> exitcode::ExitCode {
+    let dir = match cache::cache_dir() {
+        Some(dir) => dir,
+        None => {
+            println!("There is no cache directory");
+            return exitcode::OK;
+        }
+    };
+    match cache::clear(&dir) {
+        Ok(n_entries) => {
+            println!("Removed {} cached notebook(s) from {}", n_entries, dir.display());
+            exitcode::OK
+        },
+        Err(e) => {
+            eprintln!("Could not delete the cache {:?}: {}", dir, e);
+            exitcode::CANTCREAT
+        }
+    }
+}
+
+/// Build or update the index for --use-index (--build-index) instead of searching. Returns the
+/// exit code: DATAERR if any notebook could not be read, or CANTCREAT if the index could not
+/// be written.
+pub fn build_index(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let index_path = Path::new(&opts.index_file);
+    // An index that is missing, unreadable, or from another version is just built from scratch
+    let old = NotebookIndex::load(index_path).ok();
+    let (new_index, summary, errors) = NotebookIndex::build(old, paths, &opts.search);
+    if !opts.no_messages {
+        for (filename, e) in errors.iter() {
+            eprintln!("Error in file {:?}: {}", filename, e);
+        }
+    }
+    if let Err(e) = new_index.save(index_path) {
+        eprintln!("Could not write the index {:?}: {}", index_path, e);
+        return exitcode::CANTCREAT;
+    }
+    #[cfg(feature = "tantivy")]
+    {
+        let fulltext_dir = fulltext::index_dir(index_path);
+        if let Err(e) = fulltext::build(&fulltext_dir, &new_index) {
+            eprintln!("Could not write the full-text index {:?}: {}", fulltext_dir, e);
+            return exitcode::CANTCREAT;
+        }
+    }
+
+    println!(
+        "{} notebooks indexed in {}: {} added, {} updated, {} unchanged, {} removed",
+        summary.added + summary.updated + summary.unchanged, index_path.display(),
+        summary.added, summary.updated, summary.unchanged, summary.removed
+    );
+    if errors.is_empty() { exitcode::OK } else { exitcode::DATAERR }
+}
+
+/// Run the pattern as a ranked query over the full-text index (--fulltext) instead of searching,
+/// printing the best matching cells as text or, with --json or --json-lines, as JSON. Returns
+/// the exit code: NOINPUT if the index could not be opened, or DATAERR if the query is invalid.
+#[cfg(feature = "tantivy")]
+pub fn fulltext_search(opts: &CliOptions) -> exitcode::ExitCode {
+    let dir = fulltext::index_dir(Path::new(&opts.index_file));
+    if !dir.is_dir() {
+        eprintln!("There is no full-text index at {:?}; create it with --build-index", dir);
+        return exitcode::NOINPUT;
+    }
+    let hits = match fulltext::search(&dir, opts.search.pattern(), opts.max_results.unwrap_or(10)) {
+        Ok(hits) => hits,
+        Err(e) => {
+            eprintln!("{}", e);
+            return exitcode::DATAERR;
+        }
+    };
+
+    match opts.output_format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&hits).unwrap_or_default()),
+        OutputFormat::JsonLines => {
+            for hit in hits.iter() {
+                println!("{}", serde_json::to_string(hit).unwrap_or_default());
+            }
+        },
+        _ => if let Err(e) = print_hits(&hits, opts) {
+            eprintln!("Error writing results: {}", e);
+            return exitcode::IOERR;
+        }
+    }
+    exitcode::OK
+}
+
+#[cfg(feature = "tantivy")]
+/// Print the hits, best first, each as a header line and its snippet with the matching words highlighted
+fn print_hits(hits: &[Hit], opts: &CliOptions) -> std::io::Result<()> {
+    let mut out = termcolor::StandardStream::stdout(opts.color_choice);
+    for (i, hit) in hits.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        printer::write_styled(&mut out, &hit.path, &opts.colors.path)?;
+        write!(out, ": ")?;
+        printer::write_styled(&mut out, &format!("cell {} ({})", hit.cell, hit.cell_type), &opts.colors.cell)?;
+        writeln!(out, "  score {:.2}", hit.score)?;
+
+        let mut line_start = 0;
+        for line in hit.snippet.split_inclusive('\n') {
+            let line_end = line_start + line.trim_end_matches(['\n', '\r']).len();
+            write!(out, "    ")?;
+            let mut last = line_start;
+            for &(start, end) in hit.highlights.iter() {
+                let (start, end) = (start.max(last), end.min(line_end));
+                if start >= end {
+                    continue;
+                }
+                write!(out, "{}", &hit.snippet[last..start])?;
+                printer::write_styled(&mut out, &hit.snippet[start..end], &opts.colors.matched)?;
+                last = end;
+            }
+            writeln!(out, "{}", &hit.snippet[last..line_end])?;
+            line_start += line.len();
+        }
+    }
+    out.flush()
+}
+
+/// Print the cell and output types of each notebook (--list-types) instead of searching.
+/// Returns the exit code: DATAERR if any notebook could not be read.
+pub fn list_notebook_types(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut n_printed = 0;
+    let mut n_errors = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        match notebook.parse(&opts.search) {
+            Ok(nb) => {
+                if n_printed > 0 {
+                    println!();
+                }
+                listing::print_types(filename, &nb, &opts.search);
+                n_printed += 1;
+            },
+            Err(e) => {
+                if !opts.no_messages {
+                    eprintln!("Error in file {:?}: {}", filename, e);
+                }
+                n_errors += 1;
+            }
+        }
+    }
+
+    if n_errors > 0 { exitcode::DATAERR } else { exitcode::OK }
+}
+
+/// Check each notebook against the nbformat schema (--validate) instead of searching, printing
+/// one line per problem. Returns the exit code: DATAERR if any notebook had problems.
+pub fn validate_notebooks(paths: &[FoundNotebook]) -> exitcode::ExitCode {
+    let mut n_invalid = 0;
+    for notebook in paths {
+        let filename = &notebook.name;
+        let parsed = notebook.read()
+            .map_err(RunErr::from)
+            .and_then(|data| Ok(serde_json::from_str::<serde_json::Value>(&data)?));
+        let violations = match parsed {
+            Ok(nb) => validate::validate_notebook(&nb),
+            Err(e) => vec![validate::Violation{pointer: String::new(), message: format!("could not be read: {}", e)}]
+        };
+
+        for v in violations.iter() {
+            if v.pointer.is_empty() {
+                println!("{}: {}", filename.to_string_lossy(), v.message);
+            }else{
+                println!("{}: {}: {}", filename.to_string_lossy(), v.pointer, v.message);
+            }
+        }
+        if !violations.is_empty() {
+            n_invalid += 1;
+        }
+    }
+
+    if n_invalid > 0 {
+        eprintln!("{} of {} notebooks are not valid", n_invalid, paths.len());
+        exitcode::DATAERR
+    }else{
+        exitcode::OK
+    }
+}
+
+/// Print the notebooks that would be searched (--files) instead of searching them
+pub fn print_files(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let mut out = termcolor::BufferedStandardStream::stdout(opts.color_choice);
+    let printed = paths.iter().try_for_each(|p| printer::print_path(&mut out, &p.name, opts)).and_then(|_| out.flush());
+    match printed {
+        Ok(()) => exitcode::OK,
+        // E.g. piped to `head`, which has all it wants
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => exitcode::OK,
+        Err(e) => {
+            eprintln!("Error printing the notebooks: {}", e);
+            exitcode::IOERR
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-notebook statistics for the `--notebook-stats` mode
+//!
+//! This counts what is in each notebook rather than searching it: cells by type, how
+//! many code cells were run, lines of source, and how much space the outputs take up.
+//! Output sizes are grouped by MIME type, with printed text counted as "stream" and
+//! exceptions as "error", since those outputs have no MIME type. Sizes are the bytes
+//! of the text as stored in the notebook (so images are counted in base64).
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+use jrep::{Notebook, Output, SearchOptions};
+
+#[derive(Serialize, Default)]
+pub struct NotebookStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel: Option<String>,
+    pub cells: usize,
+    pub cells_by_type: BTreeMap<String, usize>,
+    pub executed_cells: usize,
+    pub unexecuted_cells: usize,
+    pub source_lines: usize,
+    pub output_bytes: BTreeMap<String, usize>
+}
+
+impl NotebookStats {
+    /// The statistics of the cells in `nb` that pass the cell filters
+    pub fn new(path: String, nb: &Notebook, opts: &SearchOptions) -> Self {
+        let kernel = nb.metadata.kernelspec.as_ref()
+            .and_then(|ks| ks.other.get("name"))
+            .and_then(|name| name.as_str())
+            .map(String::from);
+        let mut stats = Self{path: Some(path), language: nb.language().map(String::from), kernel, ..Self::default()};
+
+        for icell in opts.searched_cells(nb) {
+            let cell = &nb.cells[icell];
+
+            stats.cells += 1;
+            *stats.cells_by_type.entry(cell.cell_type.clone()).or_insert(0) += 1;
+            stats.source_lines += cell.source.len();
+            if cell.cell_type == "code" {
+                if cell.execution_count.is_some() {
+                    stats.executed_cells += 1;
+                }else{
+                    stats.unexecuted_cells += 1;
+                }
+            }
+            for (kind, n) in cell.outputs.iter().flatten().flat_map(output_sizes) {
+                *stats.output_bytes.entry(String::from(kind)).or_insert(0) += n;
+            }
+        }
+        stats
+    }
+
+    /// Add another notebook's statistics into this one, for the totals over all notebooks
+    pub fn add(&mut self, other: &NotebookStats) {
+        self.cells += other.cells;
+        for (cell_type, n) in other.cells_by_type.iter() {
+            *self.cells_by_type.entry(cell_type.clone()).or_insert(0) += n;
+        }
+        self.executed_cells += other.executed_cells;
+        self.unexecuted_cells += other.unexecuted_cells;
+        self.source_lines += other.source_lines;
+        for (kind, n) in other.output_bytes.iter() {
+            *self.output_bytes.entry(kind.clone()).or_insert(0) += n;
+        }
+    }
+
+    /// Print these statistics as an indented block under `title`
+    pub fn print(&self, title: &str) {
+        println!("{}", title);
+        match (&self.language, &self.kernel) {
+            (Some(lang), Some(kernel)) => println!("  language: {} (kernel: {})", lang, kernel),
+            (Some(lang), None) => println!("  language: {}", lang),
+            (None, Some(kernel)) => println!("  kernel: {}", kernel),
+            (None, None) => {}
+        }
+
+        let by_type: Vec<String> = self.cells_by_type.iter().map(|(t, n)| format!("{} {}", n, t)).collect();
+        if by_type.is_empty() {
+            println!("  cells: 0");
+        }else{
+            println!("  cells: {} ({})", self.cells, by_type.join(", "));
+        }
+        println!("  code cells: {} executed, {} not executed", self.executed_cells, self.unexecuted_cells);
+        println!("  source lines: {}", self.source_lines);
+
+        let total: usize = self.output_bytes.values().sum();
+        let by_kind: Vec<String> = self.output_bytes.iter().map(|(k, n)| format!("{} {}", k, format_bytes(*n))).collect();
+        if by_kind.is_empty() {
+            println!("  output size: 0 B");
+        }else{
+            println!("  output size: {} ({})", format_bytes(total), by_kind.join(", "));
+        }
+    }
+}
+
+/// The size of each part of an output: of each MIME type's data, or of the text of stream and
+/// error outputs (as "stream" and "error")
+pub fn output_sizes(outp: &Output) -> Vec<(&str, usize)> {
+    match outp.output_type.as_str() {
+        "stream" => vec![("stream", outp.text.iter().flatten().map(|s| s.len()).sum())],
+        "error" => {
+            let traceback: usize = outp.traceback.iter().flatten().map(|s| s.len()).sum();
+            let name_and_value = outp.ename.as_ref().map_or(0, |s| s.len()) + outp.evalue.as_ref().map_or(0, |s| s.len());
+            vec![("error", traceback + name_and_value)]
+        },
+        _ => outp.data.iter().flatten().map(|(dtype, val)| (dtype.as_str(), value_bytes(val))).collect()
+    }
+}
+
+/// The size of output data as stored: the length of the text, or of the serialized JSON
+fn value_bytes(val: &serde_json::Value) -> usize {
+    match val {
+        serde_json::Value::String(s) => s.len(),
+        serde_json::Value::Array(a) if a.iter().all(serde_json::Value::is_string) => {
+            a.iter().filter_map(|s| s.as_str()).map(str::len).sum()
+        },
+        other => other.to_string().len()
+    }
+}
+
+/// e.g. "512 B", "3.4 KB", "1.2 MB"
+pub fn format_bytes(n: usize) -> String {
+    const KB: f64 = 1024.0;
+    let n_f = n as f64;
+    if n_f < KB {
+        format!("{} B", n)
+    }else if n_f < KB * KB {
+        format!("{:.1} KB", n_f / KB)
+    }else{
+        format!("{:.1} MB", n_f / (KB * KB))
+    }
+}
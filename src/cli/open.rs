@@ -0,0 +1,45 @@
+//! Launching matched notebooks for the `--open` option
+//!
+//! `nbopen` is preferred since it opens the notebook on an already running Jupyter
+//! server if there is one; otherwise a new `jupyter notebook` server is started for
+//! each file. Setting `JREP_OPEN` to a command overrides both. The launched programs
+//! run in the background, so jrep does not wait for them to exit.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use jrep::RunErr;
+
+/// Open each notebook in `paths`, returning an error for the first one that could not be launched.
+pub fn open_notebooks(paths: &[PathBuf]) -> Result<(), RunErr> {
+    for path in paths.iter() {
+        open_notebook(path)
+            .map_err(|e| RunErr::from(format!("Could not open {:?}: {}", path, e)))?;
+    }
+    Ok(())
+}
+
+fn open_notebook(path: &Path) -> Result<(), RunErr> {
+    let commands: Vec<Vec<String>> = match std::env::var("JREP_OPEN") {
+        Ok(cmd) if !cmd.trim().is_empty() => vec![cmd.split_whitespace().map(String::from).collect()],
+        _ => vec![vec![String::from("nbopen")], vec![String::from("jupyter"), String::from("notebook")]]
+    };
+
+    for cmd in commands.iter() {
+        let spawned = Command::new(&cmd[0])
+            .args(&cmd[1..])
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(_) => return Ok(()),
+            // Only fall back to the next command if this one is not installed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(RunErr::from(e))
+        }
+    }
+
+    let tried: Vec<&str> = commands.iter().map(|cmd| cmd[0].as_str()).collect();
+    Err(RunErr::from(format!("could not find '{}' (set JREP_OPEN to the command to use)", tried.join("' or '"))))
+}
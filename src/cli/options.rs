@@ -0,0 +1,313 @@
+//! The options of the jrep program: the search options, and everything else the command line
+//! decides, such as the output format and the modes that do something other than search
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use atty::Stream;
+use termcolor::ColorChoice;
+use jrep::{RunErr, SearchOptions};
+use super::colors::OutputColors;
+use super::printer::{self, OutputFormat};
+use super::template::FormatTemplate;
+use super::{lint, serve};
+
+/// The options parsed from the command line
+pub struct CliOptions {
+    /// What to search for and which notebooks to search
+    pub search: SearchOptions,
+    pub lenient: bool,
+    pub no_messages: bool,
+    pub strip_ansi: bool,
+    pub color_choice: ColorChoice,
+    pub colors: OutputColors,
+    pub hyperlinks: bool,
+    pub hyperlink_base: Option<String>,
+    pub jupyter_url: Option<String>,
+    pub open_count: Option<usize>,
+    pub trim: bool,
+    #[cfg_attr(not(feature = "syntect"), allow(dead_code))]
+    pub syntax_highlight: bool,
+    pub show_tags: bool,
+    pub show_cell_id: bool,
+    pub validate: bool,
+    pub list_types: bool,
+    pub notebook_stats: bool,
+    pub lint: Option<lint::LintConfig>,
+    pub replace: Option<String>,
+    pub replace_outputs: bool,
+    pub dry_run: bool,
+    pub redact_dir: Option<OsString>,
+    pub mask: String,
+    pub strip_outputs: bool,
+    pub extract_script: Option<OsString>,
+    pub cat: bool,
+    pub toc: bool,
+    pub list_files: bool,
+    pub dupes: bool,
+    pub dupes_min_lines: usize,
+    pub dupes_similarity: Option<f64>,
+    pub build_index: bool,
+    pub index_file: OsString,
+    #[cfg_attr(not(feature = "tantivy"), allow(dead_code))]
+    pub fulltext: bool,
+    pub clear_cache: bool,
+    pub serve: bool,
+    pub rpc: bool,
+    pub serve_bind: std::net::IpAddr,
+    pub serve_port: u16,
+    pub serve_workers: usize,
+    pub strip_all: bool,
+    pub show_line_detail: u8,
+    pub show_column: bool,
+    pub show_json_pointer: bool,
+    pub output_format: OutputFormat,
+    pub format_template: Option<FormatTemplate>,
+    pub group_cells: bool,
+    pub show_stats: bool,
+    pub unordered: bool,
+    pub max_results: Option<usize>,
+    pub null_separated: bool,
+    pub path_style: printer::PathStyle,
+    pub output_path: Option<OsString>,
+    pub line_buffered: bool,
+    pub show_file_name: bool,
+    pub search_locally: bool,
+    // Whether to run jrep on the --remote host rather than searching here; decided once the
+    // options are parsed, since it means asking the host
+    pub remote_jrep: bool
+}
+
+impl CliOptions {
+    /// Options parsed from command line arguments (without the program name), e.g. for a
+    /// request to --serve or --rpc. Unlike the command line itself, invalid arguments are an
+    /// error rather than ending the program.
+    pub fn from_args(args: Vec<OsString>) -> Result<Self, RunErr> {
+        // The search options are parsed from the same arguments, and say what is wrong with them first
+        let search = SearchOptions::from_args(args.iter().cloned())?;
+        let yml = clap::load_yaml!("../clargs.yml");
+        let argv = std::iter::once(OsString::from("jrep")).chain(args);
+        let matches = clap::App::from_yaml(yml).setting(clap::AppSettings::ColorNever).get_matches_from_safe(argv)
+            .map_err(|e| RunErr::from(e.message))?;
+        Self::from_arg_matches(&matches, search)
+    }
+
+    /// The options in `matches`, with the `search` options parsed from the same arguments
+    pub fn from_arg_matches(matches: &clap::ArgMatches, mut search: SearchOptions) -> Result<Self, RunErr> {
+        // --errors-only takes no pattern, so the first positional argument is a path (see `path_args`)
+        let errors_only = matches.occurrences_of("errors_only") > 0;
+        // With --fulltext, the pattern is a query for tantivy, not a regex
+        let fulltext = matches.occurrences_of("fulltext") > 0;
+        if fulltext && cfg!(not(feature = "tantivy")) {
+            return Err(RunErr::from("jrep was not compiled with the 'tantivy' feature, so '--fulltext' is not available"));
+        }
+        let syntax_highlight = matches.occurrences_of("syntax_highlight") > 0;
+        if syntax_highlight && cfg!(not(feature = "syntect")) {
+            return Err(RunErr::from("jrep was not compiled with the 'syntect' feature, so '--syntax-highlight' is not available"));
+        }
+
+        let output_path = matches.value_of_os("output").map(OsString::from);
+        // Terminal colors only make sense on stdout, so never write them to a file. When
+        // colors are forced, use ANSI codes even on Windows so that they survive piping.
+        // In the automatic case, termcolor also turns colors off if NO_COLOR is set.
+        let color_choice = match matches.value_of("color").unwrap() {
+            _ if output_path.is_some() => ColorChoice::Never,
+            "always" => ColorChoice::AlwaysAnsi,
+            "never" => ColorChoice::Never,
+            "auto" if atty::is(Stream::Stdout) => ColorChoice::Auto,
+            "auto" => ColorChoice::Never,
+            _ => {return Err(RunErr::from("Unexpected value for '--color'"))}
+        };
+
+        let open_count = match matches.value_of("open_count") {
+            Some(n) => Some(n.parse::<usize>().map_err(|_| RunErr::from("The value for '--open-count' must be a non-negative integer"))?),
+            None if matches.occurrences_of("open") > 0 => Some(1),
+            None => None
+        };
+        let hyperlink_base = matches.value_of("hyperlink_base").map(String::from);
+        let colors = OutputColors::from_specs(matches.values_of("colors").into_iter().flatten())?;
+
+        let lint = if matches.occurrences_of("lint") > 0 {
+            let max_output_kb = matches.value_of("max_output_kb").unwrap_or("100").parse::<usize>()
+                .map_err(|_| RunErr::from("The value for '--max-output-kb' must be a non-negative integer"))?;
+            Some(lint::LintConfig::new(
+                matches.values_of("lint_rule").into_iter().flatten(),
+                matches.values_of("lint_ignore").into_iter().flatten(),
+                max_output_kb
+            )?)
+        }else{
+            None
+        };
+
+        let dupes_min_lines = matches.value_of("dupes_min_lines").unwrap().parse::<usize>()
+            .map_err(|_| RunErr::from("The value for '--dupes-min-lines' must be a non-negative integer"))?;
+        let dupes_similarity = match matches.value_of("similarity") {
+            Some(s) => Some(s.parse::<f64>().ok().filter(|f| (0.0..=1.0).contains(f))
+                .ok_or_else(|| RunErr::from("The value for '--similarity' must be a number from 0 to 1"))?),
+            None => None
+        };
+        let max_results = match matches.value_of("max_results") {
+            Some(n) => Some(n.parse::<usize>().ok().filter(|&n| n > 0)
+                .ok_or_else(|| RunErr::from("The value for '--max-results' must be a positive integer"))?),
+            None => None
+        };
+
+        let index_file = matches.value_of_os("index_file").map(OsString::from)
+            .unwrap_or_else(|| OsString::from(jrep::index::DEFAULT_INDEX_FILE));
+        let serve_port = matches.value_of("port").unwrap().parse::<u16>()
+            .map_err(|_| RunErr::from("The value for '--port' must be a port number from 0 to 65535"))?;
+        let serve_bind = matches.value_of("bind").unwrap().parse::<std::net::IpAddr>()
+            .map_err(|_| RunErr::from("The value for '--bind' must be an IPv4 or IPv6 address"))?;
+        let serve_workers = if matches.occurrences_of("threads") > 0 { search.threads() } else { serve::DEFAULT_WORKERS };
+
+        // Options controlling output detail
+        let line_detail_level = if matches.occurrences_of("max_line_info") > 0 {
+            255
+        } else if errors_only {
+            // The cell is the point of --errors-only, so always show it
+            std::cmp::max(matches.occurrences_of("line_info") as u8, 1)
+        } else {
+            matches.occurrences_of("line_info") as u8
+        };
+        let show_column = matches.occurrences_of("column") > 0;
+        let show_json_pointer = matches.occurrences_of("json_pointer") > 0;
+
+        let format_template = match matches.value_of("format_str") {
+            Some(t) => Some(FormatTemplate::parse(t)?),
+            None => None
+        };
+
+        let output_format = if matches.occurrences_of("files_with_matches") > 0 {
+            OutputFormat::FilesWithMatches
+        }else if matches.occurrences_of("files_without_match") > 0 {
+            OutputFormat::FilesWithoutMatch
+        }else if let Some(name) = matches.value_of("report") {
+            match name {
+                "html" => OutputFormat::HtmlReport,
+                "md" => OutputFormat::MarkdownReport,
+                _ => return Err(RunErr::from("Unexpected value for '--report'"))
+            }
+        }else if matches.occurrences_of("json_lines") > 0 {
+            OutputFormat::JsonLines
+        }else if matches.occurrences_of("json") > 0 {
+            OutputFormat::Json
+        }else if let Some(name) = matches.value_of("format") {
+            OutputFormat::from_name(name).ok_or_else(|| RunErr::from("Unexpected value for '--format'"))?
+        }else{
+            OutputFormat::Standard
+        };
+        // SARIF locates each match by its line in the notebook file
+        if let OutputFormat::Sarif = output_format {
+            search.set_file_lines(true);
+        }
+        // --abs-paths and --relative-to override each other, so at most one will be present
+        let path_style = if matches.occurrences_of("abs_paths") > 0 {
+            printer::PathStyle::Absolute
+        }else if let Some(dir) = matches.value_of_os("relative_to") {
+            let dir = fs::canonicalize(dir)
+                .map_err(|e| RunErr::from(format!("Could not find the directory {:?} for '--relative-to': {}", dir, e)))?;
+            printer::PathStyle::RelativeTo(dir)
+        }else{
+            printer::PathStyle::AsGiven
+        };
+        let show_filenames_raw = matches.value_of("show_filenames").unwrap();
+        let show_filenames = if matches.occurrences_of("force_show_file") > 0 {
+            true
+        } else if show_filenames_raw == "auto" {
+            let paths_raw = path_args(matches);
+            // Assume that if one of the input paths is a directory (or archive, or cloud storage URL) that
+            // we should print the file names so that we know which file
+            // is matching. With --rev, a directory may only exist at the revision.
+            let with_rev = matches.occurrences_of("rev") > 0;
+            paths_raw.len() > 1 || paths_raw.iter().any(|x| Path::new(x).is_dir() || jrep::is_archive(Path::new(x)) || jrep::is_cloud_url(x) || (with_rev && !Path::new(x).is_file()))
+                || matches.occurrences_of("files_from") > 0 || matches.occurrences_of("remote") > 0
+        } else {
+            show_filenames_raw == "always"
+        };
+
+        Ok(CliOptions{
+            search,
+            lenient: matches.occurrences_of("lenient") > 0,
+            no_messages: matches.occurrences_of("no_messages") > 0,
+            // Like --include-source, stripping is on unless the last of these is --no-strip-ansi
+            strip_ansi: matches.occurrences_of("no_strip_ansi") == 0,
+            color_choice,
+            colors,
+            hyperlinks: matches.occurrences_of("hyperlinks") > 0 || hyperlink_base.is_some(),
+            hyperlink_base,
+            jupyter_url: matches.value_of("jupyter_url").map(String::from),
+            open_count,
+            trim: matches.occurrences_of("trim") > 0,
+            syntax_highlight,
+            show_tags: matches.occurrences_of("show_tags") > 0,
+            show_cell_id: matches.occurrences_of("show_cell_id") > 0,
+            validate: matches.occurrences_of("validate") > 0,
+            list_types: matches.occurrences_of("list_types") > 0,
+            notebook_stats: matches.occurrences_of("notebook_stats") > 0,
+            lint,
+            replace: matches.value_of("replace").map(String::from),
+            replace_outputs: matches.occurrences_of("replace_outputs") > 0,
+            dry_run: matches.occurrences_of("dry_run") > 0,
+            redact_dir: matches.value_of_os("redact").map(OsString::from),
+            mask: String::from(matches.value_of("mask").unwrap()),
+            strip_outputs: matches.occurrences_of("strip_outputs") > 0,
+            extract_script: matches.value_of_os("extract_script").map(OsString::from),
+            cat: matches.occurrences_of("cat") > 0,
+            toc: matches.occurrences_of("toc") > 0,
+            list_files: matches.occurrences_of("files") > 0,
+            dupes: matches.occurrences_of("dupes") > 0,
+            dupes_min_lines,
+            dupes_similarity,
+            build_index: matches.occurrences_of("build_index") > 0,
+            index_file,
+            fulltext,
+            clear_cache: matches.occurrences_of("clear_cache") > 0,
+            serve: matches.occurrences_of("serve") > 0,
+            rpc: matches.occurrences_of("rpc") > 0,
+            serve_bind,
+            serve_port,
+            serve_workers,
+            strip_all: matches.occurrences_of("strip_all") > 0,
+            show_line_detail: line_detail_level,
+            show_column,
+            show_json_pointer,
+            output_format,
+            format_template,
+            group_cells: matches.occurrences_of("group_cells") > 0,
+            show_stats: matches.occurrences_of("stats") > 0,
+            unordered: matches.occurrences_of("unordered") > 0,
+            max_results,
+            null_separated: matches.occurrences_of("null") > 0,
+            path_style,
+            output_path,
+            line_buffered: matches.occurrences_of("line_buffered") > 0,
+            show_file_name: show_filenames,
+            search_locally: matches.occurrences_of("search_locally") > 0,
+            remote_jrep: false
+        })
+    }
+}
+
+/// The paths given on the command line. With --errors-only, --validate, --list-types,
+/// --notebook-stats, --lint, --cat, --toc, --dupes, --preset, --entropy, --strip-outputs --all,
+/// --build-index, --clear-cache, --serve, or --rpc there is no pattern, so the argument in the pattern's place
+/// is the first path.
+pub fn path_args<'a>(matches: &'a clap::ArgMatches) -> Vec<&'a std::ffi::OsStr> {
+    let paths_given = matches.occurrences_of("paths") > 0;
+    // The default "." is only for when no paths are given at all, including with --files-from
+    // or --remote
+    let paths: Vec<_> = if paths_given || (matches.occurrences_of("files_from") == 0 && matches.occurrences_of("remote") == 0) {
+        matches.values_of_os("paths").unwrap().collect()
+    }else{
+        Vec::new()
+    };
+    let no_pattern = ["errors_only", "validate", "list_types", "notebook_stats", "lint", "cat", "toc", "dupes", "preset", "entropy", "strip_all", "build_index", "clear_cache", "serve", "rpc", "files"].iter().any(|&mode| matches.occurrences_of(mode) > 0);
+    match matches.value_of_os("pattern") {
+        Some(first) if no_pattern => {
+            // Without this check, `jrep --errors-only demo.ipynb` would also search the default "."
+            let rest = if paths_given { paths } else { Vec::new() };
+            std::iter::once(first).chain(rest).collect()
+        },
+        _ => paths
+    }
+}
@@ -0,0 +1,159 @@
+//! Searching several notebooks at once for `-j/--threads`
+//!
+//! Each worker thread takes the next notebook from the list, then reads, parses, and searches
+//! it, recording the matches instead of printing them. The main thread replays each notebook's
+//! matches through the real printer, so the printers only ever see one notebook at a time
+//! and do not need to know about threads at all. Notebooks that finish early are held back
+//! until the ones listed before them are done, so the output is the same as searching them
+//! one at a time, unless --unordered was given.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use termcolor::{NoColor, WriteColor};
+use jrep::{Cell, FoundNotebook, MatchContext, MatchedLine, Printer, RunErr, SearchOptions, Searched};
+
+/// One match, with everything it borrowed from the notebook copied out
+struct RecordedMatch {
+    icell: Option<usize>,
+    piece: String,
+    section: Option<String>,
+    line: String,
+    line_number: usize,
+    match_positions: Vec<(usize, usize)>,
+    is_text: bool,
+    pointer: String,
+    file_line: Option<usize>,
+    mime_type: Option<String>
+}
+
+/// The matches in one notebook, and copies of the cells they were found in
+pub struct RecordedNotebook {
+    language: Option<String>,
+    // Keyed the same way as MatchContext::icell, so the placeholder cell for notebook
+    // metadata is kept under `None`
+    cells: HashMap<Option<usize>, Cell>,
+    matches: Vec<RecordedMatch>
+}
+
+impl RecordedNotebook {
+    /// Print the recorded matches with `printer`, returning how many there were. This does
+    /// not call `end_file`, just as `search_notebook` does not.
+    pub fn replay(&self, filename: &OsString, printer: &mut dyn Printer, opts: &SearchOptions) -> io::Result<usize> {
+        for rm in self.matches.iter() {
+            let ctx = MatchContext{
+                filename,
+                cell: &self.cells[&rm.icell],
+                icell: rm.icell,
+                piece: &rm.piece,
+                language: self.language.as_deref(),
+                section: rm.section.as_deref()
+            };
+            let m = MatchedLine{
+                line: &rm.line,
+                line_number: rm.line_number,
+                match_positions: rm.match_positions.clone(),
+                is_text: rm.is_text,
+                pointer: rm.pointer.clone(),
+                file_line: rm.file_line,
+                mime_type: rm.mime_type.as_deref()
+            };
+            printer.print_match(&ctx, &m, opts)?;
+        }
+        Ok(self.matches.len())
+    }
+}
+
+/// A printer that keeps the matches for later instead of printing them
+struct Recorder {
+    notebook: RecordedNotebook,
+    sink: NoColor<io::Sink>
+}
+
+impl Printer for Recorder {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        if self.notebook.matches.is_empty() {
+            self.notebook.language = ctx.language.map(String::from);
+        }
+        self.notebook.cells.entry(ctx.icell).or_insert_with(|| ctx.cell.clone());
+        self.notebook.matches.push(RecordedMatch{
+            icell: ctx.icell,
+            piece: String::from(ctx.piece),
+            section: ctx.section.map(String::from),
+            line: String::from(m.line),
+            line_number: m.line_number,
+            match_positions: m.match_positions.clone(),
+            is_text: m.is_text,
+            pointer: m.pointer.clone(),
+            file_line: m.file_line,
+            mime_type: m.mime_type.map(String::from)
+        });
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        &mut self.sink
+    }
+}
+
+/// Search one notebook like `search_notebook`, but record the matches instead of printing them
+fn record_notebook(notebook: &FoundNotebook, opts: &SearchOptions) -> Result<Searched<RecordedNotebook>, RunErr> {
+    let mut recorder = Recorder{
+        notebook: RecordedNotebook{language: None, cells: HashMap::new(), matches: Vec::new()},
+        sink: NoColor::new(io::sink())
+    };
+    let result = jrep::search_notebook(notebook, opts, &mut recorder)?;
+    Ok(result.map(|_| recorder.notebook))
+}
+
+/// Search `paths` with `n_threads` worker threads. `handle` is called on the calling thread
+/// with the index of each notebook in `paths` and its result: in the order of `paths` if
+/// `in_order` is true, otherwise as soon as each notebook is done. It returns whether to go
+/// on; once it returns false, no more notebooks are started or handled.
+pub fn search_notebooks<F>(paths: &[FoundNotebook], opts: &SearchOptions, n_threads: usize, in_order: bool, mut handle: F)
+where F: FnMut(usize, Result<Searched<RecordedNotebook>, RunErr>) -> bool
+{
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..n_threads.min(paths.len()) {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let notebook = match paths.get(i) {
+                    Some(nb) => nb,
+                    None => break
+                };
+                // The receiver only goes away if the main thread panicked
+                if tx.send((i, record_notebook(notebook, opts))).is_err() {
+                    break;
+                }
+            });
+        }
+        // Otherwise the loop below would wait forever for this sender
+        drop(tx);
+        // Results that arrived before those of an earlier notebook, by index
+        let mut waiting = BTreeMap::new();
+        let mut next_index = 0;
+        'results: for (i, result) in rx {
+            if !in_order {
+                if !handle(i, result) {
+                    break;
+                }
+                continue;
+            }
+            waiting.insert(i, result);
+            while let Some(result) = waiting.remove(&next_index) {
+                if !handle(next_index, result) {
+                    break 'results;
+                }
+                next_index += 1;
+            }
+        }
+        // Workers finish the notebook they are on, but do not start another
+        next.store(paths.len(), Ordering::Relaxed);
+    });
+}
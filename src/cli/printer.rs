@@ -0,0 +1,749 @@
+//! The output formats of search results
+//!
+//! Each format is a [`Printer`], which `search_notebook` gives the matches as it finds them.
+//! The standard printer writes matches as they are found, in the grep-like format;
+//! the JSON printer collects them and writes a single JSON document at the end.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufWriter, LineWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use termcolor::{BufferedStandardStream, ColorSpec, HyperlinkSpec, NoColor, StandardStream, WriteColor};
+use serde::Serialize;
+use serde_json::json;
+use jrep::{MatchContext, MatchedLine, Printer, SearchOptions};
+use super::CliOptions;
+use super::report::{HtmlReportPrinter, MarkdownReportPrinter};
+#[cfg(feature = "syntect")]
+use super::highlight::SyntaxHighlighter;
+
+/// Which format to write search results in
+pub enum OutputFormat {
+    Standard,
+    Json,
+    JsonLines,
+    Sarif,
+    HtmlReport,
+    MarkdownReport,
+    FilesWithMatches,
+    FilesWithoutMatch
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(OutputFormat::Standard),
+            "json" => Some(OutputFormat::Json),
+            "json-lines" => Some(OutputFormat::JsonLines),
+            "sarif" => Some(OutputFormat::Sarif),
+            _ => None
+        }
+    }
+}
+
+/// How the paths of notebooks are printed (--abs-paths, --relative-to)
+pub enum PathStyle {
+    /// As given on the command line or found in a directory
+    AsGiven,
+    Absolute,
+    /// Relative to this directory, which is absolute
+    RelativeTo(PathBuf)
+}
+
+impl PathStyle {
+    /// How the notebook file at `path` is printed. Only files on this computer have a path to
+    /// show in another way (see `FoundNotebook::show_path`).
+    pub fn show(&self, path: &Path) -> OsString {
+        // Canonical if possible, but a path that cannot be canonicalized (e.g. it no longer
+        // exists) is still made absolute
+        let absolute = || std::fs::canonicalize(path).or_else(|_| std::path::absolute(path));
+        match self {
+            PathStyle::AsGiven => path.as_os_str().to_os_string(),
+            PathStyle::Absolute => absolute().map_or_else(|_| path.as_os_str().to_os_string(), PathBuf::into_os_string),
+            PathStyle::RelativeTo(dir) => absolute().map_or_else(|_| path.as_os_str().to_os_string(), |abs| relative_path(&abs, dir).into_os_string())
+        }
+    }
+}
+
+/// `path` relative to `dir`, going up with ".." as far as needed. Both must be absolute.
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    let common = path.components().zip(dir.components()).take_while(|(a, b)| a == b).count();
+    let n_up = dir.components().count() - common;
+    std::iter::repeat_n(Component::ParentDir, n_up).chain(path.components().skip(common)).collect()
+}
+
+/// The colors of a piece of a line from syntax highlighting, and the byte range of the line it covers
+pub type Token = (ColorSpec, std::ops::Range<usize>);
+
+/// Create the printer for the requested output format, writing to stdout or, if
+/// --output was given, to that file. With --extract-script, it also writes the matching
+/// code cells to the script.
+///
+/// The output is buffered and only written out when the buffer fills up or the printer
+/// finishes, since flushing after every line is slow when there are many matches. With
+/// --line-buffered, each line is written as soon as it is complete instead.
+pub fn make_printer(opts: &CliOptions) -> io::Result<Box<dyn Printer + '_>> {
+    let out: Box<dyn WriteColor> = match (&opts.output_path, opts.line_buffered) {
+        (Some(path), false) => Box::new(NoColor::new(BufWriter::new(File::create(path)?))),
+        (Some(path), true) => Box::new(NoColor::new(LineWriter::new(File::create(path)?))),
+        (None, false) => Box::new(BufferedStandardStream::stdout(opts.color_choice)),
+        // Stdout itself flushes at the end of each line
+        (None, true) => Box::new(StandardStream::stdout(opts.color_choice))
+    };
+
+    let printer: Box<dyn Printer + '_> = match opts.output_format {
+        OutputFormat::Standard => Box::new(StandardPrinter::new(out, opts)),
+        OutputFormat::Json => Box::new(JsonPrinter{out, files: Vec::new()}),
+        OutputFormat::JsonLines => Box::new(JsonLinesPrinter{out}),
+        OutputFormat::Sarif => Box::new(SarifPrinter{out, files: Vec::new()}),
+        OutputFormat::HtmlReport => Box::new(HtmlReportPrinter::new(out)),
+        OutputFormat::MarkdownReport => Box::new(MarkdownReportPrinter::new(out)),
+        OutputFormat::FilesWithMatches => Box::new(FileListPrinter{out, opts, with_matches: true}),
+        OutputFormat::FilesWithoutMatch => Box::new(FileListPrinter{out, opts, with_matches: false})
+    };
+    match &opts.extract_script {
+        Some(path) => Ok(Box::new(super::extract::ScriptExtractor::new(printer, path)?)),
+        None => Ok(printer)
+    }
+}
+
+
+/// Write a path followed by a newline, or a NUL byte if --null was given. On Unix,
+/// the path is written as raw bytes so that names which are not valid UTF-8 still
+/// come out exactly right for programs like `xargs -0`.
+pub fn print_path(out: &mut dyn WriteColor, path: &std::ffi::OsStr, opts: &CliOptions) -> io::Result<()> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path).to_vec();
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().into_owned().into_bytes();
+
+    let terminator: &[u8] = if opts.null_separated { b"\0" } else { b"\n" };
+    let link = start_hyperlink(out, path, opts)?;
+    out.write_all(&bytes)?;
+    if link {
+        out.set_hyperlink(&HyperlinkSpec::close())?;
+    }
+    out.write_all(terminator)
+}
+
+/// Write a notebook's file name in the path colors, as a terminal hyperlink to the
+/// notebook if --hyperlinks was given.
+fn write_file_name(out: &mut dyn WriteColor, path: &std::ffi::OsStr, opts: &CliOptions) -> io::Result<()> {
+    let link = start_hyperlink(out, path, opts)?;
+    write_styled(out, &format!("{:?}", path), &opts.colors.path)?;
+    if link {
+        out.set_hyperlink(&HyperlinkSpec::close())?;
+    }
+    Ok(())
+}
+
+/// Open an OSC 8 hyperlink to `path` if hyperlinks were requested and `out` can show
+/// them (i.e. it is a terminal with colors on). Returns whether a link was opened.
+fn start_hyperlink(out: &mut dyn WriteColor, path: &std::ffi::OsStr, opts: &CliOptions) -> io::Result<bool> {
+    if !opts.hyperlinks || !out.supports_hyperlinks() {
+        return Ok(false);
+    }
+    let uri = hyperlink_uri(&path.to_string_lossy(), opts);
+    out.set_hyperlink(&HyperlinkSpec::open(uri.as_bytes()))?;
+    Ok(true)
+}
+
+/// The URI a file name links to: the path as given appended to the --hyperlink-base
+/// URL, the notebook on the --jupyter-url server, or a `file://` URI.
+fn hyperlink_uri(path: &str, opts: &CliOptions) -> String {
+    match (&opts.hyperlink_base, &opts.jupyter_url) {
+        (Some(base), _) => {
+            let rel_path = path.replace('\\', "/");
+            let rel_path = rel_path.trim_start_matches("./");
+            format!("{}/{}", base.trim_end_matches('/'), percent_encode(rel_path))
+        },
+        (None, Some(base)) => jupyter_url(base, path),
+        (None, None) => file_uri(path)
+    }
+}
+
+/// The URL of a notebook on the Jupyter server at `base`. Jupyter serves files relative
+/// to the directory it was started in, which is assumed to be the current directory.
+pub fn jupyter_url(base: &str, path: &str) -> String {
+    let rel_path = std::env::current_dir().ok()
+        .and_then(|cwd| std::fs::canonicalize(cwd).ok())
+        .and_then(|cwd| {
+            let abs_path = std::fs::canonicalize(path).ok()?;
+            abs_path.strip_prefix(cwd).ok().map(|p| p.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| String::from(path.trim_start_matches("./")));
+    let rel_path = rel_path.replace('\\', "/");
+    format!("{}/notebooks/{}", base.trim_end_matches('/'), percent_encode(&rel_path))
+}
+
+
+/// Convert a path to a `file://` URI. The path is made absolute if possible, and any
+/// characters not allowed in URIs are percent-encoded.
+pub fn file_uri(path: &str) -> String {
+    let abs_path = match std::fs::canonicalize(path) {
+        Ok(p) => p.to_string_lossy().into_owned(),
+        Err(_) => String::from(path)
+    };
+    // Windows paths need an extra slash (file:///C:/...) and forward slashes
+    let abs_path = abs_path.replace('\\', "/");
+    let prefix = if abs_path.starts_with('/') { "file://" } else { "file:///" };
+    format!("{}{}", prefix, percent_encode(&abs_path))
+}
+
+/// Percent-encode the bytes of a path that are not allowed in a URI path
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~:".contains(&b) {
+            encoded.push(b as char);
+        }else{
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+
+/// Prints matches in the grep-like format, one line per match.
+pub struct StandardPrinter<'a> {
+    out: Box<dyn WriteColor>,
+    opts: &'a CliOptions,
+    // The file and cell index of the last match printed, used for --group-cells
+    last_cell: Option<(std::ffi::OsString, Option<usize>)>,
+    #[cfg(feature = "syntect")]
+    highlighter: Option<SyntaxHighlighter>
+}
+
+impl<'a> StandardPrinter<'a> {
+    fn new(out: Box<dyn WriteColor>, opts: &'a CliOptions) -> Self {
+        Self{
+            // Loading the syntax definitions takes a moment, so only do it if they will be used
+            #[cfg(feature = "syntect")]
+            highlighter: if opts.syntax_highlight && out.supports_color() { Some(SyntaxHighlighter::new()) } else { None },
+            out,
+            opts,
+            last_cell: None
+        }
+    }
+}
+
+impl Printer for StandardPrinter<'_> {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        let opts = self.opts;
+        let out = self.out.as_mut();
+        if opts.group_cells && opts.format_template.is_none() {
+            let is_new_cell = match &self.last_cell {
+                Some((f, i)) => f != ctx.filename || *i != ctx.icell,
+                None => true
+            };
+            if is_new_cell {
+                if self.last_cell.is_some() {
+                    writeln!(out)?;
+                }
+                print_cell_header(out, ctx, opts)?;
+                self.last_cell = Some((ctx.filename.clone(), ctx.icell));
+            }
+        }
+
+        if let Some(template) = &opts.format_template {
+            writeln!(out, "{}", template.render(ctx, m))?;
+        }else if m.is_text {
+            // Only highlight the line if it really is the line of the cell source being highlighted
+            #[cfg(feature = "syntect")]
+            let tokens = match &mut self.highlighter {
+                Some(h) if ctx.cell.source.get(m.line_number).map(String::as_str) == Some(m.line) => h.line_tokens(ctx, m.line_number),
+                _ => None
+            };
+            #[cfg(not(feature = "syntect"))]
+            let tokens = None;
+            print_text_match(out, ctx, m, tokens, opts)?;
+        }else{
+            print_nontext_match(out, ctx, m, opts)?;
+        }
+        Ok(())
+    }
+
+    fn end_file(&mut self, filename: &std::ffi::OsString, n_matches: usize, _opts: &SearchOptions) -> io::Result<()> {
+        let opts = self.opts;
+        if let Some(base) = &opts.jupyter_url {
+            if n_matches > 0 && opts.format_template.is_none() {
+                let out = self.out.as_mut();
+                let url = jupyter_url(base, &filename.to_string_lossy());
+                write!(out, "Open in Jupyter: ")?;
+                let link = opts.hyperlinks && out.supports_hyperlinks();
+                if link {
+                    out.set_hyperlink(&HyperlinkSpec::open(url.as_bytes()))?;
+                }
+                write_styled(out, &url, &opts.colors.path)?;
+                if link {
+                    out.set_hyperlink(&HyperlinkSpec::close())?;
+                }
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+}
+
+
+/// Prints only the names of notebooks that have (or do not have) any matches (-l/-L)
+pub struct FileListPrinter<'a> {
+    out: Box<dyn WriteColor>,
+    opts: &'a CliOptions,
+    with_matches: bool
+}
+
+impl Printer for FileListPrinter<'_> {
+    fn print_match(&mut self, _ctx: &MatchContext, _m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_file(&mut self, filename: &std::ffi::OsString, n_matches: usize, _opts: &SearchOptions) -> io::Result<()> {
+        if (n_matches > 0) == self.with_matches {
+            print_path(self.out.as_mut(), filename, self.opts)?;
+        }
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+}
+
+
+/// Passes on only the first `remaining` matches to the printer it wraps (--max-results)
+pub struct LimitedPrinter<'a> {
+    inner: &'a mut dyn Printer,
+    remaining: usize
+}
+
+impl<'a> LimitedPrinter<'a> {
+    pub fn new(inner: &'a mut dyn Printer, remaining: usize) -> Self {
+        Self{inner, remaining}
+    }
+}
+
+impl Printer for LimitedPrinter<'_> {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, opts: &SearchOptions) -> io::Result<()> {
+        if self.remaining == 0 {
+            return Ok(());
+        }
+        self.remaining -= 1;
+        self.inner.print_match(ctx, m, opts)
+    }
+
+    fn end_file(&mut self, filename: &std::ffi::OsString, n_matches: usize, opts: &SearchOptions) -> io::Result<()> {
+        self.inner.end_file(filename, n_matches, opts)
+    }
+
+    fn finish(&mut self, opts: &SearchOptions) -> io::Result<()> {
+        self.inner.finish(opts)
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.inner.writer()
+    }
+}
+
+
+/// A single match, in the form written by the JSON output formats
+#[derive(Serialize)]
+pub struct MatchRecord {
+    // None for matches in the notebook metadata
+    pub cell: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub cell_type: String,
+    pub execution_count: Option<usize>,
+    pub piece: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub pointer: String,
+    pub spans: Vec<(usize, usize)>,
+    // None for matches in non-text output data, which would usually be huge
+    pub text: Option<String>
+}
+
+impl MatchRecord {
+    pub fn new(ctx: &MatchContext, m: &MatchedLine) -> Self {
+        let text = if m.is_text {
+            let mut s = String::from(m.line);
+            trim_newline(&mut s);
+            Some(s)
+        }else{
+            None
+        };
+
+        Self{
+            cell: ctx.icell,
+            id: ctx.cell.id.clone(),
+            cell_type: ctx.cell.cell_type.clone(),
+            execution_count: ctx.cell.execution_count,
+            piece: String::from(ctx.piece),
+            mime_type: m.mime_type.map(String::from),
+            line: m.line_number + 1,
+            file_line: m.file_line,
+            section: ctx.section.map(String::from),
+            tags: ctx.cell.metadata.tags.clone(),
+            pointer: m.pointer.clone(),
+            spans: m.match_positions.clone(),
+            text
+        }
+    }
+}
+
+/// All the matches in one notebook
+#[derive(Serialize)]
+pub struct FileRecord {
+    pub path: String,
+    pub matches: Vec<MatchRecord>
+}
+
+impl FileRecord {
+    /// Add a match to the last file record in `files`, starting a new record if the
+    /// match is from a different file. Since files are searched one at a time, this
+    /// groups the matches by file.
+    pub fn push_match(files: &mut Vec<FileRecord>, ctx: &MatchContext, m: &MatchedLine) {
+        let path = ctx.filename.to_string_lossy();
+        match files.last_mut() {
+            Some(f) if f.path == path => {},
+            _ => files.push(FileRecord{path: path.into_owned(), matches: Vec::new()})
+        }
+        files.last_mut().unwrap().matches.push(MatchRecord::new(ctx, m));
+    }
+}
+
+
+/// Collects all matches and prints them as one JSON document once the search is done.
+pub struct JsonPrinter {
+    out: Box<dyn WriteColor>,
+    files: Vec<FileRecord>
+}
+
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    files: &'a [FileRecord]
+}
+
+impl Printer for JsonPrinter {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        FileRecord::push_match(&mut self.files, ctx, m);
+        Ok(())
+    }
+
+    fn finish(&mut self, _opts: &SearchOptions) -> io::Result<()> {
+        let doc = JsonDocument{files: &self.files};
+        // Serializing these structures cannot fail: they contain only strings, numbers, and options
+        writeln!(self.out, "{}", serde_json::to_string(&doc).unwrap())?;
+        self.out.flush()
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+}
+
+
+/// Prints each match as a JSON object on its own line as soon as it is found.
+pub struct JsonLinesPrinter {
+    out: Box<dyn WriteColor>
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    path: &'a str,
+    #[serde(flatten)]
+    record: MatchRecord
+}
+
+impl Printer for JsonLinesPrinter {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        let line = JsonLine{path: &ctx.filename.to_string_lossy(), record: MatchRecord::new(ctx, m)};
+        writeln!(self.out, "{}", serde_json::to_string(&line).unwrap())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+}
+
+
+/// Collects all matches and prints them as a SARIF 2.1.0 log once the search is done,
+/// for upload to code scanning services (e.g. GitHub). Each pattern searched for is a
+/// "rule" and each match is a "result" of that rule.
+pub struct SarifPrinter {
+    out: Box<dyn WriteColor>,
+    files: Vec<FileRecord>
+}
+
+const SARIF_RULE_ID: &str = "jrep/pattern";
+
+impl Printer for SarifPrinter {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        FileRecord::push_match(&mut self.files, ctx, m);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+
+    fn finish(&mut self, opts: &SearchOptions) -> io::Result<()> {
+        let rules = vec![json!({
+            "id": SARIF_RULE_ID,
+            "name": "PatternMatch",
+            "shortDescription": {"text": format!("Matches the pattern '{}'", opts.pattern())},
+            "defaultConfiguration": {"level": "warning"}
+        })];
+
+        let mut results = Vec::new();
+        for file in self.files.iter() {
+            // SARIF URIs always use forward slashes
+            let uri = file.path.replace('\\', "/");
+            for rec in file.matches.iter() {
+                let location = match rec.cell {
+                    Some(icell) => format!("cell {}", icell),
+                    None => String::from("the notebook")
+                };
+                let message = format!("'{}' matched in {} ({}), line {}", opts.pattern(), location, rec.piece, rec.line);
+                let mut physical = json!({"artifactLocation": {"uri": uri}});
+                // Columns are not given because the decoded text in the notebook does not line up
+                // with the raw JSON text (indentation, escaped characters, etc.)
+                if let Some(line) = rec.file_line {
+                    physical["region"] = json!({"startLine": line});
+                }
+                results.push(json!({
+                    "ruleId": SARIF_RULE_ID,
+                    "ruleIndex": 0,
+                    "level": "warning",
+                    "message": {"text": message},
+                    "locations": [{
+                        "physicalLocation": physical,
+                        "logicalLocations": [{"fullyQualifiedName": rec.pointer, "kind": "element"}]
+                    }]
+                }));
+            }
+        }
+
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "jrep",
+                        "version": clap::crate_version!(),
+                        "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                        "rules": rules
+                    }
+                },
+                "results": results
+            }]
+        });
+        writeln!(self.out, "{}", serde_json::to_string_pretty(&log).unwrap())?;
+        self.out.flush()
+    }
+}
+
+
+/// Print the header that introduces the matches in one cell when grouping by cell,
+/// e.g. `Cell 12 [exec 7] (code)`.
+fn print_cell_header(out: &mut dyn WriteColor, ctx: &MatchContext, opts: &CliOptions) -> io::Result<()> {
+    if opts.show_file_name {
+        write_file_name(out, ctx.filename, opts)?;
+        write!(out, ": ")?;
+    }
+    write_styled(out, &cell_header(ctx, opts), &opts.colors.cell)?;
+    writeln!(out)
+}
+
+/// e.g. "Cell 3 [exec 2] (code)", with the cell ID and tags if requested
+pub fn cell_header(ctx: &MatchContext, opts: &CliOptions) -> String {
+    let mut header = match ctx.icell {
+        Some(icell) => format!("Cell {}", icell),
+        None => return String::from("Notebook metadata")
+    };
+    if let Some(n) = ctx.cell.execution_count {
+        header.push_str(&format!(" [exec {}]", n));
+    }
+    header.push_str(&format!(" ({})", ctx.cell.cell_type));
+    if let Some(extra) = cell_extra_detail(ctx, opts) {
+        header.push_str(&extra);
+    }
+    header
+}
+
+
+fn print_line_detail(out: &mut dyn WriteColor, ctx: &MatchContext, m: &MatchedLine, opts: &CliOptions) -> io::Result<()> {
+    let (cell, icell) = (ctx.cell, ctx.icell);
+    // text/plain is by far the most common output type, so only name the others
+    let cell_piece = match m.mime_type {
+        Some(mime_type) if mime_type != "text/plain" => format!("{} {}", ctx.piece, mime_type),
+        _ => String::from(ctx.piece)
+    };
+    if opts.group_cells {
+        // The file and cell are already given in the header, so just indent under it
+        write!(out, "  ")?;
+    }else if opts.show_file_name {
+        write_file_name(out, ctx.filename, opts)?;
+        write!(out, ": ")?;
+    }
+    if let Some(section) = ctx.section {
+        write_styled(out, section, &opts.colors.cell)?;
+        write!(out, ": ")?;
+    }
+    if let Some(line) = m.file_line {
+        write_styled(out, &line.to_string(), &opts.colors.cell)?;
+        write!(out, ": ")?;
+    }
+    if opts.show_json_pointer {
+        write_styled(out, &m.pointer, &opts.colors.cell)?;
+        write!(out, ": ")?;
+    }
+    let column = if opts.show_column { m.first_column() } else { None };
+    // With --group-cells, the cell ID and tags are in the cell header instead
+    let cell_extra = if opts.group_cells { None } else { cell_extra_detail(ctx, opts) };
+    if opts.show_line_detail == 0 {
+        if let Some(col) = column {
+            write_styled(out, &format!("col.{}", col), &opts.colors.cell)?;
+            write!(out, ": ")?;
+        }
+        if let Some(extra) = cell_extra {
+            write_styled(out, extra.trim_start(), &opts.colors.cell)?;
+            write!(out, ": ")?;
+        }
+        return write!(out, "\t");
+    }
+
+    let exec_cnt_str = if let Some(n) = cell.execution_count {
+        format!(" [{}]", n)
+    }else{
+        if opts.show_line_detail < 4 {String::from("")}
+        else {String::from("[None]")}
+    };
+
+    let col_str = match (column, opts.show_line_detail) {
+        (None, _) => String::from(""),
+        (Some(col), 1..=3) => format!(" col.{}", col),
+        (Some(col), _) => format!(", column {}", col)
+    };
+
+    let mut info = match (opts.show_line_detail, opts.group_cells, icell) {
+        (1..=3, false, None) => format!("nb ({}) l.{}{}", cell_piece, m.line_number+1, col_str),
+        (_, false, None) => format!("Notebook {}, line {}{}", cell_piece, m.line_number+1, col_str),
+        (1..=2, true, _) => format!("l.{}{}", m.line_number+1, col_str),
+        (3, true, _) => format!("({}) l.{}{}", cell_piece, m.line_number+1, col_str),
+        (_, true, _) => format!("{}, line {}{}", cell_piece, m.line_number+1, col_str),
+        (1, false, Some(icell)) => format!("c.{} l.{}{}", icell, m.line_number+1, col_str),
+        (2, false, Some(icell)) => format!("c.{}{} l.{}{}", icell, exec_cnt_str, m.line_number+1, col_str),
+        (3, false, Some(icell)) => format!("c.{}{} ({}) l.{}{}", icell, exec_cnt_str, cell_piece, m.line_number+1, col_str),
+        (_, false, Some(icell)) => format!("Cell #{} (exec. {}) {}, line {}{}", icell, exec_cnt_str, cell_piece, m.line_number+1, col_str)
+    };
+    if let Some(extra) = cell_extra {
+        info.push_str(&extra);
+    }
+
+    write_styled(out, &info, &opts.colors.cell)?;
+    write!(out, ": \t")
+}
+
+/// The cell's ID and tags formatted to append to the cell information, e.g.
+/// " [id: 1a2b3c] [tags: solution, hide]", if --show-cell-id or --show-tags was
+/// given and the cell has them.
+fn cell_extra_detail(ctx: &MatchContext, opts: &CliOptions) -> Option<String> {
+    let mut extra = String::new();
+    if let (true, Some(id)) = (opts.show_cell_id, &ctx.cell.id) {
+        extra.push_str(&format!(" [id: {}]", id));
+    }
+    let tags = &ctx.cell.metadata.tags;
+    if opts.show_tags && !tags.is_empty() {
+        extra.push_str(&format!(" [tags: {}]", tags.join(", ")));
+    }
+    if extra.is_empty() { None } else { Some(extra) }
+}
+
+
+fn print_text_match(out: &mut dyn WriteColor, ctx: &MatchContext, m: &MatchedLine, tokens: Option<&[Token]>, opts: &CliOptions) -> io::Result<()> {
+    print_line_detail(out, ctx, m, opts)?;
+
+    let mut s = String::from(m.line);
+    trim_newline(&mut s);
+    // With --trim, skip the indentation but keep the offsets relative to the whole line
+    let indent = if opts.trim { s.len() - s.trim_start().len() } else { 0 };
+    if !out.supports_color() {
+        write!(out, "{}", &s[indent..])?;
+        return writeln!(out);
+    }
+
+    // The start/end values from the regex are byte offsets: https://docs.rs/regex/1.4.3/regex/struct.Match.html
+    // so we can slice the line between them, as long as they fall on character boundaries.
+    let mut last = indent;
+    for &(start, stop) in m.match_positions.iter() {
+        // the positions are relative to the original line, which may have had a trailing newline trimmed
+        // (or, with --trim, whitespace at the start that a match may have included)
+        let (start, stop) = (start.clamp(indent, s.len()), stop.min(s.len()));
+        if start < last || stop <= start || !s.is_char_boundary(start) || !s.is_char_boundary(stop) {
+            continue;
+        }
+        write_highlighted(out, &s, last..start, tokens)?;
+        write_styled(out, &s[start..stop], &opts.colors.matched)?;
+        last = stop;
+    }
+    write_highlighted(out, &s, last..s.len(), tokens)?;
+    writeln!(out)
+}
+
+/// Write `s[range]`, colored by the syntax highlighting tokens that overlap it if there are any
+pub fn write_highlighted(out: &mut dyn WriteColor, s: &str, range: std::ops::Range<usize>, tokens: Option<&[Token]>) -> io::Result<()> {
+    let tokens = match tokens {
+        Some(t) => t,
+        None => return write!(out, "{}", &s[range])
+    };
+    for (spec, token_range) in tokens.iter() {
+        let (start, stop) = (token_range.start.max(range.start), token_range.end.min(range.end));
+        if start < stop && s.is_char_boundary(start) && s.is_char_boundary(stop) {
+            write_styled(out, &s[start..stop], spec)?;
+        }
+    }
+    Ok(())
+}
+
+
+fn print_nontext_match(out: &mut dyn WriteColor, ctx: &MatchContext, m: &MatchedLine, opts: &CliOptions) -> io::Result<()> {
+    print_line_detail(out, ctx, m, opts)?;
+    write_styled(out, "Non-text output data matches.", &opts.colors.matched)?;
+    writeln!(out)
+}
+
+
+pub fn trim_newline(s: &mut String) {
+    // https://stackoverflow.com/a/55041833
+    if s.ends_with('\n') {
+        s.pop();
+        if s.ends_with('\r') {
+            s.pop();
+        }
+    }
+}
+
+/// Write `msg` in the given colors. If `out` does not support color (e.g. colors are off
+/// or it is a file), this is the same as writing it plainly.
+pub fn write_styled(out: &mut dyn WriteColor, msg: &str, spec: &ColorSpec) -> io::Result<()> {
+    if spec.is_none() {
+        return write!(out, "{}", msg);
+    }
+    out.set_color(spec)?;
+    write!(out, "{}", msg)?;
+    out.reset()
+}
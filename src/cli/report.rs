@@ -0,0 +1,198 @@
+//! Human-readable reports of all matches (`--report`)
+//!
+//! Unlike the standard output, reports are meant to be read outside the terminal,
+//! e.g. shared with people who would not run jrep themselves. Like the JSON printer,
+//! the report printers collect every match and write the whole report at the end.
+
+use std::io;
+use termcolor::WriteColor;
+use jrep::{MatchContext, MatchedLine, Printer, SearchOptions};
+use super::printer::{file_uri, FileRecord};
+
+/// Writes a standalone HTML page with one section per notebook.
+pub struct HtmlReportPrinter {
+    out: Box<dyn WriteColor>,
+    files: Vec<FileRecord>
+}
+
+impl HtmlReportPrinter {
+    pub fn new(out: Box<dyn WriteColor>) -> Self {
+        Self{out, files: Vec::new()}
+    }
+}
+
+const HTML_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { text-align: left; padding: 0.2em 0.8em; border-bottom: 1px solid #ddd; vertical-align: top; }
+td.text { font-family: monospace; white-space: pre-wrap; }
+mark { background-color: #ffd54f; font-weight: bold; }
+.nontext { font-style: italic; color: #777; }
+";
+
+impl Printer for HtmlReportPrinter {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        FileRecord::push_match(&mut self.files, ctx, m);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+
+    fn finish(&mut self, opts: &SearchOptions) -> io::Result<()> {
+        let out = self.out.as_mut();
+        let title = format!("jrep results for '{}'", html_escape(opts.pattern()));
+        let n_matches: usize = self.files.iter().map(|f| f.matches.len()).sum();
+
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>", title, HTML_STYLE)?;
+        writeln!(out, "<h1>{}</h1>", title)?;
+        writeln!(out, "<p>{} matches in {} notebooks.</p>", n_matches, self.files.len())?;
+
+        writeln!(out, "<ul>")?;
+        for (ifile, file) in self.files.iter().enumerate() {
+            writeln!(out, "<li><a href=\"#file-{}\">{}</a> ({} matches)</li>", ifile, html_escape(&file.path), file.matches.len())?;
+        }
+        writeln!(out, "</ul>")?;
+
+        for (ifile, file) in self.files.iter().enumerate() {
+            writeln!(out, "<section id=\"file-{}\">", ifile)?;
+            writeln!(out, "<h2><a href=\"{}\">{}</a></h2>", html_escape(&file_uri(&file.path)), html_escape(&file.path))?;
+            writeln!(out, "<table>\n<tr><th>Cell</th><th>Exec.</th><th>Type</th><th>Piece</th><th>Line</th><th>Text</th></tr>")?;
+            for rec in file.matches.iter() {
+                let exec = rec.execution_count.map(|n| n.to_string()).unwrap_or_default();
+                let text = match &rec.text {
+                    Some(t) => format!("<td class=\"text\">{}</td>", highlight_html(t, &rec.spans)),
+                    None => String::from("<td class=\"nontext\">Non-text output data matches.</td>")
+                };
+                let cell = rec.cell.map(|n| n.to_string()).unwrap_or_else(|| String::from("notebook"));
+                writeln!(out, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>{}</tr>",
+                         cell, exec, html_escape(&rec.cell_type), rec.piece, rec.line, text)?;
+            }
+            writeln!(out, "</table>\n</section>")?;
+        }
+
+        writeln!(out, "</body>\n</html>")?;
+        out.flush()
+    }
+}
+
+
+/// Writes a Markdown summary with a heading per notebook and the matched lines of
+/// each cell in a fenced code block, suitable for pasting into issues or PRs.
+pub struct MarkdownReportPrinter {
+    out: Box<dyn WriteColor>,
+    files: Vec<FileRecord>
+}
+
+impl MarkdownReportPrinter {
+    pub fn new(out: Box<dyn WriteColor>) -> Self {
+        Self{out, files: Vec::new()}
+    }
+}
+
+impl Printer for MarkdownReportPrinter {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        FileRecord::push_match(&mut self.files, ctx, m);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        self.out.as_mut()
+    }
+
+    fn finish(&mut self, opts: &SearchOptions) -> io::Result<()> {
+        let out = self.out.as_mut();
+        let n_matches: usize = self.files.iter().map(|f| f.matches.len()).sum();
+        writeln!(out, "# jrep results for `{}`\n", opts.pattern())?;
+        writeln!(out, "{} matches in {} notebooks.", n_matches, self.files.len())?;
+
+        for file in self.files.iter() {
+            writeln!(out, "\n## {}", file.path)?;
+
+            // Matches from the same piece of the same cell go in one code block
+            let mut start = 0;
+            while start < file.matches.len() {
+                let first = &file.matches[start];
+                let stop = file.matches[start..].iter()
+                    .position(|r| r.cell != first.cell || r.piece != first.piece)
+                    .map(|n| start + n)
+                    .unwrap_or_else(|| file.matches.len());
+                let group = &file.matches[start..stop];
+
+                let exec = match first.execution_count {
+                    Some(n) => format!(" [{}]", n),
+                    None => String::new()
+                };
+                match first.cell {
+                    Some(icell) => writeln!(out, "\n**Cell {}{}** ({}, {})\n", icell, exec, first.cell_type, first.piece)?,
+                    None => writeln!(out, "\n**Notebook** ({})\n", first.piece)?
+                }
+
+                let fence = code_fence(group.iter().filter_map(|r| r.text.as_deref()));
+                writeln!(out, "{}", fence)?;
+                for rec in group.iter() {
+                    match &rec.text {
+                        Some(t) => writeln!(out, "{:>4}: {}", rec.line, t)?,
+                        None => writeln!(out, "Non-text output data matches.")?
+                    }
+                }
+                writeln!(out, "{}", fence)?;
+
+                start = stop;
+            }
+        }
+        out.flush()
+    }
+}
+
+/// A code fence long enough that none of the lines can close it early
+fn code_fence<'a, I: Iterator<Item = &'a str>>(lines: I) -> String {
+    let mut longest = 0;
+    for line in lines {
+        let mut run = 0;
+        for c in line.chars() {
+            run = if c == '`' { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+
+/// Escape the characters that are special in HTML text and attribute values.
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// HTML-escape `text`, wrapping the byte ranges given by `spans` in `<mark>` tags.
+fn highlight_html(text: &str, spans: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for &(start, stop) in spans.iter() {
+        // spans are relative to the original line, which may have had a trailing newline trimmed
+        let (start, stop) = (start.min(text.len()), stop.min(text.len()));
+        if start < last || !text.is_char_boundary(start) || !text.is_char_boundary(stop) {
+            continue;
+        }
+        out.push_str(&html_escape(&text[last..start]));
+        out.push_str("<mark>");
+        out.push_str(&html_escape(&text[start..stop]));
+        out.push_str("</mark>");
+        last = stop;
+    }
+    out.push_str(&html_escape(&text[last..]));
+    out
+}
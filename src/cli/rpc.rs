@@ -0,0 +1,181 @@
+//! The `--rpc` mode, which takes search requests on stdin and streams the matches to stdout
+//!
+//! This is meant for editor extensions that keep jrep running as a child process. Messages are
+//! JSON-RPC 2.0, one JSON object per line in each direction. A `search` request's params are
+//! the pattern, optionally the paths to search (otherwise those given on the command line),
+//! and the same options as the parameters of `--serve`, e.g.
+//!
+//! ```json
+//! {"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"pattern": "read_csv", "paths": ["."], "recursive": true, "cell_type": ["code"]}}
+//! ```
+//!
+//! Each match is sent as soon as it is found, as a `match` notification whose params are the
+//! match in the same form as `--json-lines`, plus the id of the search request as `request`.
+//! Once the search is done, the response to the request gives the number of matches and any
+//! notebooks that could not be searched. Requests are handled one at a time; jrep exits at the
+//! end of stdin or on `exit`.
+
+use std::ffi::OsStr;
+use std::io::{self, BufRead, Write};
+use serde::Serialize;
+use serde_json::{json, Value};
+use termcolor::{NoColor, WriteColor};
+use jrep::{FoundNotebook, MatchContext, MatchedLine, Printer, RunErr, SearchOptions, Searched};
+use super::CliOptions;
+use super::printer::{LimitedPrinter, MatchRecord};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Sends each match as a notification as soon as it is found
+struct MatchNotifier<'a> {
+    out: NoColor<io::StdoutLock<'a>>,
+    id: &'a Value
+}
+
+#[derive(Serialize)]
+struct MatchParams<'a> {
+    /// The id of the search request; `id` is the cell's
+    request: &'a Value,
+    path: &'a str,
+    #[serde(flatten)]
+    record: MatchRecord
+}
+
+impl Printer for MatchNotifier<'_> {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        let params = MatchParams{request: self.id, path: &ctx.filename.to_string_lossy(), record: MatchRecord::new(ctx, m)};
+        writeln!(self.out, "{}", json!({"jsonrpc": "2.0", "method": "match", "params": params}))?;
+        self.out.flush()
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        &mut self.out
+    }
+}
+
+/// Answer requests from stdin until it ends or an `exit` request arrives. `paths` are the
+/// notebooks searched by requests that do not give their own.
+pub fn run(paths: &[FoundNotebook], opts: &CliOptions) -> exitcode::ExitCode {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Error reading a request: {}", e);
+                return exitcode::IOERR;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Requests without an id are notifications, which get no response
+        let (id, result, respond) = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                let result = handle(&request, &id, paths, opts);
+                (id, result, request.get("id").is_some())
+            },
+            Err(e) => (Value::Null, Err((PARSE_ERROR, format!("Could not parse the request: {}", e))), true)
+        };
+        let response = match &result {
+            Ok(Some(result)) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Ok(None) => json!({"jsonrpc": "2.0", "id": id, "result": null}),
+            Err((code, message)) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+        };
+        if respond {
+            if let Err(e) = send(&response) {
+                eprintln!("Error writing a response: {}", e);
+                return exitcode::IOERR;
+            }
+        }
+        if let Ok(None) = result {
+            break;
+        }
+    }
+    exitcode::OK
+}
+
+fn send(message: &Value) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "{}", message)?;
+    out.flush()
+}
+
+/// The result of one request, or `None` to exit, or the error code and message
+fn handle(request: &Value, id: &Value, paths: &[FoundNotebook], opts: &CliOptions) -> Result<Option<Value>, (i64, String)> {
+    let method = request.get("method").and_then(Value::as_str)
+        .ok_or((INVALID_REQUEST, String::from("The request has no method")))?;
+    let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+    match method {
+        "search" => search(&params, id, paths, opts).map(Some).map_err(|e| (INVALID_PARAMS, e.to_string())),
+        "exit" => Ok(None),
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'; use 'search' or 'exit'", method)))
+    }
+}
+
+fn search(params: &Value, id: &Value, paths: &[FoundNotebook], server_opts: &CliOptions) -> Result<Value, RunErr> {
+    let params = params.as_object().ok_or_else(|| RunErr::from("The params must be an object"))?;
+    let mut pairs = Vec::new();
+    for (name, value) in params.iter() {
+        let name = match name.as_str() {
+            "pattern" => "q",
+            "paths" | "recursive" => continue,
+            other => other
+        };
+        match value {
+            Value::Bool(true) => pairs.push((String::from(name), String::new())),
+            Value::Bool(false) | Value::Null => {},
+            Value::String(s) => pairs.push((String::from(name), s.clone())),
+            Value::Number(n) => pairs.push((String::from(name), n.to_string())),
+            Value::Array(values) => for v in values {
+                let v = v.as_str().map(String::from).unwrap_or_else(|| v.to_string());
+                pairs.push((String::from(name), v));
+            },
+            Value::Object(_) => return Err(RunErr::from(format!("The value of '{}' cannot be an object", name)))
+        }
+    }
+    let mut flags = vec!["--no-messages"];
+    if server_opts.lenient {
+        flags.push("--lenient");
+    }
+    if params.get("recursive").and_then(Value::as_bool).unwrap_or(false) {
+        flags.push("--recursive");
+    }
+    let opts = super::serve::request_options(&pairs, &flags)?;
+
+    let found;
+    let paths = match params.get("paths").and_then(Value::as_array) {
+        Some(requested) => {
+            let requested: Vec<&OsStr> = requested.iter().filter_map(Value::as_str).map(OsStr::new).collect();
+            found = jrep::find_notebooks(&requested, &opts.search)?;
+            &found
+        },
+        None => paths
+    };
+
+    let stdout = io::stdout();
+    let mut notifier = MatchNotifier{out: NoColor::new(stdout.lock()), id};
+    let mut errors = Vec::new();
+    let mut remaining = opts.max_results.unwrap_or(usize::MAX);
+    let mut n_matches = 0;
+    let mut notebooks_searched = 0;
+    for notebook in paths.iter() {
+        let mut limited = LimitedPrinter::new(&mut notifier, remaining);
+        match jrep::search_notebook(notebook, &opts.search, &mut limited) {
+            Ok(Searched::Done(n)) => {
+                notebooks_searched += 1;
+                n_matches += n.min(remaining);
+                remaining -= n.min(remaining);
+            },
+            Ok(_) => {},
+            Err(e) => errors.push(json!({"path": notebook.name.to_string_lossy(), "error": e.to_string()}))
+        }
+        if remaining == 0 {
+            break;
+        }
+    }
+    Ok(json!({"matches": n_matches, "notebooks_searched": notebooks_searched, "errors": errors}))
+}
@@ -0,0 +1,285 @@
+//! The `--serve` mode, which answers searches over HTTP instead of searching once
+//!
+//! When the server starts, it finds the notebooks in the paths it was given and parses them
+//! all into an index in memory (starting from the one of `--use-index`, if given), so that each
+//! search only has to check that the notebooks have not changed. Each `GET /search` request is
+//! searched as if its parameters had been given on the command line (`q` is the pattern, and
+//! e.g. `cell_type=code` is `--cell-type code`), and the matches are returned in the same form
+//! as `--json`. Only the options that select what is searched are allowed as parameters, so a
+//! request can never write files. Connections are handled by a fixed number of worker threads
+//! (`--threads`, or `DEFAULT_WORKERS`), which all take them from the listener and share the one
+//! index, so that a slow client only holds up one worker and no number of clients can make the
+//! server start more threads. Clients that stall are dropped after `CLIENT_TIMEOUT`.
+
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::json;
+use termcolor::{NoColor, WriteColor};
+use jrep::index::NotebookIndex;
+use jrep::{FoundNotebook, MatchContext, MatchedLine, Printer, RunErr, SearchOptions, Searched};
+use super::CliOptions;
+use super::printer::{FileRecord, LimitedPrinter};
+
+/// The parameters a search request may have, as the command line option they stand for
+/// (without the leading "--") and whether that option takes a value
+const SEARCH_PARAMS: &[(&str, &str, bool)] = &[
+    ("ignore_case", "ignore-case", false),
+    ("invert_match", "invert-match", false),
+    ("engine", "engine", true),
+    ("fuzzy", "fuzzy", true),
+    ("preset", "preset", true),
+    ("normalize", "normalize", true),
+    ("cell_type", "cell-type", true),
+    ("lang", "lang", true),
+    ("tag", "tag", true),
+    ("exclude_tag", "exclude-tag", true),
+    ("exec_count", "exec-count", true),
+    ("has_output", "has-output", false),
+    ("no_output", "no-output", false),
+    ("cells", "cells", true),
+    ("output_type", "output-type", true),
+    ("output_kind", "output-kind", true),
+    ("stream", "stream", true),
+    ("json_query", "json-query", true),
+    ("all_text_outputs", "all-text-outputs", false),
+    ("no_include_source", "no-include-source", false),
+    ("search_metadata", "search-metadata", false),
+    ("search_notebook_metadata", "search-notebook-metadata", false),
+    ("metadata_key", "metadata-key", true),
+    ("strip_ansi", "strip-ansi", false),
+    ("show_section", "show-section", false),
+    ("max_results", "max-results", true)
+];
+
+/// How long to wait for a client to send its request, or to take the response, before giving up on it
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many connections are handled at a time if --threads is not given
+pub const DEFAULT_WORKERS: usize = 8;
+
+/// Collects the matches of one search request
+struct Collector {
+    files: Vec<FileRecord>,
+    sink: NoColor<io::Sink>
+}
+
+impl Printer for Collector {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        FileRecord::push_match(&mut self.files, ctx, m);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        &mut self.sink
+    }
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    files: Vec<FileRecord>,
+    notebooks_searched: usize,
+    errors: Vec<FileError>
+}
+
+#[derive(Serialize)]
+struct FileError {
+    path: String,
+    error: String
+}
+
+struct Response {
+    status: &'static str,
+    body: String
+}
+
+impl Response {
+    fn ok<T: Serialize>(body: &T) -> Self {
+        // Serializing these structures cannot fail: they contain only strings, numbers, and options
+        Self{status: "200 OK", body: serde_json::to_string(body).unwrap()}
+    }
+
+    fn error(status: &'static str, msg: &str) -> Self {
+        Self{status, body: json!({"error": msg}).to_string()}
+    }
+}
+
+/// Index the notebooks in `paths`, then answer requests on the address from --bind and
+/// --port until killed. Returns the exit code if the server could not start.
+pub fn serve(paths: &[FoundNotebook], mut opts: CliOptions) -> exitcode::ExitCode {
+    // Nothing else holds the index yet, so it can be taken back out of the `Arc`
+    let old = opts.search.take_index().and_then(|index| Arc::try_unwrap(index).ok());
+    let (index, _, errors) = NotebookIndex::build(old, paths, &opts.search);
+    if !opts.no_messages {
+        for (filename, e) in errors.iter() {
+            eprintln!("Error in file {:?}: {}", filename, e);
+        }
+    }
+    let server = Server{paths, index: Arc::new(index), lenient: opts.lenient};
+
+    let address = SocketAddr::new(opts.serve_bind, opts.serve_port);
+    let listener = match TcpListener::bind(address) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Could not listen on {}: {}", address, e);
+            return exitcode::OSERR;
+        }
+    };
+    println!("Serving {} notebooks on http://{}", paths.len() - errors.len(), address);
+
+    let no_messages = opts.no_messages;
+    thread::scope(|scope| {
+        for _ in 0..opts.serve_workers {
+            let (server, listener) = (&server, &listener);
+            scope.spawn(move || {
+                for stream in listener.incoming() {
+                    if let Err(e) = stream.and_then(|stream| server.handle(stream)) {
+                        if !no_messages {
+                            eprintln!("Error answering a request: {}", e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+    exitcode::OK
+}
+
+struct Server<'a> {
+    paths: &'a [FoundNotebook],
+    index: Arc<NotebookIndex>,
+    lenient: bool
+}
+
+impl Server<'_> {
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // The headers do not matter, but are read so the client is not cut off while sending them
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+            header.clear();
+        }
+
+        let response = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["GET", target, _] => self.route(target),
+            [_, _, _] => Response::error("405 Method Not Allowed", "Only GET requests are supported"),
+            _ => Response::error("400 Bad Request", "Malformed request")
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status, response.body.len(), response.body
+        )?;
+        stream.flush()
+    }
+
+    fn route(&self, target: &str) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params: Vec<(String, String)> = query.split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (name, value) = p.split_once('=').unwrap_or((p, ""));
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect();
+        match path {
+            "/search" => match self.search(&params) {
+                Ok(response) => Response::ok(&response),
+                Err(e) => Response::error("400 Bad Request", &e.to_string())
+            },
+            "/notebooks" => {
+                let notebooks: Vec<_> = self.paths.iter().map(|p| p.name.to_string_lossy()).collect();
+                Response::ok(&json!({"notebooks": notebooks}))
+            },
+            _ => Response::error("404 Not Found", "Unknown endpoint; use /search or /notebooks")
+        }
+    }
+
+    fn search(&self, params: &[(String, String)]) -> Result<SearchResponse, RunErr> {
+        let mut flags = vec!["--no-messages"];
+        if self.lenient {
+            flags.push("--lenient");
+        }
+        let mut opts = request_options(params, &flags)?;
+        opts.search.set_index(Some(Arc::clone(&self.index)));
+        Ok(self.search_with(&opts))
+    }
+
+    fn search_with(&self, opts: &CliOptions) -> SearchResponse {
+        let mut collector = Collector{files: Vec::new(), sink: NoColor::new(io::sink())};
+        let mut errors = Vec::new();
+        let mut remaining = opts.max_results.unwrap_or(usize::MAX);
+        let mut notebooks_searched = 0;
+        for notebook in self.paths.iter() {
+            let mut limited = LimitedPrinter::new(&mut collector, remaining);
+            match jrep::search_notebook(notebook, &opts.search, &mut limited) {
+                Ok(Searched::Done(n)) => {
+                    notebooks_searched += 1;
+                    remaining -= n.min(remaining);
+                },
+                Ok(_) => {},
+                Err(e) => errors.push(FileError{path: notebook.name.to_string_lossy().into_owned(), error: e.to_string()})
+            }
+            if remaining == 0 {
+                break;
+            }
+        }
+        SearchResponse{files: collector.files, notebooks_searched, errors}
+    }
+}
+
+/// The options for a search request, parsed just like the command line would be. `params`
+/// are the names in `SEARCH_PARAMS` and their values, plus `q` for the pattern. `flags` are
+/// options the server sets itself (e.g. --no-messages), which need not be in `SEARCH_PARAMS`.
+pub fn request_options(params: &[(String, String)], flags: &[&str]) -> Result<CliOptions, RunErr> {
+    let mut args: Vec<OsString> = flags.iter().map(OsString::from).collect();
+    let mut pattern = None;
+    for (name, value) in params.iter() {
+        if name == "q" {
+            pattern = Some(value.clone());
+            continue;
+        }
+        let &(_, flag, takes_value) = SEARCH_PARAMS.iter().find(|(param, _, _)| param == name)
+            .ok_or_else(|| RunErr::from(format!("Unknown parameter '{}'", name)))?;
+        args.push(OsString::from(format!("--{}", flag)));
+        if takes_value {
+            args.push(OsString::from(value));
+        }
+    }
+    let has_preset = params.iter().any(|(name, _)| name == "preset");
+    match pattern {
+        Some(p) => args.extend([OsString::from("--"), OsString::from(p)]),
+        None if has_preset => {},
+        None => return Err(RunErr::from("Missing the 'q' parameter with the pattern to search for"))
+    }
+    CliOptions::from_args(args)
+}
+
+/// Decode a query string component, in which spaces may be '+' and other bytes '%XX'
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i+1..i+3).filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'+', _) => decoded.push(b' '),
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 2;
+            },
+            (b, _) => decoded.push(b)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,145 @@
+//! User-defined output lines for the `--format-str` option
+//!
+//! A template is plain text with placeholders in braces, e.g. `{path}:{cell}:{line}: {text}`.
+//! It is parsed once when the command line is read, so that a typo in a placeholder
+//! is reported immediately rather than once per match.
+
+use jrep::{MatchContext, MatchedLine, RunErr};
+use super::printer::trim_newline;
+
+#[derive(Clone, Copy)]
+enum Field {
+    Path,
+    Cell,
+    Id,
+    Exec,
+    CellType,
+    Piece,
+    MimeType,
+    Line,
+    Column,
+    FileLine,
+    Pointer,
+    Section,
+    Tags,
+    Match,
+    Text
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(Field::Path),
+            "cell" => Some(Field::Cell),
+            "id" => Some(Field::Id),
+            "exec" => Some(Field::Exec),
+            "cell_type" => Some(Field::CellType),
+            "piece" => Some(Field::Piece),
+            "mime_type" => Some(Field::MimeType),
+            "line" => Some(Field::Line),
+            "column" => Some(Field::Column),
+            "file_line" => Some(Field::FileLine),
+            "pointer" => Some(Field::Pointer),
+            "section" => Some(Field::Section),
+            "tags" => Some(Field::Tags),
+            "match" => Some(Field::Match),
+            "text" => Some(Field::Text),
+            _ => None
+        }
+    }
+}
+
+enum Piece {
+    Literal(String),
+    Field(Field)
+}
+
+pub struct FormatTemplate {
+    pieces: Vec<Piece>
+}
+
+impl FormatTemplate {
+    /// Parse a template string. `{{` and `}}` stand for literal braces.
+    pub fn parse(template: &str) -> Result<Self, RunErr> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => { chars.next(); literal.push('{'); },
+                '}' if chars.peek() == Some(&'}') => { chars.next(); literal.push('}'); },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(nc) => name.push(nc),
+                            None => return Err(RunErr::from("Unclosed '{' in --format-str"))
+                        }
+                    }
+                    let field = Field::from_name(&name)
+                        .ok_or_else(|| RunErr::from(format!("Unknown placeholder '{{{}}}' in --format-str", name)))?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(field));
+                },
+                '}' => return Err(RunErr::from("Unmatched '}' in --format-str (use '}}' for a literal brace)")),
+                _ => literal.push(c)
+            }
+        }
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Self{pieces})
+    }
+
+    /// Fill in the template for one match. Fields that do not apply to this match
+    /// (e.g. the execution count of a markdown cell) are left empty.
+    pub fn render(&self, ctx: &MatchContext, m: &MatchedLine) -> String {
+        let mut out = String::new();
+        for piece in self.pieces.iter() {
+            match piece {
+                Piece::Literal(s) => out.push_str(s),
+                Piece::Field(field) => out.push_str(&render_field(*field, ctx, m))
+            }
+        }
+        out
+    }
+}
+
+fn render_field(field: Field, ctx: &MatchContext, m: &MatchedLine) -> String {
+    let opt_to_string = |v: Option<usize>| v.map(|n| n.to_string()).unwrap_or_default();
+    match field {
+        Field::Path => ctx.filename.to_string_lossy().into_owned(),
+        Field::Cell => opt_to_string(ctx.icell),
+        Field::Id => ctx.cell.id.clone().unwrap_or_default(),
+        Field::Exec => opt_to_string(ctx.cell.execution_count),
+        Field::CellType => ctx.cell.cell_type.clone(),
+        Field::Piece => String::from(ctx.piece),
+        Field::MimeType => String::from(m.mime_type.unwrap_or_default()),
+        Field::Line => (m.line_number + 1).to_string(),
+        Field::Column => opt_to_string(m.first_column()),
+        Field::FileLine => opt_to_string(m.file_line),
+        Field::Pointer => m.pointer.clone(),
+        Field::Section => String::from(ctx.section.unwrap_or_default()),
+        Field::Tags => ctx.cell.metadata.tags.join(","),
+        Field::Match => {
+            match m.match_positions.first() {
+                Some(&(start, stop)) if m.is_text => String::from(&m.line[start..stop]),
+                _ => String::new()
+            }
+        },
+        Field::Text => {
+            if m.is_text {
+                let mut s = String::from(m.line);
+                trim_newline(&mut s);
+                s
+            }else{
+                String::from("Non-text output data matches.")
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! The `--toc` mode, which prints an outline of each notebook's markdown headings
+//!
+//! Each heading is listed with the index of its cell, indented by its level relative to
+//! the highest level in the notebook (so a notebook whose top headings are `##` is not
+//! indented at all). The index can be given to `--cells` or `--cat --cells` to go there.
+
+use serde::Serialize;
+use jrep::{Notebook, SearchOptions};
+
+#[derive(Serialize)]
+pub struct Heading {
+    pub cell: usize,
+    pub line: usize,
+    pub level: usize,
+    pub title: String
+}
+
+/// The headings in the markdown cells of `nb` that pass the cell filters
+pub fn notebook_headings(nb: &Notebook, opts: &SearchOptions) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    for icell in opts.searched_cells(nb) {
+        let cell = &nb.cells[icell];
+        if cell.cell_type != "markdown" {
+            continue;
+        }
+        for (iline, heading) in jrep::markdown_headings(&cell.source) {
+            let level = heading.chars().take_while(|&c| c == '#').count();
+            let title = String::from(heading[level..].trim());
+            headings.push(Heading{cell: icell, line: iline, level, title});
+        }
+    }
+    headings
+}
+
+/// Print the outline of one notebook under its file name
+pub fn print_toc(filename: &std::ffi::OsString, headings: &[Heading]) {
+    println!("{}", filename.to_string_lossy());
+    let top_level = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let width = headings.iter().map(|h| h.cell.to_string().len()).max().unwrap_or(1);
+    for h in headings.iter() {
+        let indent = "  ".repeat(h.level - top_level);
+        println!("  {:>width$}  {}{}", h.cell, indent, h.title, width = width);
+    }
+}
@@ -0,0 +1,221 @@
+//! Checking notebooks against the nbformat 4 schema for the `--validate` option
+//!
+//! This is a hand-written version of the checks in nbformat's JSON schema (v4.0 to v4.5):
+//! the required keys, their types, and the keys allowed in each kind of cell and output.
+//! It works on the raw JSON rather than our `Notebook` struct, since that is deliberately
+//! forgiving about what it accepts. Each problem found is reported with the JSON Pointer
+//! of the value it concerns, and checking continues so that all problems are listed at once.
+
+use serde_json::{Map, Value};
+
+pub struct Violation {
+    pub pointer: String,
+    pub message: String
+}
+
+const CELL_KEYS: [&str; 5] = ["id", "cell_type", "metadata", "source", "attachments"];
+const CODE_CELL_KEYS: [&str; 6] = ["id", "cell_type", "metadata", "source", "outputs", "execution_count"];
+
+/// Check a parsed notebook file, returning every problem found (empty if it is valid).
+pub fn validate_notebook(nb: &Value) -> Vec<Violation> {
+    let mut v = Validator{violations: Vec::new()};
+    v.notebook(nb);
+    v.violations
+}
+
+struct Validator {
+    violations: Vec<Violation>
+}
+
+impl Validator {
+    fn report(&mut self, pointer: &str, message: String) {
+        self.violations.push(Violation{pointer: String::from(pointer), message});
+    }
+
+    /// Get the object at `pointer`, reporting a problem if `value` is not one
+    fn object<'a>(&mut self, value: &'a Value, pointer: &str, what: &str) -> Option<&'a Map<String, Value>> {
+        let obj = value.as_object();
+        if obj.is_none() {
+            self.report(pointer, format!("{} must be an object", what));
+        }
+        obj
+    }
+
+    /// Check that `obj[key]` exists and passes `check`; `expected` describes what it should be.
+    fn required(&mut self, obj: &Map<String, Value>, pointer: &str, key: &str, check: fn(&Value) -> bool, expected: &str) {
+        match obj.get(key) {
+            Some(value) => self.check_type(value, &format!("{}/{}", pointer, key), key, check, expected),
+            None => self.report(pointer, format!("missing required key '{}'", key))
+        }
+    }
+
+    /// Like `required`, but it is fine for `key` to be missing
+    fn optional(&mut self, obj: &Map<String, Value>, pointer: &str, key: &str, check: fn(&Value) -> bool, expected: &str) {
+        if let Some(value) = obj.get(key) {
+            self.check_type(value, &format!("{}/{}", pointer, key), key, check, expected);
+        }
+    }
+
+    fn check_type(&mut self, value: &Value, pointer: &str, key: &str, check: fn(&Value) -> bool, expected: &str) {
+        if !check(value) {
+            self.report(pointer, format!("'{}' must be {}", key, expected));
+        }
+    }
+
+    fn only_keys(&mut self, obj: &Map<String, Value>, pointer: &str, allowed: &[&str], what: &str) {
+        for key in obj.keys() {
+            if !allowed.contains(&key.as_str()) {
+                self.report(pointer, format!("unexpected key '{}' in {}", key, what));
+            }
+        }
+    }
+
+    fn notebook(&mut self, nb: &Value) {
+        let nb = match self.object(nb, "", "the notebook") {
+            Some(nb) => nb,
+            None => return
+        };
+
+        match nb.get("nbformat").and_then(Value::as_u64) {
+            Some(4) => {},
+            Some(n) => {
+                self.report("/nbformat", format!("only nbformat 4 notebooks can be validated, this is version {}", n));
+                return;
+            },
+            None => self.required(nb, "", "nbformat", is_uint, "an integer")
+        }
+        self.required(nb, "", "nbformat_minor", is_uint, "an integer");
+        self.required(nb, "", "metadata", Value::is_object, "an object");
+        self.required(nb, "", "cells", Value::is_array, "a list");
+        self.only_keys(nb, "", &["metadata", "nbformat_minor", "nbformat", "cells"], "the notebook");
+
+        if let Some(meta) = nb.get("metadata").and_then(Value::as_object) {
+            self.notebook_metadata(meta);
+        }
+
+        // Cell IDs are required from 4.5 on, and not allowed before then
+        let minor = nb.get("nbformat_minor").and_then(Value::as_u64).unwrap_or(0);
+        if let Some(cells) = nb.get("cells").and_then(Value::as_array) {
+            for (icell, cell) in cells.iter().enumerate() {
+                self.cell(cell, &format!("/cells/{}", icell), minor);
+            }
+        }
+    }
+
+    fn notebook_metadata(&mut self, meta: &Map<String, Value>) {
+        if let Some(kernelspec) = meta.get("kernelspec") {
+            if let Some(ks) = self.object(kernelspec, "/metadata/kernelspec", "'kernelspec'") {
+                self.required(ks, "/metadata/kernelspec", "name", Value::is_string, "a string");
+                self.required(ks, "/metadata/kernelspec", "display_name", Value::is_string, "a string");
+            }
+        }
+        if let Some(language_info) = meta.get("language_info") {
+            if let Some(li) = self.object(language_info, "/metadata/language_info", "'language_info'") {
+                self.required(li, "/metadata/language_info", "name", Value::is_string, "a string");
+            }
+        }
+    }
+
+    fn cell(&mut self, cell: &Value, pointer: &str, minor: u64) {
+        let cell = match self.object(cell, pointer, "a cell") {
+            Some(cell) => cell,
+            None => return
+        };
+
+        self.required(cell, pointer, "metadata", Value::is_object, "an object");
+        self.required(cell, pointer, "source", is_multiline, "a string or a list of strings");
+        if minor >= 5 {
+            self.required(cell, pointer, "id", is_cell_id, "1 to 64 letters, numbers, '-', or '_'");
+        }else if cell.contains_key("id") {
+            self.report(pointer, format!("cell IDs are only allowed from nbformat 4.5 on, this notebook is 4.{}", minor));
+        }
+
+        match cell.get("cell_type").and_then(Value::as_str) {
+            Some("code") => {
+                self.required(cell, pointer, "execution_count", is_uint_or_null, "an integer or null");
+                self.required(cell, pointer, "outputs", Value::is_array, "a list");
+                self.only_keys(cell, pointer, &CODE_CELL_KEYS, "a code cell");
+                if let Some(outputs) = cell.get("outputs").and_then(Value::as_array) {
+                    for (ioutp, outp) in outputs.iter().enumerate() {
+                        self.output(outp, &format!("{}/outputs/{}", pointer, ioutp));
+                    }
+                }
+            },
+            Some(cell_type @ ("markdown" | "raw")) => {
+                self.optional(cell, pointer, "attachments", Value::is_object, "an object");
+                self.only_keys(cell, pointer, &CELL_KEYS, &format!("a {} cell", cell_type));
+            },
+            Some(other) => self.report(&format!("{}/cell_type", pointer), format!("unknown cell type '{}'", other)),
+            None => self.required(cell, pointer, "cell_type", Value::is_string, "a string")
+        }
+    }
+
+    fn output(&mut self, outp: &Value, pointer: &str) {
+        let outp = match self.object(outp, pointer, "an output") {
+            Some(outp) => outp,
+            None => return
+        };
+
+        match outp.get("output_type").and_then(Value::as_str) {
+            Some("execute_result") => {
+                self.required(outp, pointer, "execution_count", is_uint_or_null, "an integer or null");
+                self.mime_bundle(outp, pointer);
+                self.only_keys(outp, pointer, &["output_type", "execution_count", "data", "metadata"], "an execute_result output");
+            },
+            Some("display_data") => {
+                self.mime_bundle(outp, pointer);
+                self.optional(outp, pointer, "transient", Value::is_object, "an object");
+                self.only_keys(outp, pointer, &["output_type", "data", "metadata", "transient"], "a display_data output");
+            },
+            Some("stream") => {
+                self.required(outp, pointer, "name", Value::is_string, "a string");
+                self.required(outp, pointer, "text", is_multiline, "a string or a list of strings");
+                self.only_keys(outp, pointer, &["output_type", "name", "text"], "a stream output");
+            },
+            Some("error") => {
+                self.required(outp, pointer, "ename", Value::is_string, "a string");
+                self.required(outp, pointer, "evalue", Value::is_string, "a string");
+                self.required(outp, pointer, "traceback", is_string_list, "a list of strings");
+                self.only_keys(outp, pointer, &["output_type", "ename", "evalue", "traceback"], "an error output");
+            },
+            Some(other) => self.report(&format!("{}/output_type", pointer), format!("unknown output type '{}'", other)),
+            None => self.required(outp, pointer, "output_type", Value::is_string, "a string")
+        }
+    }
+
+    /// The "data" and "metadata" of execute_result and display_data outputs
+    fn mime_bundle(&mut self, outp: &Map<String, Value>, pointer: &str) {
+        self.required(outp, pointer, "metadata", Value::is_object, "an object");
+        self.required(outp, pointer, "data", Value::is_object, "an object");
+        if let Some(data) = outp.get("data").and_then(Value::as_object) {
+            for (mime_type, value) in data.iter() {
+                // JSON types hold the JSON itself, everything else is text (images are base64 text)
+                if !jrep::is_json(mime_type) && !is_multiline(value) {
+                    let data_pointer = format!("{}/data/{}", pointer, jrep::escape_pointer_token(mime_type));
+                    self.report(&data_pointer, format!("'{}' data must be a string or a list of strings", mime_type));
+                }
+            }
+        }
+    }
+}
+
+fn is_uint(value: &Value) -> bool {
+    value.is_u64()
+}
+
+fn is_uint_or_null(value: &Value) -> bool {
+    value.is_u64() || value.is_null()
+}
+
+fn is_string_list(value: &Value) -> bool {
+    matches!(value, Value::Array(a) if a.iter().all(Value::is_string))
+}
+
+fn is_multiline(value: &Value) -> bool {
+    value.is_string() || is_string_list(value)
+}
+
+fn is_cell_id(value: &Value) -> bool {
+    matches!(value, Value::String(id)
+        if (1..=64).contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
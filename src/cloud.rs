@@ -0,0 +1,119 @@
+//! Searching the notebooks in cloud object stores (Amazon S3, Google Cloud Storage, and Azure
+//! Blob Storage), with the `object_store` feature
+//!
+//! A path like "s3://bucket/prefix/" is listed like a directory: with --recursive, everything
+//! under the prefix, otherwise only the objects directly in it. Each notebook is named by its
+//! full URL, and reading it (see `read_notebook_data`) downloads it into memory to be searched
+//! just like a notebook piped to stdin. Since each search thread downloads one notebook at a
+//! time, no more than --threads requests are made at once.
+//!
+//! Credentials and other settings come from the same environment variables as the providers'
+//! own tools use, e.g. AWS_ACCESS_KEY_ID, AWS_REGION, and AWS_ENDPOINT for S3,
+//! GOOGLE_APPLICATION_CREDENTIALS for GCS, and AZURE_STORAGE_ACCOUNT_NAME for Azure.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use futures::TryStreamExt;
+use object_store::ObjectStore;
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use tokio::runtime::Runtime;
+use crate::{FoundNotebook, RunErr, SearchOptions, Source};
+
+/// The runtime that the requests run on, made the first time one is needed
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// The store for each bucket (e.g. "s3://bucket"), so that each is only set up once
+static STORES: OnceLock<Mutex<HashMap<String, Arc<dyn ObjectStore>>>> = OnceLock::new();
+
+fn runtime() -> Result<&'static Runtime, RunErr> {
+    if let Some(rt) = RUNTIME.get() {
+        return Ok(rt);
+    }
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build()
+        .map_err(|e| RunErr{msg: format!("Could not start the runtime for cloud storage requests: {}", e)})?;
+    Ok(RUNTIME.get_or_init(|| rt))
+}
+
+/// Split `url` into its bucket (with the scheme) and the path of the object or prefix in it
+fn split_url(url: &str) -> Result<(&str, &str), RunErr> {
+    let after_scheme = url.find("://").map(|i| i + 3).unwrap_or(0);
+    let (bucket, path) = match url[after_scheme..].find('/') {
+        Some(i) => (&url[..after_scheme + i], &url[after_scheme + i + 1..]),
+        None => (url, "")
+    };
+    if bucket.len() == after_scheme {
+        return Err(RunErr{msg: format!("{} does not name a bucket", url)});
+    }
+    Ok((bucket, path))
+}
+
+fn store(bucket: &str) -> Result<Arc<dyn ObjectStore>, RunErr> {
+    let mut stores = STORES.get_or_init(Default::default).lock().unwrap();
+    if let Some(store) = stores.get(bucket) {
+        return Ok(Arc::clone(store));
+    }
+    let built: object_store::Result<Arc<dyn ObjectStore>> = if bucket.starts_with("s3://") {
+        AmazonS3Builder::from_env().with_url(bucket).build().map(|s| Arc::new(s) as _)
+    }else if bucket.starts_with("gs://") {
+        GoogleCloudStorageBuilder::from_env().with_url(bucket).build().map(|s| Arc::new(s) as _)
+    }else{
+        MicrosoftAzureBuilder::from_env().with_url(bucket).build().map(|s| Arc::new(s) as _)
+    };
+    let store = built.map_err(|e| RunErr{msg: format!("Could not connect to {}: {}", bucket, e)})?;
+    stores.insert(bucket.to_string(), Arc::clone(&store));
+    Ok(store)
+}
+
+/// The notebooks at `url`, named by their URLs: the object itself if it has a notebook's
+/// extension, otherwise those under it as a prefix that would be searched if it were a directory
+pub fn list_notebooks(url: &OsStr, opts: &SearchOptions) -> Result<Vec<FoundNotebook>, RunErr> {
+    let url = url.to_str().ok_or_else(|| RunErr{msg: format!("{:?} is not a valid URL", url)})?;
+    let found = |url: String| FoundNotebook{name: OsString::from(&url), source: Source::Cloud(url)};
+    if crate::walk::has_notebook_extension(std::path::Path::new(url), opts) {
+        return Ok(vec![found(url.to_string())]);
+    }
+
+    let (bucket, prefix) = split_url(url)?;
+    let store = store(bucket)?;
+    let prefix = ObjectPath::from(prefix);
+    let listed = runtime()?.block_on(async {
+        if opts.recursive || opts.max_depth.is_some() {
+            store.list(Some(&prefix)).try_collect::<Vec<_>>().await
+        }else{
+            store.list_with_delimiter(Some(&prefix)).await.map(|listing| listing.objects)
+        }
+    }).map_err(|e| RunErr{msg: format!("Could not list {}: {}", url, e)})?;
+
+    let mut notebooks: Vec<FoundNotebook> = listed.into_iter().filter(|meta| {
+        // As for a directory, the path under the prefix is what the filters apply to
+        let rel_path: PathBuf = match meta.location.prefix_match(&prefix) {
+            Some(parts) => parts.map(|p| p.as_ref().to_string()).collect(),
+            None => return false
+        };
+        opts.max_depth.is_none_or(|d| rel_path.components().count() <= d)
+            && crate::walk::keeps_member(&rel_path, opts)
+    }).map(|meta| found(format!("{}/{}", bucket, meta.location))).collect();
+    notebooks.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(notebooks)
+}
+
+/// The text of the notebook at `url`
+pub fn read_object(url: &str) -> io::Result<String> {
+    let other = |e: RunErr| io::Error::other(e.msg);
+    let (bucket, path) = split_url(url).map_err(other)?;
+    let store = store(bucket).map_err(other)?;
+    let path = ObjectPath::from(path);
+    let bytes = runtime().map_err(other)?
+        .block_on(async { store.get(&path).await?.bytes().await })
+        .map_err(|e| match e {
+            object_store::Error::NotFound{..} => io::Error::new(io::ErrorKind::NotFound, "No such object"),
+            e => io::Error::other(format!("Could not download {}: {}", url, e))
+        })?;
+    String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,91 @@
+//! High-entropy string detection for the `--entropy` option
+//!
+//! Instead of matching a pattern, this splits each line into runs of characters from a
+//! charset (e.g. the base64 alphabet) and reports the runs that are long enough and whose
+//! Shannon entropy, in bits per character, is at least the threshold. Random tokens and
+//! keys score high; words and identifiers, which reuse a few letters, score low. Note the
+//! ceiling set by the charset: hex strings can never exceed 4 bits per character, so a
+//! threshold around 3 suits `hex`, while 4.5 is a common choice for `base64`.
+
+use crate::RunErr;
+
+#[derive(Clone, Copy)]
+pub enum Charset {
+    Base64,
+    Hex,
+    Any
+}
+
+impl Charset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "base64" => Some(Charset::Base64),
+            "hex" => Some(Charset::Hex),
+            "any" => Some(Charset::Any),
+            _ => None
+        }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        match self {
+            // Both the standard and URL-safe alphabets
+            Charset::Base64 => c.is_ascii_alphanumeric() || "+/=_-".contains(c),
+            Charset::Hex => c.is_ascii_hexdigit(),
+            // Anything that could be part of a token, so not spaces, quotes, or brackets
+            Charset::Any => !c.is_whitespace() && !"'\"`,;()[]{}<>".contains(c)
+        }
+    }
+}
+
+pub struct EntropyScanner {
+    threshold: f64,
+    min_length: usize,
+    charset: Charset
+}
+
+impl EntropyScanner {
+    pub fn new(threshold: &str, min_length: usize, charset: Charset) -> Result<Self, RunErr> {
+        let threshold = threshold.parse::<f64>().ok().filter(|t| t.is_finite() && *t >= 0.0)
+            .ok_or_else(|| RunErr::from("The value for '--entropy' must be a non-negative number of bits per character"))?;
+        Ok(Self{threshold, min_length, charset})
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find_positions(text).is_empty()
+    }
+
+    /// Return the (start, stop) byte offsets of the runs of charset characters in `text`
+    /// that are at least the minimum length and have at least the threshold entropy.
+    pub fn find_positions(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut positions = Vec::new();
+        let mut run_start: Option<usize> = None;
+        for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+            match (run_start, i < text.len() && self.charset.contains(c)) {
+                (None, true) => run_start = Some(i),
+                (Some(start), false) => {
+                    let run = &text[start..i];
+                    if run.chars().count() >= self.min_length && shannon_entropy(run) >= self.threshold {
+                        positions.push((start, i));
+                    }
+                    run_start = None;
+                },
+                _ => {}
+            }
+        }
+        positions
+    }
+}
+
+/// The Shannon entropy of the characters in `s`, in bits per character
+fn shannon_entropy(s: &str) -> f64 {
+    let mut counts = std::collections::HashMap::new();
+    let mut n = 0;
+    for c in s.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+        n += 1;
+    }
+    counts.values().map(|&count| {
+        let p = count as f64 / n as f64;
+        -p * p.log2()
+    }).sum()
+}
@@ -0,0 +1,178 @@
+//! The notebooks to search, and where each is read from
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "object_store")]
+use crate::cloud;
+#[cfg(feature = "git2")]
+use crate::RevBlob;
+use crate::{archive, git, jupytext, remote, walk, zeppelin, ArchiveMember, Notebook, RemoteFile, RunErr, SearchOptions};
+use crate::notebook::parse_notebook_keeping;
+use crate::options::SortKey;
+
+/// The path that stands for a notebook piped to stdin
+pub const STDIN_PATH: &str = "-";
+
+/// The URL schemes of the cloud object stores that jrep can search with the `object_store`
+/// feature: Amazon S3, Google Cloud Storage, and Azure Blob Storage
+const CLOUD_SCHEMES: [&str; 3] = ["s3://", "gs://", "az://"];
+
+/// Whether `path` is the URL of an object or prefix in a cloud object store
+pub fn is_cloud_url(path: &OsStr) -> bool {
+    path.to_str().is_some_and(|p| CLOUD_SCHEMES.iter().any(|scheme| p.starts_with(scheme)))
+}
+
+/// A notebook to search: what it is called in the output, and where it is read from
+#[derive(Debug)]
+pub struct FoundNotebook {
+    /// The path of the notebook as given or found in a directory, "-" for stdin, or e.g.
+    /// "handouts.zip!intro.ipynb" for a notebook in an archive
+    pub name: OsString,
+    pub source: Source
+}
+
+impl FoundNotebook {
+    /// The notebook file at `path`, named by its path
+    pub fn local<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        Self{name: path.as_os_str().to_os_string(), source: Source::Local(path.to_path_buf())}
+    }
+
+    /// Rename a notebook file with `show`, which is given its path, or a notebook in an archive
+    /// file with the archive's path renamed that way (e.g. to show absolute paths). Other
+    /// notebooks are not on this computer, so keep their names.
+    pub fn rename_path<F: Fn(&Path) -> OsString>(&mut self, show: F) {
+        match &self.source {
+            Source::Local(path) => self.name = show(path),
+            Source::Archive(member) => self.name = archive::renamed(member, &show),
+            _ => {}
+        }
+    }
+
+    /// Whether the notebook is in Jupyter's own format, rather than one that jrep converts
+    /// (a jupytext script or a Zeppelin note), judging by its name
+    pub fn is_jupyter_format(&self) -> bool {
+        jupytext::Format::of(&self.name).is_none() && !zeppelin::is_note(&self.name)
+    }
+
+    /// The text of the notebook, wherever it is
+    pub fn read(&self) -> std::io::Result<Cow<'_, str>> {
+        match &self.source {
+            Source::Local(path) => fs::read_to_string(path).map(Cow::Owned),
+            Source::Stdin => std::io::read_to_string(std::io::stdin()).map(Cow::Owned),
+            Source::Archive(member) => archive::read_member(member),
+            #[cfg(feature = "git2")]
+            Source::Rev(blob) => git::read_blob(blob).map(Cow::Owned),
+            Source::Remote(file) => remote::read_file(file).map(Cow::Owned),
+            #[cfg(feature = "object_store")]
+            Source::Cloud(url) => cloud::read_object(url).map(Cow::Owned),
+            #[cfg(not(feature = "object_store"))]
+            Source::Cloud(url) => Err(std::io::Error::other(format!("jrep was not compiled with the 'object_store' feature, so {} cannot be read", url)))
+        }
+    }
+
+    /// Read and parse the notebook with all of its outputs, for uses other than searching it
+    /// (`search_notebook` only keeps the outputs that `opts` searches). Only the parsing
+    /// options of `opts` (e.g. --lenient) matter.
+    pub fn parse(&self, opts: &SearchOptions) -> Result<Notebook, RunErr> {
+        let data = self.read()?;
+        parse_notebook_keeping(&data, &self.name, None, opts)
+    }
+}
+
+/// Where a notebook is read from
+#[derive(Debug)]
+pub enum Source {
+    /// A notebook file
+    Local(std::path::PathBuf),
+    /// A notebook piped to stdin
+    Stdin,
+    /// A notebook in a zip or tar archive
+    Archive(ArchiveMember),
+    /// A notebook as it was at a git revision (--rev)
+    #[cfg(feature = "git2")]
+    Rev(RevBlob),
+    /// A notebook on the --remote host
+    Remote(RemoteFile),
+    /// The URL of a notebook in a cloud object store
+    Cloud(String)
+}
+
+/// The notebooks to search for the given paths: files as they are, and the notebooks in
+/// directories (recursively with --recursive), less those left out by --include and
+/// --exclude, sorted if --sort was given.
+pub fn find_notebooks(paths_raw: &[&OsStr], opts: &SearchOptions) -> Result<Vec<FoundNotebook>, RunErr> {
+    let mut paths: Vec<FoundNotebook> = Vec::new();
+    for &p in paths_raw {
+        let curr_path = Path::new(p);
+        if p == STDIN_PATH {
+            paths.push(FoundNotebook{name: OsString::from(p), source: Source::Stdin});
+        }else if let Some(rev) = &opts.rev {
+            #[cfg(feature = "git2")]
+            paths.extend(git::list_notebooks_at(p, rev, opts)?);
+            #[cfg(not(feature = "git2"))]
+            return Err(RunErr{msg: format!("jrep was not compiled with the 'git2' feature, so cannot read notebooks at revision '{}'", rev)});
+        }else if is_cloud_url(p) {
+            #[cfg(feature = "object_store")]
+            paths.extend(cloud::list_notebooks(p, opts)?);
+            #[cfg(not(feature = "object_store"))]
+            return Err(RunErr{msg: format!("jrep was not compiled with the 'object_store' feature, so {} cannot be searched", p.to_string_lossy())});
+        }else if curr_path.is_file() && archive::is_archive(curr_path) {
+            paths.extend(archive::list_notebooks(curr_path, opts)?);
+        }else if curr_path.is_file() {
+            if opts.path_filter.keeps_file(curr_path) {
+                paths.push(FoundNotebook::local(curr_path));
+            }
+        }else if curr_path.is_dir() {
+            walk::get_notebooks_in_dir(curr_path, &mut paths, opts)?;
+        } 
+    }
+    if let Some(target) = &opts.remote {
+        paths.extend(target.list_notebooks(opts)?);
+    }
+
+    if paths.is_empty() {
+        return Err(RunErr{msg: "No notebook files listed or found in the given directories.".to_string()})
+    }
+
+    // It is not an error if none of them changed, only nothing to search
+    if let Some(rev) = &opts.changed {
+        let changed = git::changed_files(paths_raw, rev.as_deref())?;
+        paths.retain(|nb| match &nb.source {
+            Source::Stdin => true,
+            Source::Local(path) => fs::canonicalize(path).is_ok_and(|p| changed.contains(&p)),
+            _ => false
+        });
+    }
+
+    if let Some(key) = &opts.sort_by {
+        sort_paths(&mut paths, key, opts.sort_reverse);
+    }
+
+    Ok(paths)
+}
+
+fn sort_paths(paths: &mut [FoundNotebook], key: &SortKey, reverse: bool) {
+    // Files whose metadata cannot be read sort first; they will error when searched anyway.
+    // Notebooks that are not files have no metadata either.
+    let metadata = |nb: &FoundNotebook| match &nb.source {
+        Source::Local(path) => fs::metadata(path),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    };
+    match key {
+        SortKey::Path => paths.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Mtime => paths.sort_by_cached_key(|p| {
+            metadata(p).and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH)
+        }),
+        SortKey::Size => paths.sort_by_cached_key(|p| {
+            metadata(p).map(|m| m.len()).unwrap_or(0)
+        })
+    }
+
+    if reverse {
+        paths.reverse();
+    }
+}
+
@@ -0,0 +1,160 @@
+//! Ranked full-text search of the index for `--fulltext`, when compiled with tantivy
+//!
+//! When jrep has the 'tantivy' feature, `--build-index` also writes a tantivy index next to
+//! the JSON one, with one document per cell. Its `source` and `output` fields are tokenized
+//! text, and are what a query searches by default; `path`, `cell_type`, `tag`, and `language`
+//! are kept whole so they can be used as filters, e.g. `cell_type:markdown "data cleaning"`.
+//! Cells are ranked by BM25, so the cells that best match a few words come first even if
+//! none of them has all of the words on one line.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, INDEXED, STORED, STRING, TEXT};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{Index, IndexWriter, TantivyDocument};
+use crate::index::NotebookIndex;
+use crate::RunErr;
+
+/// The tantivy index that goes with the JSON index at `index_file`
+pub fn index_dir(index_file: &Path) -> PathBuf {
+    let mut name = index_file.as_os_str().to_os_string();
+    name.push(".tantivy");
+    PathBuf::from(name)
+}
+
+fn tantivy_err<E: std::fmt::Display>(e: E) -> RunErr {
+    RunErr{msg: format!("Full-text index error: {}", e)}
+}
+
+struct Fields {
+    path: Field,
+    cell: Field,
+    cell_type: Field,
+    tag: Field,
+    language: Field,
+    source: Field,
+    output: Field
+}
+
+impl Fields {
+    fn schema() -> (Schema, Self) {
+        let mut builder = Schema::builder();
+        let fields = Self{
+            path: builder.add_text_field("path", STRING | STORED),
+            cell: builder.add_u64_field("cell", INDEXED | STORED),
+            cell_type: builder.add_text_field("cell_type", STRING | STORED),
+            tag: builder.add_text_field("tag", STRING),
+            language: builder.add_text_field("language", STRING),
+            source: builder.add_text_field("source", TEXT | STORED),
+            output: builder.add_text_field("output", TEXT)
+        };
+        (builder.build(), fields)
+    }
+
+    fn of(schema: &Schema) -> Result<Self, RunErr> {
+        let get = |name: &str| schema.get_field(name).map_err(tantivy_err);
+        Ok(Self{
+            path: get("path")?,
+            cell: get("cell")?,
+            cell_type: get("cell_type")?,
+            tag: get("tag")?,
+            language: get("language")?,
+            source: get("source")?,
+            output: get("output")?
+        })
+    }
+}
+
+/// Replace the tantivy index in `dir` with one for the notebooks in `index`
+pub fn build(dir: &Path, index: &NotebookIndex) -> Result<(), RunErr> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+    let (schema, fields) = Fields::schema();
+    let tindex = Index::create_in_dir(dir, schema).map_err(tantivy_err)?;
+    let mut writer: IndexWriter = tindex.writer(50_000_000).map_err(tantivy_err)?;
+
+    for (path, nb) in index.notebooks() {
+        for (icell, cell) in nb.cells.iter().enumerate() {
+            let mut doc = TantivyDocument::new();
+            doc.add_text(fields.path, path);
+            doc.add_u64(fields.cell, icell as u64);
+            doc.add_text(fields.cell_type, &cell.cell_type);
+            for tag in cell.metadata.tags.iter() {
+                doc.add_text(fields.tag, tag);
+            }
+            if let Some(lang) = nb.language() {
+                doc.add_text(fields.language, lang.to_ascii_lowercase());
+            }
+            doc.add_text(fields.source, cell.source.concat());
+            for outp in cell.outputs.iter().flatten() {
+                for text in outp.text.iter().chain(outp.traceback.iter()) {
+                    doc.add_text(fields.output, text.concat());
+                }
+                let data_text = outp.data.iter().flatten()
+                    .filter_map(|(_, val)| crate::notebook::convert_output_text_data(val).ok())
+                    .map(|lines| lines.concat());
+                for text in data_text {
+                    doc.add_text(fields.output, text);
+                }
+            }
+            writer.add_document(doc).map_err(tantivy_err)?;
+        }
+    }
+    writer.commit().map_err(tantivy_err)?;
+    Ok(())
+}
+
+/// One cell found by a full-text query
+#[derive(Serialize)]
+pub struct Hit {
+    pub path: String,
+    pub cell: u64,
+    pub cell_type: String,
+    pub score: f32,
+    /// The part of the source around the best match, or the first line of the source if the
+    /// match was only in the outputs
+    pub snippet: String,
+    /// Byte ranges of `snippet` that matched the query
+    #[serde(skip)]
+    pub highlights: Vec<(usize, usize)>
+}
+
+/// The `limit` best matches for `query` in the tantivy index in `dir`
+pub fn search(dir: &Path, query: &str, limit: usize) -> Result<Vec<Hit>, RunErr> {
+    let tindex = Index::open_in_dir(dir).map_err(|e| RunErr{
+        msg: format!("Could not open the full-text index {:?} ({}); create it with --build-index", dir, e)
+    })?;
+    let fields = Fields::of(&tindex.schema())?;
+    let searcher = tindex.reader().map_err(tantivy_err)?.searcher();
+    let parser = QueryParser::for_index(&tindex, vec![fields.source, fields.output]);
+    let query = parser.parse_query(query).map_err(|e| RunErr{msg: format!("Invalid full-text query: {}", e)})?;
+    let snippets = SnippetGenerator::create(&searcher, &*query, fields.source).map_err(tantivy_err)?;
+
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(limit)).map_err(tantivy_err)?;
+    let mut hits = Vec::with_capacity(top_docs.len());
+    for (score, address) in top_docs {
+        let doc: TantivyDocument = searcher.doc(address).map_err(tantivy_err)?;
+        let text = |field: Field| doc.get_first(field).and_then(|v| v.as_str()).map(String::from).unwrap_or_default();
+        let snippet = snippets.snippet_from_doc(&doc);
+        let (snippet, highlights) = if snippet.is_empty() {
+            (text(fields.source).lines().next().map(String::from).unwrap_or_default(), Vec::new())
+        }else{
+            let ranges = snippet.highlighted().iter().map(|h| (h.start, h.end)).collect();
+            (String::from(snippet.fragment()), ranges)
+        };
+        hits.push(Hit{
+            path: text(fields.path),
+            cell: doc.get_first(fields.cell).and_then(|v| v.as_u64()).unwrap_or_default(),
+            cell_type: text(fields.cell_type),
+            score,
+            snippet,
+            highlights
+        });
+    }
+    Ok(hits)
+}
@@ -0,0 +1,91 @@
+//! Approximate matching for the `--fuzzy` option
+//!
+//! This implements Sellers' algorithm: a column of the usual Levenshtein dynamic
+//! programming table is computed for each character of the searched text, with the
+//! difference that a match may *start* anywhere in the text at no cost. Alongside
+//! the edit costs we carry the position where each partial alignment started, so
+//! that we can report byte offsets for highlighting just like the regex engines do.
+
+pub struct FuzzyPattern {
+    chars: Vec<char>,
+    max_errors: usize,
+    ignore_case: bool
+}
+
+impl FuzzyPattern {
+    pub fn new(pattern: &str, max_errors: usize, ignore_case: bool) -> Self {
+        Self{chars: pattern.chars().collect(), max_errors, ignore_case}
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find_positions(text).is_empty()
+    }
+
+    /// Return the (start, stop) byte offsets of non-overlapping substrings of `text`
+    /// within `max_errors` edits of the pattern. Where several overlapping substrings
+    /// qualify, the one with the fewest edits is kept (the longest, in case of a tie).
+    pub fn find_positions(&self, text: &str) -> Vec<(usize, usize)> {
+        let m = self.chars.len();
+        let text_chars: Vec<(usize, char)> = text.char_indices().collect();
+        let byte_offset = |ichar: usize| -> usize {
+            if ichar < text_chars.len() { text_chars[ichar].0 } else { text.len() }
+        };
+
+        // cost[i] is the number of edits needed to match the first i characters of the pattern
+        // ending at the current text position; start[i] is the text character index where that
+        // alignment began.
+        let mut cost: Vec<usize> = (0..=m).collect();
+        let mut start: Vec<usize> = vec![0; m+1];
+        let mut positions = Vec::new();
+        let mut best: Option<(usize, usize, usize)> = None;
+        let mut last_stop = 0;
+
+        for (j, &(_, tc)) in text_chars.iter().enumerate() {
+            let mut new_cost = vec![0; m+1];
+            let mut new_start = vec![j+1; m+1];
+            for i in 1..=m {
+                let subst = if self.chars_equal(self.chars[i-1], tc) { 0 } else { 1 };
+                let (mut c, mut s) = (cost[i-1] + subst, start[i-1]);
+                if new_cost[i-1] + 1 < c {
+                    c = new_cost[i-1] + 1;
+                    s = new_start[i-1];
+                }
+                if cost[i] + 1 < c {
+                    c = cost[i] + 1;
+                    s = start[i];
+                }
+                new_cost[i] = c;
+                new_start[i] = s;
+            }
+            cost = new_cost;
+            start = new_start;
+
+            // Consecutive end positions that all qualify are really the same match with
+            // more or fewer characters tacked on, so only keep the best of them.
+            if cost[m] <= self.max_errors && start[m] < j+1 {
+                let candidate = (start[m], j+1, cost[m]);
+                match best {
+                    Some((_, _, best_cost)) if best_cost < candidate.2 => {},
+                    _ => best = Some(candidate)
+                }
+            }else if let Some((s, e, _)) = best.take() {
+                if s >= last_stop {
+                    positions.push((byte_offset(s), byte_offset(e)));
+                    last_stop = e;
+                }
+            }
+        }
+
+        if let Some((s, e, _)) = best {
+            if s >= last_stop {
+                positions.push((byte_offset(s), byte_offset(e)));
+            }
+        }
+
+        positions
+    }
+
+    fn chars_equal(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+}
@@ -0,0 +1,209 @@
+//! Asking git which notebooks have changed (--changed), and reading notebooks as they were at
+//! a revision (--rev), both with the `git2` feature
+//!
+//! For --changed, the notebooks are found as usual, then only those that git lists as changed
+//! are kept. A notebook has changed if it differs from the revision (HEAD if none is given),
+//! whether or not the change is staged or committed, or if it is new and not ignored. Each path
+//! given is looked up in the repository it is in, so they may be in different repositories.
+//!
+//! With --rev, the working tree is not read at all: each path is looked up in the tree of the
+//! revision, which is listed like a directory, and each notebook is read from the object
+//! database when it is searched, just like a notebook piped to stdin. They are named
+//! "REV:PATH", as `git show` would name them. A path need not exist in the working tree, as
+//! long as the directory it would be in is inside the repository.
+use std::collections::HashSet;
+use std::ffi::OsStr;
+#[cfg(feature = "git2")]
+use std::ffi::OsString;
+#[cfg(feature = "git2")]
+use std::io;
+use std::path::PathBuf;
+#[cfg(feature = "git2")]
+use std::path::Path;
+use crate::RunErr;
+#[cfg(feature = "git2")]
+use crate::{FoundNotebook, SearchOptions, Source};
+
+/// Where a notebook listed at a revision with --rev is: the repository and the blob in it
+#[cfg(feature = "git2")]
+#[derive(Debug)]
+pub struct RevBlob {
+    git_dir: PathBuf,
+    oid: git2::Oid
+}
+
+/// The canonical paths of the changed files in the repositories that contain `paths`,
+/// compared with `rev`, or with HEAD if it is `None`
+#[cfg(feature = "git2")]
+pub fn changed_files(paths: &[&OsStr], rev: Option<&str>) -> Result<HashSet<PathBuf>, RunErr> {
+    let mut changed = HashSet::new();
+    let mut repos: HashSet<PathBuf> = HashSet::new();
+    for &p in paths {
+        let path = Path::new(p);
+        let dir = if path.is_dir() {
+            path
+        }else if path.is_file() {
+            path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."))
+        }else{
+            // E.g. stdin, or a notebook in cloud storage
+            continue;
+        };
+        let repo = git2::Repository::discover(dir)
+            .map_err(|e| RunErr{msg: format!("{} is not in a git repository: {}", dir.display(), e.message())})?;
+        let workdir = repo.workdir()
+            .ok_or_else(|| RunErr{msg: format!("The repository at {} has no working tree", repo.path().display())})?
+            .to_path_buf();
+        if !repos.insert(workdir.clone()) {
+            continue;
+        }
+
+        let err = |e: git2::Error| RunErr{msg: format!("Could not compare with '{}': {}", rev.unwrap_or("HEAD"), e.message())};
+        let diff = match rev {
+            Some(rev) => {
+                let spec = repo.revparse(rev).map_err(err)?;
+                match (spec.from(), spec.to()) {
+                    // Like `git diff A..B` and `git diff A...B`, two commits are compared with
+                    // each other, not with the working tree
+                    (Some(from), Some(to)) => {
+                        let old_tree = if spec.mode().contains(git2::RevparseMode::MERGE_BASE) {
+                            let base = repo.merge_base(from.id(), to.id()).map_err(err)?;
+                            repo.find_commit(base).and_then(|c| c.tree()).map_err(err)?
+                        }else{
+                            from.peel_to_tree().map_err(err)?
+                        };
+                        let new_tree = to.peel_to_tree().map_err(err)?;
+                        repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None).map_err(err)?
+                    },
+                    (Some(single), None) | (None, Some(single)) => {
+                        let tree = single.peel_to_tree().map_err(err)?;
+                        repo.diff_tree_to_workdir_with_index(Some(&tree), None).map_err(err)?
+                    },
+                    (None, None) => return Err(RunErr{msg: format!("Could not compare with '{}'", rev)})
+                }
+            },
+            None => {
+                // With nothing committed yet, every file in the index is new
+                let head_tree = match repo.head() {
+                    Ok(head) => Some(head.peel_to_tree().map_err(err)?),
+                    Err(_) => None
+                };
+                repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), None).map_err(err)?
+            }
+        };
+        let listed = diff.deltas()
+            .filter(|delta| delta.status() != git2::Delta::Deleted)
+            .filter_map(|delta| delta.new_file().path().map(Path::to_path_buf));
+
+        // New files that are not ignored have changed too, whatever they are compared with
+        let mut status_opts = git2::StatusOptions::new();
+        status_opts.include_untracked(true).recurse_untracked_dirs(true).include_ignored(false);
+        let statuses = repo.statuses(Some(&mut status_opts)).map_err(err)?;
+        let untracked = statuses.iter()
+            .filter(|entry| entry.status().is_wt_new())
+            .filter_map(|entry| entry.path().map(PathBuf::from));
+
+        changed.extend(listed.chain(untracked).filter_map(|f| workdir.join(f).canonicalize().ok()));
+    }
+    Ok(changed)
+}
+
+/// Without the `git2` feature, there is nothing to ask which files have changed
+#[cfg(not(feature = "git2"))]
+pub fn changed_files(_paths: &[&OsStr], _rev: Option<&str>) -> Result<HashSet<PathBuf>, RunErr> {
+    Err(RunErr::from("jrep was not compiled with the 'git2' feature, so '--changed' is not available"))
+}
+
+/// The notebooks at `path` in the tree of revision `rev`, named "REV:PATH": the notebook itself
+/// if `path` is one, otherwise those in it that would be searched if it were a directory.
+/// Nothing is listed if `path` is not in that tree.
+#[cfg(feature = "git2")]
+pub fn list_notebooks_at(path: &OsStr, rev: &str, opts: &SearchOptions) -> Result<Vec<FoundNotebook>, RunErr> {
+    let (repo, rel_path) = open_repository(Path::new(path))?;
+    let err = |e: git2::Error| RunErr{msg: format!("Could not read revision '{}': {}", rev, e.message())};
+    let tree = repo.revparse_single(rev).and_then(|obj| obj.peel_to_tree()).map_err(err)?;
+    let name = |inner: &Path| OsString::from(format!("{}:{}", rev, Path::new(path).join(inner).display()));
+
+    let mut found = Vec::new();
+    let dir = if rel_path.as_os_str().is_empty() {
+        tree
+    }else{
+        let entry = match tree.get_path(&rel_path) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(Vec::new())
+        };
+        match entry.kind() {
+            Some(git2::ObjectType::Blob) => {
+                // As for a file given as a path, its extension does not matter
+                if opts.path_filter.keeps_file(&rel_path) {
+                    found.push((OsString::from(format!("{}:{}", rev, Path::new(path).display())), entry.id()));
+                }
+                return Ok(found_blobs(&repo, found));
+            },
+            Some(git2::ObjectType::Tree) => repo.find_tree(entry.id()).map_err(err)?,
+            _ => return Ok(Vec::new())
+        }
+    };
+
+    let max_depth = opts.max_depth.or(if opts.recursive { None } else { Some(1) });
+    dir.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        let depth = Path::new(root).components().count() + 1;
+        match entry.kind() {
+            Some(git2::ObjectType::Tree) if max_depth.is_some_and(|d| depth >= d) => git2::TreeWalkResult::Skip,
+            // Symbolic links are blobs too, but their content is only the path they point to
+            Some(git2::ObjectType::Blob) if entry.filemode() != i32::from(git2::FileMode::Link) => {
+                let inner = Path::new(root).join(String::from_utf8_lossy(entry.name_bytes()).as_ref());
+                if crate::walk::keeps_member(&inner, opts) {
+                    found.push((name(&inner), entry.id()));
+                }
+                git2::TreeWalkResult::Ok
+            },
+            _ => git2::TreeWalkResult::Ok
+        }
+    }).map_err(err)?;
+    found.sort_unstable();
+    Ok(found_blobs(&repo, found))
+}
+
+/// The repository that `path` is in, and its path within the working tree
+#[cfg(feature = "git2")]
+fn open_repository(path: &Path) -> Result<(git2::Repository, PathBuf), RunErr> {
+    // The path may only exist at the revision, so the repository is found from the nearest
+    // directory above it that does exist
+    let absolute = std::path::absolute(path)?;
+    let existing = absolute.ancestors().find(|p| p.is_dir())
+        .ok_or_else(|| RunErr{msg: format!("{} is not in a git repository", path.display())})?;
+    let repo = git2::Repository::discover(existing)
+        .map_err(|e| RunErr{msg: format!("{} is not in a git repository: {}", path.display(), e.message())})?;
+    let workdir = repo.workdir()
+        .ok_or_else(|| RunErr{msg: format!("The repository at {} has no working tree", repo.path().display())})?
+        .canonicalize()?;
+    let mut rel_path = existing.canonicalize()?.strip_prefix(&workdir)
+        .map_err(|_| RunErr{msg: format!("{} is not in the working tree of {}", path.display(), workdir.display())})?
+        .to_path_buf();
+    for part in absolute.strip_prefix(existing).unwrap().components() {
+        match part {
+            std::path::Component::ParentDir => { rel_path.pop(); },
+            std::path::Component::Normal(part) => rel_path.push(part),
+            _ => ()
+        }
+    }
+    Ok((repo, rel_path))
+}
+
+/// The listed notebooks, each with its name and blob
+#[cfg(feature = "git2")]
+fn found_blobs(repo: &git2::Repository, found: Vec<(OsString, git2::Oid)>) -> Vec<FoundNotebook> {
+    found.into_iter().map(|(name, oid)| FoundNotebook{
+        name,
+        source: Source::Rev(RevBlob{git_dir: repo.path().to_path_buf(), oid})
+    }).collect()
+}
+
+/// The text of a notebook listed at a revision with --rev
+#[cfg(feature = "git2")]
+pub fn read_blob(blob: &RevBlob) -> io::Result<String> {
+    // A repository cannot be shared between threads, so each read opens its own
+    let repo = git2::Repository::open(&blob.git_dir).map_err(|e| io::Error::other(e.message().to_string()))?;
+    let blob = repo.find_blob(blob.oid).map_err(|e| io::Error::other(e.message().to_string()))?;
+    String::from_utf8(blob.content().to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
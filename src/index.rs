@@ -0,0 +1,173 @@
+//! A persistent index of notebooks for `--build-index` and `--use-index`
+//!
+//! The index is a single JSON file holding every notebook already parsed, minus the output
+//! data that is not text (plots, widgets, and the like, which usually make up nearly all of
+//! a notebook's size). Searching with the index reads that one file instead of reading and
+//! parsing each notebook. The modification time and size of each notebook are stored with it,
+//! and a notebook that has changed since the index was built is read from disk as usual, so
+//! the results are always up to date; rebuilding the index only makes the search fast again.
+//!
+//! Rebuilding keeps the notebooks whose time and size have not changed without reading them.
+//! Ones that have a new time but the same content (e.g. after a checkout) are read to check
+//! their hash, but not parsed again.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use crate::{rewrite, FoundNotebook, Notebook, RunErr, SearchOptions, Source};
+
+/// Where the index is kept if --index-file is not given, relative to the current directory
+pub const DEFAULT_INDEX_FILE: &str = ".jrep-index.json";
+
+/// Changes whenever the format of the index changes, so that old indices are rebuilt
+const INDEX_VERSION: u32 = 2;
+
+/// The modification time and size of a file, to tell whether it has changed
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub(crate) struct FileStamp {
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    size: u64
+}
+
+impl FileStamp {
+    pub(crate) fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Self{mtime_secs: mtime.as_secs(), mtime_nanos: mtime.subsec_nanos(), size: meta.len()})
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    stamp: FileStamp,
+    hash: u64,
+    notebook: Notebook
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NotebookIndex {
+    version: u32,
+    /// Keyed by the path as it was found (e.g. "./analysis/run1.ipynb"), which is how it
+    /// will be looked up as long as jrep is run from the same directory
+    notebooks: BTreeMap<String, IndexEntry>
+}
+
+/// What `NotebookIndex::build` did
+#[derive(Default)]
+pub struct BuildSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize
+}
+
+impl NotebookIndex {
+    pub fn load(path: &Path) -> Result<Self, RunErr> {
+        let data = fs::read_to_string(path).map_err(|e| RunErr{
+            msg: format!("Could not read the index {:?} ({}); create it with --build-index", path, e)
+        })?;
+        let index: Self = serde_json::from_str(&data)?;
+        if index.version != INDEX_VERSION {
+            return Err(RunErr{msg: format!("The index {:?} was made by a different version of jrep; rebuild it with --build-index", path)});
+        }
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RunErr> {
+        rewrite::write_atomically(path, &serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Index the notebooks in `paths`, reusing what can be reused from `old`. The new index
+    /// has exactly these notebooks; ones in `old` that are not in `paths` are dropped. Also
+    /// returns the notebooks that could not be read or parsed, or are not files, which are
+    /// left out.
+    pub fn build(old: Option<Self>, paths: &[FoundNotebook], opts: &SearchOptions) -> (Self, BuildSummary, Vec<(OsString, RunErr)>) {
+        let mut old_notebooks = old.map(|index| index.notebooks).unwrap_or_default();
+        let mut notebooks = BTreeMap::new();
+        let mut summary = BuildSummary::default();
+        let mut errors = Vec::new();
+
+        for found in paths {
+            // Only files can be checked for changes when the index is used
+            let path = match &found.source {
+                Source::Local(path) => path,
+                _ => {
+                    errors.push((found.name.clone(), RunErr::from("Only notebook files can be indexed")));
+                    continue;
+                }
+            };
+            let key = path.to_string_lossy().into_owned();
+            let old_entry = old_notebooks.remove(&key);
+            match Self::index_notebook(&found.name, path, old_entry, &mut summary, opts) {
+                Ok(entry) => { notebooks.insert(key, entry); },
+                Err(e) => errors.push((found.name.clone(), e))
+            }
+        }
+
+        summary.removed = old_notebooks.len();
+        (Self{version: INDEX_VERSION, notebooks}, summary, errors)
+    }
+
+    fn index_notebook(filename: &OsString, path: &Path, old_entry: Option<IndexEntry>, summary: &mut BuildSummary, opts: &SearchOptions) -> Result<IndexEntry, RunErr> {
+        let stamp = FileStamp::of(path)?;
+        let old_entry = match old_entry {
+            Some(entry) if entry.stamp == stamp => {
+                summary.unchanged += 1;
+                return Ok(entry);
+            },
+            other => other
+        };
+
+        let data = fs::read_to_string(path)?;
+        let hash = content_hash(data.as_bytes());
+        match old_entry {
+            Some(entry) if entry.hash == hash => {
+                summary.unchanged += 1;
+                return Ok(IndexEntry{stamp, hash, notebook: entry.notebook});
+            },
+            Some(_) => summary.updated += 1,
+            None => summary.added += 1
+        }
+        let kept_types: Vec<String> = crate::TEXT_OUTPUT_DATA_TYPES.iter().map(|t| String::from(*t)).collect();
+        let notebook = crate::notebook::parse_notebook_keeping(&data, filename, Some(&kept_types), opts)?;
+        Ok(IndexEntry{stamp, hash, notebook})
+    }
+
+    /// Every notebook in the index, with the path it is indexed by
+    #[cfg_attr(not(feature = "tantivy"), allow(dead_code))]
+    pub fn notebooks(&self) -> impl Iterator<Item = (&str, &Notebook)> {
+        self.notebooks.iter().map(|(path, entry)| (path.as_str(), &entry.notebook))
+    }
+
+    /// The indexed copy of the notebook file at `path`, if it is in the index, has not changed
+    /// since, and has everything this search needs (see `has_what_search_needs`).
+    pub(crate) fn notebook(&self, path: &Path, opts: &SearchOptions) -> Option<Notebook> {
+        if !has_what_search_needs(opts) {
+            return None;
+        }
+        let entry = self.notebooks.get(path.to_string_lossy().as_ref())?;
+        let stamp = FileStamp::of(path).ok()?;
+        if stamp == entry.stamp { Some(entry.notebook.clone()) } else { None }
+    }
+}
+
+/// Whether a notebook parsed with only its text output data, as in the index and the cache,
+/// is enough for this search. It is not for searches of other output types, or that need
+/// file line numbers (--file-line, SARIF output), since the parsed notebook does not know
+/// where in the file anything was.
+pub(crate) fn has_what_search_needs(opts: &SearchOptions) -> bool {
+    let has_types = opts.include_output_types.iter().all(|t| crate::is_text(t) || t == "error");
+    has_types && !opts.needs_file_lines()
+}
+
+/// 64-bit FNV-1a; only used to tell whether a notebook changed, so it need not be cryptographic,
+/// but unlike std's hasher it is the same in every version of Rust
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
@@ -0,0 +1,92 @@
+//! A small subset of jq paths for the `--json-query` option
+//!
+//! `application/json` outputs are searched by pretty-printing them, one key or value per
+//! line. A query narrows that down to part of the output first. Supported are `.key` (or
+//! `."key with spaces"`), `[N]` for an element of a list (negative counts from the end),
+//! and `[]` for every element of a list or every value of an object, chained together:
+//! e.g. `.columns[]` or `.data[0].name`. A lone `.` is the whole output.
+
+use std::convert::TryFrom;
+use serde_json::Value;
+use crate::RunErr;
+
+enum Step {
+    Key(String),
+    Index(isize),
+    Each
+}
+
+pub struct JsonQuery {
+    steps: Vec<Step>
+}
+
+impl JsonQuery {
+    pub fn parse(expr: &str) -> Result<Self, RunErr> {
+        let invalid = |why: &str| RunErr{msg: format!("Invalid --json-query '{}': {}", expr, why)};
+        let expr = expr.trim();
+        if !expr.starts_with('.') {
+            return Err(invalid("it must start with '.'"));
+        }
+        if expr == "." {
+            return Ok(Self{steps: Vec::new()});
+        }
+
+        let mut steps = Vec::new();
+        let mut rest = expr;
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                if after_dot.starts_with('[') {
+                    // ".[]" and ".[0]" mean the same as "[]" and "[0]"
+                    rest = after_dot;
+                }else if let Some(quoted) = after_dot.strip_prefix('"') {
+                    let end = quoted.find('"').ok_or_else(|| invalid("unterminated quoted key"))?;
+                    steps.push(Step::Key(String::from(&quoted[..end])));
+                    rest = &quoted[end+1..];
+                }else{
+                    let end = after_dot.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(after_dot.len());
+                    if end == 0 {
+                        return Err(invalid("expected a key after '.'"));
+                    }
+                    steps.push(Step::Key(String::from(&after_dot[..end])));
+                    rest = &after_dot[end..];
+                }
+            }else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']').ok_or_else(|| invalid("missing ']'"))?;
+                let inside = after_bracket[..end].trim();
+                let step = if inside.is_empty() {
+                    Step::Each
+                }else if let Some(key) = inside.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                    Step::Key(String::from(key))
+                }else{
+                    Step::Index(inside.parse().map_err(|_| invalid("expected a number, a quoted key, or nothing inside '[]'"))?)
+                };
+                steps.push(step);
+                rest = &after_bracket[end+1..];
+            }else{
+                return Err(invalid("expected '.' or '[' between steps"));
+            }
+        }
+        Ok(Self{steps})
+    }
+
+    /// The values in `value` selected by this query. Steps that do not apply (such as a key
+    /// of something that is not an object) select nothing rather than being an error.
+    pub fn apply<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        let mut current = vec![value];
+        for step in self.steps.iter() {
+            current = current.into_iter().flat_map(|v| -> Vec<&Value> {
+                match (step, v) {
+                    (Step::Key(key), Value::Object(obj)) => obj.get(key).into_iter().collect(),
+                    (Step::Index(i), Value::Array(arr)) => {
+                        let i = if *i < 0 { arr.len() as isize + i } else { *i };
+                        usize::try_from(i).ok().and_then(|i| arr.get(i)).into_iter().collect()
+                    },
+                    (Step::Each, Value::Array(arr)) => arr.iter().collect(),
+                    (Step::Each, Value::Object(obj)) => obj.values().collect(),
+                    _ => Vec::new()
+                }
+            }).collect();
+        }
+        current
+    }
+}
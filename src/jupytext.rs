@@ -0,0 +1,421 @@
+//! Reading notebooks saved as text by jupytext, as Python scripts (.py) or Markdown (.md), as
+//! well as marimo notebooks (also .py), and Quarto (.qmd) and R Markdown (.Rmd) documents
+//!
+//! These have only the cells' sources, so the notebooks have no outputs or execution counts.
+//! Scripts may be in the "percent" format, where each cell starts with a "# %%" line (with
+//! "[markdown]" or "[raw]" after it for those cells, whose lines are commented out), or the
+//! "light" format, where cells are separated by blank lines and a paragraph made only of
+//! comments is a markdown cell ("# +" and "# -" mark cells that contain blank lines). A
+//! script that imports marimo and has "@app.cell" functions is a marimo notebook, with each
+//! function's body (less its `return`) as a cell; one that only calls `mo.md` is a markdown
+//! cell of the text it is given.
+//!
+//! In Markdown, each fenced code block with a language is a code cell, the text between them
+//! is a markdown cell, and "<!-- #region -->" or "<!-- #raw -->" comments mark cells explicitly.
+//! Quarto and R Markdown are the same, except that only the blocks whose language is in braces
+//! (e.g. "```{r setup}") are code cells, since the others are only displayed, and those with
+//! an "=" (e.g. "```{=html}") are raw cells.
+//!
+//! The YAML header at the top is skipped, except for the kernel's language. Without one, the
+//! language of a document is that of its first code cell.
+//! Since the lines of a cell are not all next to each other in the JSON, the line in the file
+//! of each one (for --file-lines) is recorded while parsing.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use crate::{Cell, CellMetadata, KernelSpec, Notebook, NotebookMetadata};
+use crate::spans::FileLines;
+
+/// The text formats that notebooks can be read from
+#[derive(Clone, Copy)]
+pub enum Format {
+    Script,
+    Markdown,
+    Quarto
+}
+
+impl Format {
+    /// The format of the notebook `filename` by its extension (ignoring case), if it is not JSON
+    pub fn of(filename: &OsStr) -> Option<Self> {
+        let ext = Path::new(filename).extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "py" => Some(Format::Script),
+            "md" => Some(Format::Markdown),
+            "qmd" | "rmd" => Some(Format::Quarto),
+            _ => None
+        }
+    }
+}
+
+/// Parse the text of a notebook in `format`
+pub fn parse(data: &str, format: Format) -> Notebook {
+    parse_with_lines(data, format).0
+}
+
+/// Where each line of each cell's source is in the file
+pub fn file_lines(data: &str, format: Format) -> FileLines {
+    FileLines::new(parse_with_lines(data, format).1)
+}
+
+fn parse_with_lines(data: &str, format: Format) -> (Notebook, HashMap<String, usize>) {
+    // Each line with its line number in the file
+    let lines: Vec<(usize, &str)> = data.lines().enumerate().map(|(i, line)| (i + 1, line)).collect();
+    let (header_language, body) = match format {
+        Format::Script => split_header(&lines, "# ---"),
+        Format::Markdown | Format::Quarto => split_header(&lines, "---")
+    };
+    let mut builder = Builder::default();
+    let language = match format {
+        Format::Script => {
+            if is_marimo(body) {
+                parse_marimo(body, &mut builder);
+            }else if body.iter().any(|(_, line)| is_percent_marker(line)) {
+                parse_percent(body, &mut builder);
+            }else{
+                parse_light(body, &mut builder);
+            }
+            header_language.or_else(|| Some(String::from("python")))
+        },
+        Format::Markdown | Format::Quarto => {
+            let first_language = parse_markdown(body, &mut builder, matches!(format, Format::Quarto));
+            header_language.or(first_language)
+        }
+    };
+    let kernelspec = language.map(|language| KernelSpec{language: Some(language), other: serde_json::Map::new()});
+    let metadata = NotebookMetadata{kernelspec, ..NotebookMetadata::default()};
+    (Notebook{cells: builder.cells, metadata}, builder.lines)
+}
+
+/// Split the YAML header, between two `delimiter` lines at the top, from the rest of the
+/// file, returning the kernel's language if the header gives it
+fn split_header<'a>(lines: &'a [(usize, &'a str)], delimiter: &str) -> (Option<String>, &'a [(usize, &'a str)]) {
+    if lines.first().is_none_or(|(_, line)| line.trim_end() != delimiter) {
+        return (None, lines);
+    }
+    let end = match lines.iter().skip(1).position(|(_, line)| line.trim_end() == delimiter) {
+        Some(i) => i + 1,
+        None => return (None, lines)
+    };
+    let language = lines[1..end].iter()
+        .filter_map(|(_, line)| line.trim_start_matches('#').trim().strip_prefix("language:"))
+        .map(|lang| lang.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .find(|lang| !lang.is_empty());
+    (language, &lines[end + 1..])
+}
+
+#[derive(Default)]
+struct Builder {
+    cells: Vec<Cell>,
+    lines: HashMap<String, usize>
+}
+
+impl Builder {
+    /// Add a cell with the given lines, less the blank lines around them. Cells left empty are
+    /// only added if they were marked explicitly.
+    fn push(&mut self, cell_type: &str, lines: &[(usize, String)], tags: Vec<String>, explicit: bool) {
+        let first = lines.iter().position(|(_, line)| !line.trim().is_empty());
+        let last = lines.iter().rposition(|(_, line)| !line.trim().is_empty());
+        let lines = match (first, last) {
+            (Some(first), Some(last)) => &lines[first..=last],
+            _ if explicit => &[],
+            _ => return
+        };
+        let icell = self.cells.len();
+        for (j, (line_number, _)) in lines.iter().enumerate() {
+            self.lines.insert(format!("/cells/{}/source/{}", icell, j), *line_number);
+        }
+        // As in a notebook, every line but the last ends with its newline
+        let source = lines.iter().enumerate()
+            .map(|(j, (_, line))| if j + 1 < lines.len() { format!("{}\n", line) } else { line.clone() })
+            .collect();
+        let outputs = if cell_type == "code" { Some(Vec::new()) } else { None };
+        self.cells.push(Cell{
+            cell_type: cell_type.to_string(),
+            metadata: CellMetadata{tags, ..CellMetadata::default()},
+            source,
+            outputs,
+            ..Cell::default()
+        });
+    }
+}
+
+fn is_percent_marker(line: &str) -> bool {
+    line.starts_with("# %%") || line.starts_with("#%%")
+}
+
+/// The cell type given in brackets in a cell marker, e.g. "# %% [markdown]"
+fn marked_cell_type(marker: &str) -> &'static str {
+    if marker.contains("[markdown]") || marker.contains("[md]") {
+        "markdown"
+    }else if marker.contains("[raw]") {
+        "raw"
+    }else{
+        "code"
+    }
+}
+
+/// The tags in a cell marker, e.g. `# %% tags=["parameters"]`
+fn marked_tags(marker: &str) -> Vec<String> {
+    marker.find("tags=[")
+        .and_then(|start| {
+            let list = &marker[start + 5..];
+            list.find(']').and_then(|end| serde_json::from_str(&list[..=end]).ok())
+        })
+        .unwrap_or_default()
+}
+
+/// A line of a markdown or raw cell in a script, which is commented out
+fn uncomment(line: &str) -> String {
+    line.strip_prefix("# ").or_else(|| line.strip_prefix('#')).unwrap_or(line).to_string()
+}
+
+fn parse_percent(lines: &[(usize, &str)], builder: &mut Builder) {
+    // Code before the first marker is a cell of its own
+    let mut cell_type = "code";
+    let mut tags = Vec::new();
+    let mut explicit = false;
+    let mut current: Vec<(usize, String)> = Vec::new();
+    for &(n, line) in lines {
+        if is_percent_marker(line) {
+            builder.push(cell_type, &current, std::mem::take(&mut tags), explicit);
+            current.clear();
+            cell_type = marked_cell_type(line);
+            tags = marked_tags(line);
+            explicit = true;
+        }else if cell_type == "code" {
+            current.push((n, line.to_string()));
+        }else{
+            current.push((n, uncomment(line)));
+        }
+    }
+    builder.push(cell_type, &current, tags, explicit);
+}
+
+fn parse_light(lines: &[(usize, &str)], builder: &mut Builder) {
+    let mut paragraph: Vec<(usize, String)> = Vec::new();
+    let end_paragraph = |paragraph: &mut Vec<(usize, String)>, builder: &mut Builder| {
+        let all_comments = paragraph.iter().all(|(_, line)| line.trim().is_empty() || line.starts_with('#'));
+        if all_comments {
+            let uncommented: Vec<(usize, String)> = paragraph.iter().map(|(n, line)| (*n, uncomment(line))).collect();
+            builder.push("markdown", &uncommented, Vec::new(), false);
+        }else{
+            builder.push("code", paragraph, Vec::new(), false);
+        }
+        paragraph.clear();
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (n, line) = lines[i];
+        if line.starts_with("# +") {
+            // An explicit cell, which ends at "# -"
+            end_paragraph(&mut paragraph, builder);
+            let cell_type = marked_cell_type(line);
+            let tags = marked_tags(line);
+            let end = lines[i + 1..].iter().position(|(_, l)| l.trim_end() == "# -").map_or(lines.len(), |j| i + 1 + j);
+            let cell: Vec<(usize, String)> = lines[i + 1..end].iter()
+                .map(|&(n, l)| (n, if cell_type == "code" { l.to_string() } else { uncomment(l) }))
+                .collect();
+            builder.push(cell_type, &cell, tags, true);
+            i = end + 1;
+            continue;
+        }
+        // A blank line ends a cell, unless the next line is indented, as in the body of a function
+        let next_starts_cell = lines.get(i + 1).is_some_and(|(_, next)| !next.trim().is_empty() && !next.starts_with([' ', '\t']));
+        if line.trim().is_empty() && next_starts_cell {
+            end_paragraph(&mut paragraph, builder);
+        }else{
+            paragraph.push((n, line.to_string()));
+        }
+        i += 1;
+    }
+    end_paragraph(&mut paragraph, builder);
+}
+
+fn is_marimo(lines: &[(usize, &str)]) -> bool {
+    lines.iter().any(|(_, line)| line.trim_end() == "import marimo") && lines.iter().any(|(_, line)| line.starts_with("@app.cell"))
+}
+
+/// The decorators of the definitions that are cells in a marimo notebook. Since marimo 0.13,
+/// functions and classes can also be cells of their own, which are kept whole.
+const MARIMO_CELL: &str = "@app.cell";
+const MARIMO_DEFINITIONS: [&str; 2] = ["@app.function", "@app.class_definition"];
+
+fn parse_marimo(lines: &[(usize, &str)], builder: &mut Builder) {
+    // The end of the block that starts at `start`: the next line at the top level
+    let block_end = |start: usize| lines[start..].iter()
+        .position(|(_, l)| !l.trim().is_empty() && !l.starts_with([' ', '\t', ')']))
+        .map_or(lines.len(), |j| start + j);
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].1;
+        if line.starts_with(MARIMO_CELL) {
+            // The body starts after the signature, which may be split over several lines
+            let def = match lines[i + 1..].iter().position(|(_, l)| l.starts_with("def ") || l.starts_with("async def ")) {
+                Some(j) => i + 1 + j,
+                None => break
+            };
+            let body_start = lines[def..].iter().position(|(_, l)| l.trim_end().ends_with(':')).map_or(lines.len(), |j| def + j + 1);
+            let end = block_end(body_start);
+            let mut body: Vec<(usize, String)> = lines[body_start..end].iter().map(|&(n, l)| (n, dedent(l, 4).to_string())).collect();
+            // marimo returns the names the cell defines, which are not part of its code
+            while body.last().is_some_and(|(_, l)| l.trim().is_empty()) {
+                body.pop();
+            }
+            if body.last().is_some_and(|(_, l)| l.starts_with("return")) {
+                body.pop();
+            }
+            match marimo_markdown(&body) {
+                Some(text) => builder.push("markdown", &text, Vec::new(), true),
+                None => builder.push("code", &body, Vec::new(), true)
+            }
+            i = end;
+        }else if MARIMO_DEFINITIONS.iter().any(|d| line.starts_with(d)) {
+            let start = i + 1;
+            let end = block_end(start + 1);
+            let cell: Vec<(usize, String)> = lines[start..end].iter().map(|&(n, l)| (n, l.to_string())).collect();
+            builder.push("code", &cell, Vec::new(), true);
+            i = end;
+        }else if line.starts_with("with app.setup") {
+            let end = block_end(i + 1);
+            let cell: Vec<(usize, String)> = lines[i + 1..end].iter().map(|&(n, l)| (n, dedent(l, 4).to_string())).collect();
+            builder.push("code", &cell, Vec::new(), true);
+            i = end;
+        }else{
+            i += 1;
+        }
+    }
+}
+
+/// `line` without up to `n` spaces of indentation
+fn dedent(line: &str, n: usize) -> &str {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    &line[spaces.min(n)..]
+}
+
+/// If the cell `body` only calls `mo.md` with a string literal, the lines of the string,
+/// without their common indentation
+fn marimo_markdown(body: &[(usize, String)]) -> Option<Vec<(usize, String)>> {
+    let (first_n, first) = body.first()?;
+    let call = first.trim_start().strip_prefix("mo.md(")?;
+    // The string may start on the line after the call
+    let (mut rest, after): (Vec<(usize, &str)>, usize) = if call.trim().is_empty() {
+        let (n, line) = body.get(1)?;
+        (vec![(*n, line.trim_start())], 2)
+    }else{
+        (vec![(*first_n, call)], 1)
+    };
+    rest.extend(body[after..].iter().map(|(n, l)| (*n, l.as_str())));
+
+    let literal = rest[0].1.trim_start_matches(|c: char| "rRfFuU".contains(c));
+    let quote = ["\"\"\"", "'''", "\"", "'"].iter().find(|q| literal.starts_with(**q))?;
+    rest[0].1 = &literal[quote.len()..];
+
+    let mut text: Vec<(usize, String)> = Vec::new();
+    // What follows the string, which must only be the closing parenthesis
+    let mut tail: Option<String> = None;
+    for (n, line) in rest {
+        match &mut tail {
+            Some(tail) => tail.push_str(line.trim()),
+            None => match line.find(quote) {
+                Some(end) => {
+                    text.push((n, line[..end].to_string()));
+                    tail = Some(line[end + quote.len()..].trim().to_string());
+                },
+                None => text.push((n, line.to_string()))
+            }
+        }
+    }
+    if tail.as_deref() != Some(")") {
+        return None;
+    }
+    let indent = text.iter().filter(|(_, l)| !l.trim().is_empty()).map(|(_, l)| l.len() - l.trim_start_matches(' ').len()).min().unwrap_or(0);
+    Some(text.into_iter().map(|(n, l)| (n, dedent(&l, indent).to_string())).collect())
+}
+
+/// If `line` opens a fenced code block, its fence (e.g. "```") and info string (e.g. "python")
+fn opening_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let fence_char = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let fence_len = trimmed.chars().take_while(|&c| c == fence_char).count();
+    if fence_len < 3 {
+        return None;
+    }
+    Some((&trimmed[..fence_len], trimmed[fence_len..].trim()))
+}
+
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with(fence) && trimmed.chars().all(|c| fence.starts_with(c))
+}
+
+/// The type of cell that a fenced block with `info` after its fence is, if it is not part of
+/// the text, and the language of a code cell
+fn fenced_cell_type(info: &str, quarto: bool) -> Option<(&'static str, &str)> {
+    if !quarto {
+        let language = info.split_whitespace().next()?;
+        return Some(("code", language));
+    }
+    let options = info.strip_prefix('{')?;
+    if options.starts_with('=') {
+        return Some(("raw", ""));
+    }
+    let language = options.split([' ', ',', '}']).next().filter(|lang| !lang.is_empty())?;
+    Some(("code", language))
+}
+
+/// Parse a Markdown notebook, or a Quarto or R Markdown document if `quarto` is set, returning
+/// the language of the first code cell
+fn parse_markdown(lines: &[(usize, &str)], builder: &mut Builder, quarto: bool) -> Option<String> {
+    let mut first_language = None;
+    let mut text: Vec<(usize, String)> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (n, line) = lines[i];
+        let comment = line.trim();
+        let region = if comment.starts_with("<!-- #region") {
+            Some(("markdown", "<!-- #endregion -->"))
+        }else if comment.starts_with("<!-- #raw") {
+            Some(("raw", "<!-- #endraw -->"))
+        }else{
+            None
+        };
+
+        if let Some((cell_type, end_comment)) = region {
+            builder.push("markdown", &text, Vec::new(), false);
+            text.clear();
+            let end = lines[i + 1..].iter().position(|(_, l)| l.trim() == end_comment).map_or(lines.len(), |j| i + 1 + j);
+            let cell: Vec<(usize, String)> = lines[i + 1..end].iter().map(|&(n, l)| (n, l.to_string())).collect();
+            builder.push(cell_type, &cell, marked_tags(comment), true);
+            i = end + 1;
+        }else if let Some((fence, info)) = opening_fence(line) {
+            let end = lines[i + 1..].iter().position(|(_, l)| closes_fence(l, fence)).map_or(lines.len(), |j| i + 1 + j);
+            let block = lines[i + 1..end].iter().map(|&(n, l)| (n, l.to_string()));
+            match fenced_cell_type(info, quarto) {
+                Some((cell_type, language)) => {
+                    builder.push("markdown", &text, Vec::new(), false);
+                    text.clear();
+                    builder.push(cell_type, &block.collect::<Vec<_>>(), marked_tags(info), true);
+                    if cell_type == "code" && first_language.is_none() {
+                        first_language = Some(language.to_string());
+                    }
+                },
+                None => {
+                    // E.g. a block without a language, which is part of the text, fences and all
+                    text.push((n, line.to_string()));
+                    text.extend(block);
+                    if let Some(&(n, l)) = lines.get(end) {
+                        text.push((n, l.to_string()));
+                    }
+                }
+            }
+            i = end + 1;
+        }else{
+            text.push((n, line.to_string()));
+            i += 1;
+        }
+    }
+    builder.push("markdown", &text, Vec::new(), false);
+    first_language
+}
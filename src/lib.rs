@@ -0,0 +1,433 @@
+//! Search Jupyter notebooks from the command line
+//! 
+//! ## Why `jrep` exists
+//! Fundamentally, Jupyter notebooks are just JSON files, so if you wanted
+//! to quickly search for a string across many notebooks, you could use a
+//! program like `grep`. But, when figures or other images are captured as
+//! output, data will be a long jumble of encoded data which will (a) possibly
+//! match your search expression and (b) be such a long "line" of text that it
+//! completely swamps the rest of the (actually useful) grep output.
+//! 
+//! `jrep` is built to first parse Jupyter notebooks and only then search certain
+//! types of cells. This way, it returns relevant results rather than messes of
+//! image data.
+//! 
+//! ## Quick examples
+//! 
+//! Search cell inputs and plain text outputs in all notebooks in the current directory
+//! for the string "numpy":
+//! 
+//! ```bash
+//! jrep numpy
+//! ```
+//! 
+//! Search just the notebook `demo.ipynb` for the string "numpy":
+//! 
+//! ```bash
+//! jrep numpy demo.ipynb
+//! ```
+//! 
+//! Search all the notebooks in the directory `~/Documents/Notebooks` for the string "numpy":
+//! 
+//! ```bash
+//! jrep numpy ~/Documents/Notebooks
+//! ```
+//! 
+//! Search only markdown cells in `demo.ipynb` for web URLs starting with "http://" or "https://"
+//! (using regular expressions to indicate that the "s" is optional):
+//! 
+//! ```bash
+//! jrep --cell-type markdown 'https?://' demo.ipynb
+//! ```
+//! 
+//! ## Which notebooks are searched
+//! 
+//! Since the main use case is finding notebooks that contain a certain string, `jrep` will
+//! search all notebooks (i.e. files with the `.ipynb` extension) in the current directory if
+//! you do not explicitly specify paths to search as the second and later positional arguments.
+//! 
+//! You can specify exactly which notebook(s) to search by including them as arguments on the command
+//! line. The following would search only the notebooks `demo.ipynb` and `example.ipynb`, not any other
+//! notebooks, for the string "import":
+//! 
+//! ```bash
+//! jrep import demo.ipynb example.ipynb
+//! ```
+//! 
+//! Alternatively, you can specify directories as arguments, and any `*.ipynb` files in those directories
+//! will be searched. By default, `jrep` will *not* recurse into other directories; to enable that behavior,
+//! use the `--recursive` (short form: `-R`) flag, or `--max-depth N` to only go N levels deep. The
+//! following would search all notebooks in `~/Notebooks` for "import":
+//! 
+//! ```bash
+//! jrep import ~/Notebooks
+//! ```
+//! 
+//! You can mix and match directories and notebooks in the arguments, e.g.:
+//! 
+//! ```bash
+//! jrep import demo.ipynb ~/Notebooks
+//! ```
+//! 
+//! To check which notebooks a set of paths and options like these selects, give `--files` instead of a pattern,
+//! which prints the notebooks that would be searched without searching them.
+//! 
+//! Zip and tar archives (`.zip`, `.tar`, `.tar.gz`, and `.tgz` files) given as paths are searched without
+//! extracting them, and their notebooks are named by the archive and the path inside it, e.g.
+//! `handouts.zip!week1/intro.ipynb`. Archives found in directories are only searched with `--search-archives`.
+//! 
+//! A path of `-` searches a notebook piped to stdin, e.g. `curl -s https://example.com/demo.ipynb | jrep import -`.
+//! 
+//! If jrep was compiled with the `object_store` feature, notebooks can be searched in Amazon S3, Google Cloud
+//! Storage, or Azure Blob Storage without downloading them first, by giving their URLs (`s3://`, `gs://`, or `az://`)
+//! as paths. A URL that does not end in `.ipynb` is listed like a directory, e.g. `jrep -R co2 s3://bucket/runs/`.
+//! Credentials come from the providers' usual environment variables, such as `AWS_ACCESS_KEY_ID` and `AWS_REGION`.
+//! 
+//! Notebooks on another computer can be searched over SSH with `--remote`, e.g. `jrep -R co2 --remote me@cluster:runs`.
+//! If jrep is installed there, it does the search; otherwise the notebooks are copied over one at a time and searched here.
+//! 
+//! The paths can also be read from a file, or from stdin with `--files-from -`, to search the notebooks that
+//! another program lists:
+//! 
+//! ```bash
+//! git ls-files '*.ipynb' | jrep --files-from - import
+//! ```
+//!
+//! If jrep was compiled with the `git2` feature, it can also ask git which notebooks to search. To review only
+//! your own work in a repository, `--changed` keeps just the notebooks that differ from HEAD (staged or not) or
+//! are new, e.g. `jrep -R todo . --changed`. `--changed=main` compares with another revision instead, so searches
+//! the notebooks a branch has changed.
+//!
+//! `--rev REF` searches the notebooks as they were committed at a revision, read straight from the repository
+//! without checking it out, e.g. `jrep -R --rev v1.2 'old_api\(' .`. They are named like
+//! `v1.2:./analysis/fit.ipynb`.
+//!
+//! Note however, that when searching a directory, *only* files ending in `.ipynb` (in any case) are searched.
+//! To search files with other extensions, give each extension to search with `--ext`, e.g.
+//! `--ext ipynb --ext ipynb.json`.
+//! 
+//! Python scripts (`.py`) and Markdown files (`.md`) are read as notebooks that jupytext saved in its percent, light,
+//! or Markdown formats, split into code, markdown, and raw cells, so e.g. `jrep --ext py --cell-type markdown numpy`
+//! searches only the comments that jupytext would turn into markdown cells. marimo notebooks, which are also `.py` files,
+//! are recognized by their `@app.cell` functions, each of which is a cell. Quarto (`.qmd`) and R Markdown (`.Rmd`)
+//! documents are split the same way, with each code chunk a code cell and the prose between them markdown cells.
+//! None of these have outputs.
+//! 
+//! Apache Zeppelin notes (`.zpln` files, or `note.json` from older versions) are read with each paragraph as a cell
+//! (markdown if it starts with `%md`) and its results as outputs, so `--ext zpln` searches them alongside notebooks.
+//! 
+//! When listing directories, hidden files and directories (whose names start with a `.`, like `.git` and
+//! `.venv`) are skipped unless `--hidden` is given; Jupyter's `.ipynb_checkpoints` directories are skipped
+//! even then, unless `--include-checkpoints` is given too. So are files and directories that git ignores (through
+//! `.gitignore` files, `.git/info/exclude`, or the global excludes file), so virtual environments and data
+//! directories do not clutter the results. Notebooks can also be left out of searches without touching `.gitignore` by listing
+//! them in a `.jrepignore` file, with the same syntax, in the directory searched (or one above it) or in your
+//! home directory, e.g.
+//! 
+//! ```text
+//! archive/
+//! *-scratch.ipynb
+//! ```
+//! 
+//! Give `--no-ignore` to search ignored notebooks anyway.
+//! 
+//! To narrow down which notebooks are searched, give `--include` and `--exclude` globs. For example, this
+//! searches the homework notebooks but not the scratch copies of them:
+//! 
+//! ```bash
+//! jrep -R --include 'hw*/*.ipynb' --exclude '*-scratch.ipynb' import ~/Notebooks
+//! ```
+//! 
+//! Large collections of notebooks can be searched several at a time with `--threads` (`-j`).
+//! The results are printed in the same order as without it; add `--unordered` to print each
+//! notebook's results as soon as it has been searched instead:
+//! 
+//! ```bash
+//! jrep -j 8 -R import ~/Notebooks
+//! ```
+//! 
+//! If you search the same large collection often, `--build-index` saves the text of every notebook
+//! to an index file, and `--use-index` searches that instead of parsing each notebook again. Notebooks
+//! that have changed since the index was built are still read, so rebuilding it now and then only
+//! keeps it fast:
+//! 
+//! ```bash
+//! cd ~/Notebooks
+//! jrep --build-index -R
+//! jrep --use-index -R import
+//! ```
+//! 
+//! Without an index, `--cache` saves each notebook searched, without its non-text outputs, in
+//! `$XDG_CACHE_HOME/jrep` (or `~/.cache/jrep`), so searching the same notebooks again with `--cache` is
+//! faster as long as they have not changed. The least recently used notebooks are deleted once the cache
+//! passes 256 MB, and `--clear-cache` deletes all of it. The cache is off unless `--cache` is given, and
+//! `--no-cache` turns it back off, e.g. after a `--cache` in a shell alias.
+//! 
+//! To search the same notebooks from other programs (e.g. a dashboard), `--serve` keeps them
+//! parsed in memory and answers searches over HTTP with the same JSON as `--json`:
+//! 
+//! ```bash
+//! jrep --serve --port 8080 -R ~/Notebooks
+//! curl 'http://127.0.0.1:8080/search?q=read_csv&cell_type=code'
+//! ```
+//! 
+//! Editor extensions can instead run `jrep --rpc` as a child process and send it searches as
+//! JSON-RPC messages on stdin, getting the matches back on stdout as they are found.
+//! 
+//! If jrep was compiled with the `tantivy` feature, the index also supports ranked full-text queries
+//! with `--fulltext`, which lists the cells that best match some words rather than every line that
+//! matches a pattern, e.g. `jrep --fulltext 'cell_type:markdown "data cleaning"'`.
+//! 
+//! ## Understanding which cells are searched
+//! 
+//! At the top-most level, Jupyter notebooks consist of cells. Each cell has 
+//! source data (the text or code that you enter) and may have zero, one, or
+//! multiple output elements. Each cell is classified as "code", "markdown",
+//! or "raw" based on the type of source data it has. The output elements
+//! have a wider variety of types, but some common ones include "text/plain"
+//! and "image/png".
+//! 
+//! `jrep` will, by default, search the source data for all cell types, but
+//! only output from code cells with the type "text/plain". This gives you default
+//! behavior to search all or most human-readable text in the notebooks, but not
+//! any image or other non-text output. You can change this behavior with several
+//! of the command line flags:
+//! 
+//! * To limit which cell types (i.e. markdown, raw, or code) are searched, use the
+//!   `--cell-types` (short form: `-t`) option. You can specify this more than once
+//!   if you want to search two of the cell types, e.g. `-t markdown -t raw`.
+//! * To only search cells with a certain tag, use `--tag`, or to skip cells with a tag
+//!   (such as solutions in a homework notebook), use `--exclude-tag`.
+//! * To turn off searching the source data (i.e. input) of the cells, use
+//!   `--no-include-source` (short form: `-X`). This is just a flag, it doesn't take
+//!   any arguments.
+//! * To turn off searching outputs from code cells, use `--no-include-output`.
+//! * To change which output types are searched, use `--output-type` (short form: `-O`)
+//!   followed by the type. For example, if you did want to search image output (for some
+//!   reason), you could use `-O image/png`. Note that specifying any `--output-type` options
+//!   overrides the default of "text/plain". That means that if your notebook has outputs
+//!   of both "text/plain" and "text/latex" that you want to search, you need to pass both
+//!   types as options, i.e. `-O text/plain -O text/latex`.
+//!   To search every text-like output type at once, use `--all-text-outputs`.
+//! * Errors raised by a cell are stored as a special kind of output. Use `-O error` to search
+//!   the exception names, messages, and tracebacks, e.g. `jrep -O error KeyError`.
+//! 
+//! ## Specifying the search string
+//! 
+//! `jrep` treats the search pattern given to it as a regular expression. This means that both
+//! searches for simple patterns, such as "numpy" or "import", and more abstract patterns, such
+//! as "foo\s?=\s?[a-zA-Z]" can be used. The regular expression syntax should be generally
+//! similar to that of [grep](https://www.man7.org/linux/man-pages/man1/grep.1.html), although
+//! this is not strictly enforced. `jrep` uses the [Regex crate](https://docs.rs/regex/latest/regex/),
+//! so see their [syntax page](https://docs.rs/regex/latest/regex/#syntax) for the exact syntax
+//! supported.
+//!
+//! The Regex crate does not support lookaround (e.g. `(?<!#)\bimport\b`) or backreferences. If jrep
+//! was compiled with the `fancy-regex` feature (`cargo build --release --features fancy-regex`), you
+//! can pass `--engine fancy` to use the [fancy-regex crate](https://docs.rs/fancy-regex/latest/fancy_regex/)
+//! instead, which supports both at the cost of some speed.
+//!
+//! Some searches come up often enough that jrep has them built in. `--preset secrets` takes the place of
+//! the pattern and looks for credentials that tend to end up in notebooks: AWS, GitHub, Google, Slack, and
+//! Stripe keys, private key headers, passwords in connection strings, and the like. `--preset pii` looks for
+//! personal information: email addresses, phone numbers, Social Security numbers, and credit card numbers
+//! (only those that pass the Luhn checksum). Presets search all the text output types as well as the cell
+//! source, so this includes the HTML tables of DataFrames, e.g. `jrep --preset pii -R .`. Keys that no pattern
+//! knows about can often be found by how random they look: `--entropy 4.5` (also without a pattern) reports
+//! strings of 20 or more base64 characters with at least 4.5 bits of entropy per character.
+//!
+//! If you are not sure of the exact spelling of what you are looking for, `--fuzzy N` treats the
+//! pattern as literal text and allows up to N single-character edits, so `jrep --fuzzy 1 DataFrame`
+//! will also find "DataFarme".
+//!
+//! Accented characters can be stored in more than one way in Unicode (e.g. "é" as a single character
+//! or as "e" plus a combining accent), and notebooks written on different systems may not agree. Pass
+//! `--normalize nfc` (or `--normalize nfkc` to also unify compatibility characters like "ﬁ") to normalize
+//! both the pattern and the notebook text before matching, so that either form will match.
+//!
+//! Note that your shell may interpret certain special characters in the regular expressions itself -
+//! especially `*`, `?`, `{`, `}`, and `\`. If you're giving a regular expression as the pattern for
+//! `jrep` to search for, you will probably have the best luck if you wrap it in single quotes (e.g.
+//! `jrep 'foo\s?=\s?[a-zA-Z]'`), but your experience may depend on what shell you use.
+//! 
+//! The default behavior is for `jrep` to respect the case of the search string, meaning the pattern
+//! "Foo" will not match "foo" in the notebooks. You can set `jrep` to ignore case with the `--ignore-case`
+//! (short form: `-i`) flag.
+//! 
+//! ## Replacing
+//!
+//! `--replace TEMPLATE` changes the notebooks instead of printing the matches: every match in the
+//! source of the searched cells is replaced with TEMPLATE (`$1`, `${name}`, etc. are the regex's capture
+//! groups), and each changed notebook is written back. Only the changed strings are rewritten, so the
+//! rest of the file, including its formatting, metadata, and cell IDs, is left exactly as it was. For
+//! example, `jrep -R --replace load_data '\bread_data\b' .` renames `read_data` to `load_data` in every
+//! notebook under the current directory.
+//! Pass `--replace-outputs` to also change the searched text outputs, and `--dry-run` to see how many
+//! replacements would be made without writing anything.
+//!
+//! To share notebooks without what they match, `--redact DIR` instead writes a copy of every notebook
+//! into DIR with every match the search would find (in the source, the searched outputs including
+//! error messages and tracebacks, and the searched metadata) replaced by `[REDACTED]` (or the text given
+//! with `--mask`), leaving the originals alone, e.g. `jrep -R --preset secrets --redact shared/ .`.
+//!
+//! `--strip-outputs` clears the outputs and execution count of each code cell whose source matches and
+//! writes the notebook back, so `jrep --strip-outputs 'plt\.show' analysis.ipynb` drops just the plots.
+//! Add `--all` (without a pattern) to strip every searched cell.
+//!
+//! ## Machine-readable output
+//! 
+//! If you want to use the results of a search in another program, pass `--json` to get a single JSON document
+//! describing every match (file, cell, line, match positions, etc.) instead of the normal grep-like output.
+//! See `jrep --help` for the exact structure. Any of the output formats can be written to a file instead of
+//! stdout with `--output PATH`.
+//! 
+//! ## The rest of the interface
+//! 
+//! There are many more command line options not described here. They are all explained in the command line
+//! interface itself, and can be viewed with `jrep --help`. 
+//! 
+//! ## Using jrep from Rust
+//! 
+//! jrep is also a library, so other programs can search notebooks the same way. Make the
+//! [`SearchOptions`] from the same arguments you would give the program, then go through the
+//! matches with a [`Searcher`]:
+//! 
+//! ```no_run
+//! use jrep::{SearchOptions, Searcher};
+//! 
+//! let opts = SearchOptions::from_args(["-R", "-i", "--cell-type", "code", "read_csv"]).unwrap();
+//! let searcher = Searcher::new(opts);
+//! for m in searcher.search(&["notebooks"]).unwrap().take(10) {
+//!     match m {
+//!         Ok(m) => println!("{:?} cell {:?}: {}", m.path, m.cell, m.line.trim_end()),
+//!         Err(e) => eprintln!("{}", e)
+//!     }
+//! }
+//! ```
+//! 
+//! Notebooks are only searched as the matches are needed. To handle each match without copying
+//! it out of the notebook, call [`search_notebook`] with your own [`Printer`] instead:
+//! 
+//! ```no_run
+//! use jrep::{FoundNotebook, MatchContext, MatchedLine, Printer, SearchOptions};
+//! use termcolor::{NoColor, WriteColor};
+//! 
+//! struct Lines(Vec<String>, NoColor<std::io::Sink>);
+//! 
+//! impl Printer for Lines {
+//!     fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> std::io::Result<()> {
+//!         self.0.push(format!("cell {:?}: {}", ctx.icell, m.line.trim_end()));
+//!         Ok(())
+//!     }
+//! 
+//!     fn writer(&mut self) -> &mut dyn WriteColor {
+//!         &mut self.1
+//!     }
+//! }
+//! 
+//! let opts = SearchOptions::from_args(["-i", "--cell-type", "code", "read_csv"]).unwrap();
+//! let mut lines = Lines(Vec::new(), NoColor::new(std::io::sink()));
+//! jrep::search_notebook(&FoundNotebook::local("analysis.ipynb"), &opts, &mut lines).unwrap();
+//! ```
+//! 
+//! [`parse_notebook`] gives the parsed [`Notebook`] itself, for programs that want to look at the
+//! cells directly.
+
+
+
+mod ansi;
+mod archive;
+pub mod cache;
+#[cfg(feature = "object_store")]
+mod cloud;
+mod entropy;
+mod found;
+mod fuzzy;
+#[cfg(feature = "tantivy")]
+pub mod fulltext;
+mod git;
+pub mod index;
+mod jsonquery;
+mod jupytext;
+mod normalize;
+mod notebook;
+mod options;
+mod presets;
+mod printer;
+mod remote;
+mod replace;
+mod rewrite;
+mod search;
+mod searcher;
+mod spans;
+mod strip;
+mod svg;
+mod walk;
+mod zeppelin;
+
+use std::fmt;
+pub use archive::{is_archive, ArchiveMember};
+pub use found::{find_notebooks, is_cloud_url, FoundNotebook, Source, STDIN_PATH};
+#[cfg(feature = "git2")]
+pub use git::RevBlob;
+pub use notebook::{
+    escape_pointer_token, is_json, is_text, parse_notebook, Cell, CellMetadata, KernelSpec, LanguageInfo,
+    Notebook, NotebookMetadata, Output, TEXT_OUTPUT_DATA_TYPES
+};
+pub use options::SearchOptions;
+pub use printer::{MatchContext, Printer};
+pub use remote::{RemoteFile, RemoteTarget};
+pub use replace::{copy_path, replace_in_notebook, Replacement};
+pub use rewrite::write_atomically;
+pub use search::{markdown_headings, search_notebook, search_notebook_text, MatchedLine, Searched};
+pub use searcher::{Match, Matches, Searcher};
+pub use strip::strip_outputs;
+
+/// Any error in reading, parsing, or searching a notebook, or in the search options
+#[derive(Debug)]
+pub struct RunErr {
+    msg: String
+}
+
+impl fmt::Display for RunErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)?;
+        fmt::Result::Ok(())
+    }
+}
+
+impl std::error::Error for RunErr {}
+
+impl From<std::io::Error> for RunErr {
+    fn from(error: std::io::Error) -> Self {
+        let msg = error.to_string();
+        Self{msg}
+    }
+}
+
+impl From<regex::Error> for RunErr {
+    fn from(error: regex::Error) -> Self {
+        let msg = error.to_string();
+        Self{msg}
+    }
+}
+
+impl From<serde_json::Error> for RunErr {
+    fn from(error: serde_json::Error) -> Self {
+        let msg = error.to_string();
+        Self{msg}
+    }
+}
+
+impl From<&str> for RunErr {
+    fn from(msg: &str) -> Self {
+        Self{msg: String::from(msg)}
+    }
+}
+
+
+impl From<String> for RunErr {
+    fn from(msg: String) -> Self {
+        Self{msg}
+    }
+}
@@ -0,0 +1,99 @@
+//! Unicode normalization for the `--normalize` option
+//!
+//! Text that looks identical can be encoded differently: "é" may be one code point
+//! (U+00E9) or an "e" followed by a combining accent (U+0301). Normalizing both the
+//! pattern and the searched text lets them match, but the match offsets are then
+//! relative to the normalized text. To highlight the right part of the original line,
+//! we normalize it piece by piece, where each piece starts at a character that does
+//! not combine with the previous one, and record where the pieces line up.
+
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+use unicode_normalization::char::canonical_combining_class;
+
+#[derive(Clone, Copy)]
+pub enum NormalForm {
+    Nfc,
+    Nfkc
+}
+
+impl NormalForm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nfc" => Some(NormalForm::Nfc),
+            "nfkc" => Some(NormalForm::Nfkc),
+            _ => None
+        }
+    }
+
+    pub fn normalize(&self, s: &str) -> String {
+        match self {
+            NormalForm::Nfc => s.nfc().collect(),
+            NormalForm::Nfkc => s.nfkc().collect()
+        }
+    }
+
+    fn is_normalized(&self, s: &str) -> bool {
+        let check = match self {
+            NormalForm::Nfc => is_nfc_quick(s.chars()),
+            NormalForm::Nfkc => is_nfkc_quick(s.chars())
+        };
+        check == IsNormalized::Yes
+    }
+}
+
+
+/// A line of text in normal form, along with the information needed to map
+/// byte offsets in the normalized text back to the original line.
+pub struct NormalizedLine {
+    text: String,
+    // (normalized offset, original offset) pairs where the two texts line up.
+    // Empty if the original line was already normalized.
+    boundaries: Vec<(usize, usize)>
+}
+
+impl NormalizedLine {
+    pub fn new(line: &str, form: NormalForm) -> Self {
+        if form.is_normalized(line) {
+            return Self{text: String::from(line), boundaries: Vec::new()};
+        }
+
+        let mut text = String::with_capacity(line.len());
+        let mut boundaries = vec![(0, 0)];
+        let mut piece_start = 0;
+        for (idx, c) in line.char_indices() {
+            if idx > piece_start && canonical_combining_class(c) == 0 {
+                text.push_str(&form.normalize(&line[piece_start..idx]));
+                boundaries.push((text.len(), idx));
+                piece_start = idx;
+            }
+        }
+        text.push_str(&form.normalize(&line[piece_start..]));
+        boundaries.push((text.len(), line.len()));
+
+        Self{text, boundaries}
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Convert a (start, stop) span in the normalized text to the span in the original
+    /// line that covers it. Spans that begin or end partway through a normalized piece
+    /// are widened to include the whole piece.
+    pub fn to_original(&self, span: (usize, usize)) -> (usize, usize) {
+        if self.boundaries.is_empty() {
+            return span;
+        }
+
+        let (start, stop) = span;
+        let orig_start = self.boundaries.iter().rev()
+            .find(|&&(norm, _)| norm <= start)
+            .map(|&(_, orig)| orig)
+            .unwrap_or(0);
+        let orig_stop = self.boundaries.iter()
+            .find(|&&(norm, _)| norm >= stop)
+            .map(|&(_, orig)| orig)
+            .unwrap_or_else(|| self.boundaries.last().unwrap().1);
+        (orig_start, orig_stop)
+    }
+}
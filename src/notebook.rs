@@ -0,0 +1,651 @@
+//! The notebook format, and reading notebooks from their JSON
+//!
+//! Only the parts of a notebook that jrep searches or prints are kept. Output data that is
+//! not going to be searched is skipped over while parsing (see `NotebookSeed`), since it is
+//! usually most of the file.
+
+use std::fmt;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeSeed;
+use crate::{ansi, jupytext, svg, zeppelin, RunErr, SearchOptions};
+
+/// The output data types that are text, and so are searched line by line
+pub const TEXT_OUTPUT_DATA_TYPES: [&str;5] = ["text/plain", "text/latex", "text/markdown", "text/html", "application/javascript"];
+pub(crate) const SVG_DATA_TYPE: &str = "image/svg+xml";
+
+/// A parsed notebook. Only the parts that jrep searches are kept, so writing it back out would
+/// not give the same file.
+#[derive(Serialize, Clone)]
+pub struct Notebook {
+    pub cells: Vec<Cell>,
+    pub metadata: NotebookMetadata
+}
+
+impl<'de> Deserialize<'de> for Notebook {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        NotebookSeed{kept: None}.deserialize(deserializer)
+    }
+}
+
+impl Notebook {
+    /// The programming language of the notebook's kernel, e.g. "python", if the notebook records it
+    pub fn language(&self) -> Option<&str> {
+        let from_info = self.metadata.language_info.as_ref().and_then(|li| li.name.as_deref());
+        let from_kernel = self.metadata.kernelspec.as_ref().and_then(|ks| ks.language.as_deref());
+        from_info.or(from_kernel)
+    }
+
+    /// Whether the notebook's language or kernel name is one of `langs` (ignoring case). Notebooks
+    /// that record neither never match.
+    pub(crate) fn has_language(&self, langs: &[String]) -> bool {
+        let kernel_name = self.metadata.kernelspec.as_ref()
+            .and_then(|ks| ks.other.get("name"))
+            .and_then(|name| name.as_str());
+        let names = [self.language(), kernel_name];
+        langs.iter().any(|lang| names.iter().flatten().any(|name| name.eq_ignore_ascii_case(lang)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct NotebookMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernelspec: Option<KernelSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language_info: Option<LanguageInfo>,
+    /// Everything else, kept for --search-notebook-metadata
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KernelSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LanguageInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>
+}
+
+#[derive(Serialize, Default, Clone)]
+pub struct Cell {
+    /// Cell IDs were added in nbformat 4.5, so older notebooks do not have them
+    pub id: Option<String>,
+    /// "code", "markdown", or "raw"
+    pub cell_type: String,
+    pub execution_count: Option<usize>,
+    pub metadata: CellMetadata,
+    /// The lines of the source, each with its newline except perhaps the last
+    pub source: Vec<String>,
+    /// Whether the source was saved as one string rather than a list of lines, so that the
+    /// JSON Pointer to each line is that of the whole string
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub source_is_string: bool,
+    /// `None` for markdown and raw cells
+    pub outputs: Option<Vec<Output>>
+}
+
+impl<'de> Deserialize<'de> for Cell {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        CellSeed{kept: None}.deserialize(deserializer)
+    }
+}
+
+/// nbformat allows multi-line text to be saved either as a list of lines or as one string.
+/// This accepts both, splitting a single string into lines (keeping the newlines, as in the
+/// list form) so that the rest of jrep only has to handle lists, but remembers which it was.
+struct SavedLines {
+    lines: Vec<String>,
+    one_string: bool
+}
+
+impl<'de> Deserialize<'de> for SavedLines {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LinesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for LinesVisitor {
+            type Value = SavedLines;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string or a list of strings")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+                Ok(SavedLines{lines: s.split_inclusive('\n').map(String::from).collect(), one_string: true})
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut lines = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(line) = seq.next_element()? {
+                    lines.push(line);
+                }
+                Ok(SavedLines{lines, one_string: false})
+            }
+        }
+
+        deserializer.deserialize_any(LinesVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct CellMetadata {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Everything else, kept for --search-metadata
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>
+}
+
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Output {
+    // data must be a map of Value enums because some outputs are arrays ("text/plain")
+    // and others are just a string ("image/png"). Would've just made a structure for
+    // the output data with each type but (a) that's not very extensible and (b) can't have
+    // slashes in field names 
+    pub data: Option<BTreeMap<String, serde_json::Value>>, 
+    pub text: Option<Vec<String>>,
+    /// Whether `text` was saved as one string rather than a list of lines (see `Cell::source_is_string`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub text_is_string: bool,
+    pub output_type: String,
+    // Only present on "stream" outputs: "stdout" or "stderr"
+    pub name: Option<String>,
+    // Only present on "error" outputs: the exception name and message, and the formatted
+    // traceback, one frame per element (each frame may have several lines)
+    pub ename: Option<String>,
+    pub evalue: Option<String>,
+    pub traceback: Option<Vec<String>>
+}
+
+impl<'de> Deserialize<'de> for Output {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        OutputSeed{kept: None}.deserialize(deserializer)
+    }
+}
+
+impl Output {
+    /// Replace the data of searched output types that are not stored as lines of text with the
+    /// text that should be searched: for JSON, the pretty-printed part selected by --json-query
+    /// (or all of it), and for SVG images, the text labels. See `has_converted_data`.
+    ///
+    /// Vendor JSON types (e.g. widgets or plotly figures) are mostly numbers and settings, so
+    /// unless there is a query, only their strings are kept, as "key: value" lines; for a
+    /// widget that is just its model ID. Data of any other type that is unexpectedly not text
+    /// is treated the same way, so that it can be searched as non-text data instead of failing.
+    pub(crate) fn convert_data(&mut self, opts: &SearchOptions) {
+        for (dtype, val) in self.data.iter_mut().flatten() {
+            let unexpected_json = !is_json(dtype) && (val.is_object() || is_non_string_array(val));
+            if !(has_converted_data(dtype) || unexpected_json) || !opts.include_output_types.contains(dtype) {
+                continue;
+            }
+            let text = if dtype == SVG_DATA_TYPE {
+                // Usually one string, but may be split into lines like text types
+                let svg = convert_output_text_data(val).map(|lines| lines.concat()).unwrap_or_default();
+                svg::text_lines(&svg).join("\n")
+            }else if unexpected_json || (dtype.starts_with("application/vnd.") && opts.json_query.is_none()) {
+                let mut lines = Vec::new();
+                json_string_lines(val, "", &mut lines);
+                lines.join("\n")
+            }else{
+                let selected = match &opts.json_query {
+                    Some(query) => query.apply(val),
+                    None => vec![&*val]
+                };
+                selected.iter()
+                    .filter_map(|v| serde_json::to_string_pretty(v).ok())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            *val = serde_json::Value::String(text);
+        }
+    }
+
+    /// Remove terminal escape sequences from all of the text in this output (--strip-ansi)
+    pub fn strip_ansi(&mut self) {
+        let strings = self.text.iter_mut().flatten()
+            .chain(self.traceback.iter_mut().flatten())
+            .chain(self.ename.iter_mut())
+            .chain(self.evalue.iter_mut());
+        for s in strings {
+            ansi::strip_ansi_in_place(s);
+        }
+
+        // Text data is stored as a list of lines, other types usually as a single string
+        for val in self.data.iter_mut().flat_map(|d| d.values_mut()) {
+            match val {
+                serde_json::Value::String(s) => ansi::strip_ansi_in_place(s),
+                serde_json::Value::Array(a) => {
+                    for el in a.iter_mut() {
+                        if let serde_json::Value::String(s) = el {
+                            ansi::strip_ansi_in_place(s);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+pub(crate) fn convert_output_text_data(val: &serde_json::Value) -> Result<Vec<&str>, RunErr> {
+    let arr = match val {
+        serde_json::Value::Array(a) => a,
+        // Like cell source, text may also be saved as a single string
+        serde_json::Value::String(s) => return Ok(s.split_inclusive('\n').collect()),
+        _ => return Err(RunErr::from("Expected an array or string for output text values."))
+    };
+    let mut text_lines: Vec<&str> = Vec::with_capacity(arr.len());
+
+    for el in arr.iter() {
+        if let serde_json::Value::String(s) = el {
+            text_lines.push(s);
+        }else{
+            return Err(RunErr::from("Expected a string for all elements of output text value"));
+        }
+    }
+
+    Ok(text_lines)
+}
+
+pub(crate) fn convert_output_nontext_data(val: &serde_json::Value) -> Result<&str, RunErr> {
+    let data = if let serde_json::Value::String(s) = val {
+        s
+    }else{
+        return Err(RunErr::from("Unexpected type for nontext data"));
+    };
+
+    Ok(data)
+}
+
+pub fn is_text(datatype: &str) -> bool {
+    for &t in TEXT_OUTPUT_DATA_TYPES.iter() {
+        if t == datatype {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Whether output data of this type is stored as JSON rather than text. This is nbformat's
+/// pattern for JSON data, "^application/(.*\\+)?json$".
+pub fn is_json(datatype: &str) -> bool {
+    match datatype.strip_prefix("application/") {
+        Some(subtype) => subtype == "json" || subtype.ends_with("+json"),
+        None => false
+    }
+}
+
+
+/// Whether output data of this type is searched as text extracted from it (by `Output::convert_data`)
+/// rather than as it is stored
+pub(crate) fn has_converted_data(datatype: &str) -> bool {
+    is_json(datatype) || datatype == SVG_DATA_TYPE
+}
+
+
+/// Whether `value` is an array with anything other than strings in it (arrays of strings
+/// are how text data is normally stored)
+fn is_non_string_array(value: &serde_json::Value) -> bool {
+    matches!(value, serde_json::Value::Array(a) if !a.iter().all(serde_json::Value::is_string))
+}
+
+/// Collect the strings in a JSON value as "key: value" lines, with nested keys joined by dots
+/// (as for metadata). Numbers, booleans, and nulls are left out.
+fn json_string_lines(value: &serde_json::Value, key: &str, lines: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, v) in map.iter() {
+                let child_key = if key.is_empty() { k.clone() } else { format!("{}.{}", key, k) };
+                json_string_lines(v, &child_key, lines);
+            }
+        },
+        serde_json::Value::Array(arr) => {
+            for v in arr.iter() {
+                json_string_lines(v, key, lines);
+            }
+        },
+        serde_json::Value::String(s) => {
+            // A value with newlines would otherwise turn into lines without a key
+            for line in s.lines() {
+                lines.push(format!("{}: {}", key, line));
+            }
+        },
+        _ => {}
+    }
+}
+
+
+/// Escape a key for use in a JSON Pointer. MIME types always contain a "/",
+/// which would otherwise be read as a path separator.
+pub fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+
+/// Deserializes a notebook, keeping only the output data types in `kept`, or all of them if it
+/// is `None`. The others are skipped over in the file without being copied or parsed into
+/// values, which saves a lot of time and memory on notebooks full of base64-encoded plots.
+/// `CellSeed`, `OutputSeed`, and `DataSeed` do the same for the parts of a notebook.
+///
+/// A notebook as jrep saves it in the cache or index can be read back the same way.
+#[derive(Clone, Copy)]
+struct NotebookSeed<'a> {
+    kept: Option<&'a [String]>
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for NotebookSeed<'_> {
+    type Value = Notebook;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for NotebookSeed<'_> {
+    type Value = Notebook;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a notebook")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut cells = None;
+        let mut metadata = NotebookMetadata::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "cells" => cells = Some(map.next_value_seed(ListSeed(CellSeed{kept: self.kept}))?),
+                "metadata" => metadata = map.next_value()?,
+                _ => { map.next_value::<serde::de::IgnoredAny>()?; }
+            }
+        }
+        let cells = cells.ok_or_else(|| serde::de::Error::missing_field("cells"))?;
+        Ok(Notebook{cells, metadata})
+    }
+}
+
+/// Deserializes a cell (see `NotebookSeed`)
+#[derive(Clone, Copy)]
+struct CellSeed<'a> {
+    kept: Option<&'a [String]>
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for CellSeed<'_> {
+    type Value = Cell;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for CellSeed<'_> {
+    type Value = Cell;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a notebook cell")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut cell = Cell::default();
+        let mut cell_type = None;
+        let mut source: Option<SavedLines> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "id" => cell.id = map.next_value()?,
+                "cell_type" => cell_type = Some(map.next_value()?),
+                "execution_count" => cell.execution_count = map.next_value()?,
+                "metadata" => cell.metadata = map.next_value()?,
+                "source" => source = Some(map.next_value()?),
+                "source_is_string" => cell.source_is_string = map.next_value()?,
+                "outputs" => cell.outputs = map.next_value_seed(NullableSeed(ListSeed(OutputSeed{kept: self.kept})))?,
+                _ => { map.next_value::<serde::de::IgnoredAny>()?; }
+            }
+        }
+        cell.cell_type = cell_type.ok_or_else(|| serde::de::Error::missing_field("cell_type"))?;
+        let source = source.ok_or_else(|| serde::de::Error::missing_field("source"))?;
+        cell.source_is_string |= source.one_string;
+        cell.source = source.lines;
+        Ok(cell)
+    }
+}
+
+/// Deserializes an output (see `NotebookSeed`)
+#[derive(Clone, Copy)]
+struct OutputSeed<'a> {
+    kept: Option<&'a [String]>
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for OutputSeed<'_> {
+    type Value = Output;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for OutputSeed<'_> {
+    type Value = Output;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a cell output")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut outp = Output::default();
+        let mut output_type = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "data" => outp.data = map.next_value_seed(NullableSeed(DataSeed{kept: self.kept}))?,
+                // A null, as in the --use-index index, is the same as no text
+                "text" => if let Some(text) = map.next_value::<Option<SavedLines>>()? {
+                    outp.text_is_string |= text.one_string;
+                    outp.text = Some(text.lines);
+                },
+                "text_is_string" => outp.text_is_string |= map.next_value::<bool>()?,
+                "output_type" => output_type = Some(map.next_value()?),
+                "name" => outp.name = map.next_value()?,
+                "ename" => outp.ename = map.next_value()?,
+                "evalue" => outp.evalue = map.next_value()?,
+                "traceback" => outp.traceback = map.next_value()?,
+                _ => { map.next_value::<serde::de::IgnoredAny>()?; }
+            }
+        }
+        outp.output_type = output_type.ok_or_else(|| serde::de::Error::missing_field("output_type"))?;
+        Ok(outp)
+    }
+}
+
+/// Deserializes the data of an output, a map of MIME types to values (see `NotebookSeed`)
+#[derive(Clone, Copy)]
+struct DataSeed<'a> {
+    kept: Option<&'a [String]>
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for DataSeed<'_> {
+    type Value = BTreeMap<String, serde_json::Value>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for DataSeed<'_> {
+    type Value = BTreeMap<String, serde_json::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of MIME types to output data")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut data = BTreeMap::new();
+        while let Some(dtype) = map.next_key::<String>()? {
+            if self.kept.is_none_or(|types| types.contains(&dtype)) {
+                data.insert(dtype, map.next_value()?);
+            }else{
+                map.next_value::<serde::de::IgnoredAny>()?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Deserializes a list with the seed `S` for each element
+#[derive(Clone, Copy)]
+struct ListSeed<S>(S);
+
+impl<'de, S: serde::de::DeserializeSeed<'de> + Copy> serde::de::DeserializeSeed<'de> for ListSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: serde::de::DeserializeSeed<'de> + Copy> serde::de::Visitor<'de> for ListSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element_seed(self.0)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// Deserializes a value with the seed `S`, or `None` for a null
+#[derive(Clone, Copy)]
+struct NullableSeed<S>(S);
+
+impl<'de, S: serde::de::DeserializeSeed<'de>> serde::de::DeserializeSeed<'de> for NullableSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, S: serde::de::DeserializeSeed<'de>> serde::de::Visitor<'de> for NullableSeed<S> {
+    type Value = Option<S::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "null or a value")
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
+
+/// Parse the JSON text of a notebook
+pub fn parse_notebook(data: &str) -> Result<Notebook, RunErr>{
+    let notebook: Notebook = serde_json::from_str(data)?;
+
+    Ok(notebook)
+}
+
+/// Parse a notebook for --lenient: the file must still be JSON, but any cell, output, or the
+/// notebook metadata that does not have the expected structure is replaced with an empty
+/// placeholder (so that the indices of the others do not change) and a warning is printed
+/// unless `quiet` is set. Only the output data types in `kept` are kept (see `NotebookSeed`).
+fn parse_notebook_lenient(data: &str, filename: &std::ffi::OsString, kept: Option<&[String]>, quiet: bool) -> Result<Notebook, RunErr> {
+    let warn = |what: String, err: serde_json::Error| if !quiet {
+        eprintln!("Warning in file {:?}: skipping {}: {}", filename, what, err);
+    };
+    let mut value: serde_json::Value = serde_json::from_str(data)?;
+
+    let metadata = match value.get_mut("metadata").map(serde_json::Value::take) {
+        Some(meta) => serde_json::from_value(meta).unwrap_or_else(|e| {
+            warn(String::from("the notebook metadata"), e);
+            NotebookMetadata::default()
+        }),
+        None => NotebookMetadata::default()
+    };
+
+    let cell_values = match value.get_mut("cells").map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(cells)) => cells,
+        _ => return Err(RunErr::from("The notebook does not have a list of cells"))
+    };
+
+    let mut cells = Vec::with_capacity(cell_values.len());
+    for (icell, mut cell_value) in cell_values.into_iter().enumerate() {
+        // Outputs are checked one at a time so that one bad output does not take the cell with it
+        let outputs = match cell_value.get_mut("outputs").map(serde_json::Value::take) {
+            Some(serde_json::Value::Array(outputs)) => Some(outputs.into_iter().enumerate().map(|(ioutp, outp)| {
+                OutputSeed{kept}.deserialize(outp).unwrap_or_else(|e| {
+                    warn(format!("output {} of cell {}", ioutp, icell), e);
+                    Output{output_type: String::from("invalid"), ..Output::default()}
+                })
+            }).collect()),
+            _ => None
+        };
+
+        let cell = match (CellSeed{kept}).deserialize(cell_value) {
+            Ok(cell) => Cell{outputs, ..cell},
+            Err(e) => {
+                warn(format!("cell {}", icell), e);
+                // Not one of the searchable cell types, so it is never searched
+                Cell{cell_type: String::from("invalid"), ..Cell::default()}
+            }
+        };
+        cells.push(cell);
+    }
+
+    Ok(Notebook{cells, metadata})
+}
+
+
+/// Parse a notebook to be searched. Only the output data types that will be searched are
+/// kept; the other modes (e.g. --notebook-stats) need all of them and use `read_notebook`.
+pub(crate) fn parse_notebook_for_search(data: &str, filename: &std::ffi::OsString, opts: &SearchOptions) -> Result<Notebook, RunErr> {
+    parse_notebook_keeping(data, filename, Some(&opts.include_output_types), opts)
+}
+
+/// Parse a notebook (leniently, if --lenient was given), keeping only the output data types
+/// in `kept_types`, or all of them if that is `None`
+pub(crate) fn parse_notebook_keeping(data: &str, filename: &std::ffi::OsString, kept_types: Option<&[String]>, opts: &SearchOptions) -> Result<Notebook, RunErr> {
+    if let Some(nb) = parse_other_format(data, filename, kept_types) {
+        return nb;
+    }
+    if opts.lenient {
+        parse_notebook_lenient(data, filename, kept_types, opts.no_messages)
+    }else{
+        let mut deserializer = serde_json::Deserializer::from_str(data);
+        let nb = NotebookSeed{kept: kept_types}.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(nb)
+    }
+}
+
+/// Parse a notebook that is not in Jupyter's own format, if `filename` is one: saved as text by
+/// jupytext, a Quarto or R Markdown document, or a Zeppelin note. Of the outputs, only the data
+/// types in `kept_types` are kept, or all of them if that is `None`.
+fn parse_other_format(data: &str, filename: &std::ffi::OsStr, kept_types: Option<&[String]>) -> Option<Result<Notebook, RunErr>> {
+    if let Some(format) = jupytext::Format::of(filename) {
+        Some(Ok(jupytext::parse(data, format)))
+    }else if zeppelin::is_note(filename) {
+        Some(zeppelin::parse(data, kept_types))
+    }else{
+        None
+    }
+}
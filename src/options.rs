@@ -0,0 +1,703 @@
+//! The options that decide what is searched, and how to find the notebooks to search
+//!
+//! They are made from the same arguments as the jrep program takes, and parsed the same way,
+//! so a program using jrep as a library can offer exactly the options the command line does.
+//! Only the options that change which notebooks are found or what is matched in them are kept
+//! here; output formats and the modes that do something other than search (e.g. `--toc`) are
+//! the program's own.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use regex::Regex;
+use crate::entropy::{self, EntropyScanner};
+use crate::fuzzy::FuzzyPattern;
+use crate::index::{self, NotebookIndex};
+use crate::jsonquery::JsonQuery;
+use crate::normalize::{NormalForm, NormalizedLine};
+use crate::presets::PresetMatcher;
+use crate::remote::RemoteTarget;
+use crate::{ansi, cache, walk, Cell, Notebook, RunErr, TEXT_OUTPUT_DATA_TYPES};
+
+/// The output data types searched if none are given with --output-type
+const DEFAULT_OUTPUTS: [&str;1] = ["text/plain"];
+
+/// The engine used to find matches. The standard engine (the `regex` crate) is
+/// always available; the fancy engine supports lookaround and backreferences but
+/// requires jrep to be compiled with the `fancy-regex` feature. The fuzzy matcher
+/// treats the pattern as a literal string and allows a number of edits. The preset
+/// matcher searches for a built-in set of patterns, and the entropy scanner has no
+/// pattern at all; it finds random-looking strings such as tokens.
+#[allow(clippy::large_enum_variant)] // only ever one instance, so boxing the regexes gains nothing
+enum Matcher {
+    Standard(Regex),
+    #[cfg(feature = "fancy-regex")]
+    Fancy(fancy_regex::Regex),
+    Fuzzy(FuzzyPattern),
+    Preset(PresetMatcher),
+    Entropy(EntropyScanner)
+}
+
+impl Matcher {
+    fn new(pattern: &str, engine: &str) -> Result<Self, RunErr> {
+        match engine {
+            "default" => Ok(Matcher::Standard(Regex::new(pattern).map_err(invalid_pattern)?)),
+            #[cfg(feature = "fancy-regex")]
+            "fancy" => Ok(Matcher::Fancy(fancy_regex::Regex::new(pattern).map_err(invalid_pattern)?)),
+            #[cfg(not(feature = "fancy-regex"))]
+            "fancy" => Err(RunErr::from("jrep was not compiled with the 'fancy-regex' feature, so '--engine fancy' is not available")),
+            _ => Err(RunErr::from("Unexpected value for '--engine'"))
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Standard(re) => re.is_match(text),
+            // fancy-regex can fail at match time (e.g. if it exceeds its backtracking
+            // limit); treat that as "no match" rather than aborting the whole search.
+            #[cfg(feature = "fancy-regex")]
+            Matcher::Fancy(re) => re.is_match(text).unwrap_or(false),
+            Matcher::Fuzzy(fp) => fp.is_match(text),
+            Matcher::Preset(pm) => pm.is_match(text),
+            Matcher::Entropy(scanner) => scanner.is_match(text)
+        }
+    }
+
+    /// Return the (start, stop) byte offsets of every match in `text`.
+    fn find_positions(&self, text: &str) -> Vec<(usize, usize)> {
+        match self {
+            Matcher::Standard(re) => {
+                re.find_iter(text).map(|m| (m.start(), m.end())).collect()
+            },
+            #[cfg(feature = "fancy-regex")]
+            Matcher::Fancy(re) => {
+                re.find_iter(text).filter_map(|m| m.ok()).map(|m| (m.start(), m.end())).collect()
+            },
+            Matcher::Fuzzy(fp) => fp.find_positions(text),
+            Matcher::Preset(pm) => pm.find_positions(text),
+            Matcher::Entropy(scanner) => scanner.find_positions(text)
+        }
+    }
+
+    /// The (start, stop) byte offsets of every match in `text`, each with the text to replace
+    /// it with: `template`, with capture group references like `$1` filled in if `expand` is
+    /// true. Only the regex engines have groups, so for the others the template is always used
+    /// as it is.
+    fn replacements(&self, text: &str, template: &str, expand: bool) -> Vec<(usize, usize, String)> {
+        match self {
+            Matcher::Standard(re) if expand => re.captures_iter(text).map(|caps| {
+                let m = caps.get(0).unwrap();
+                let mut replacement = String::new();
+                caps.expand(template, &mut replacement);
+                (m.start(), m.end(), replacement)
+            }).collect(),
+            #[cfg(feature = "fancy-regex")]
+            Matcher::Fancy(re) if expand => re.captures_iter(text).filter_map(|caps| caps.ok()).map(|caps| {
+                let m = caps.get(0).unwrap();
+                let mut replacement = String::new();
+                caps.expand(template, &mut replacement);
+                (m.start(), m.end(), replacement)
+            }).collect(),
+            _ => self.find_positions(text).into_iter().map(|(start, stop)| (start, stop, template.to_string())).collect()
+        }
+    }
+}
+
+
+fn invalid_pattern<E: fmt::Display>(error: E) -> RunErr {
+    RunErr{msg: format!("The search pattern was not valid: {}", error)}
+}
+
+
+/// What to search for and where in each notebook, made from command line arguments
+pub struct SearchOptions {
+    pattern: String,
+    matcher: Matcher,
+    normalize: Option<NormalForm>,
+    pub(crate) include_source: bool,
+    include_cell_types: Vec<String>,
+    pub(crate) languages: Vec<String>,
+    include_tags: Vec<String>,
+    exclude_tags: Vec<String>,
+    exec_count: Option<ExecCountFilter>,
+    pub(crate) cell_range: Option<CellRange>,
+    has_output: Option<bool>,
+    pub(crate) include_output_types: Vec<String>,
+    pub(crate) include_streams: Vec<String>,
+    pub(crate) output_kinds: Vec<String>,
+    pub(crate) json_query: Option<JsonQuery>,
+    pub(crate) strip_ansi: bool,
+    pub(crate) lenient: bool,
+    pub(crate) no_messages: bool,
+    pub(crate) show_section: bool,
+    pub(crate) search_metadata: bool,
+    pub(crate) search_notebook_metadata: bool,
+    pub(crate) errors_only: bool,
+    pub(crate) metadata_keys: Vec<String>,
+    pub(crate) invert_match: bool,
+    file_lines: bool,
+    pub(crate) index: Option<Arc<NotebookIndex>>,
+    pub(crate) cache: Option<cache::NotebookCache>,
+    pub(crate) sort_by: Option<SortKey>,
+    pub(crate) sort_reverse: bool,
+    pub(crate) threads: usize,
+    pub(crate) max_filesize: Option<u64>,
+    pub(crate) timeout_per_file: Option<Duration>,
+    pub(crate) recursive: bool,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) search_archives: bool,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) no_follow: bool,
+    pub(crate) one_file_system: bool,
+    pub(crate) hidden: bool,
+    pub(crate) include_checkpoints: bool,
+    pub(crate) no_ignore: bool,
+    pub(crate) path_filter: walk::PathFilter,
+    pub(crate) remote: Option<RemoteTarget>,
+    // With --changed, the revision to compare with, if one was given
+    pub(crate) changed: Option<Option<String>>,
+    // The git revision to read the notebooks from instead of the working tree
+    pub(crate) rev: Option<String>
+}
+
+impl SearchOptions {
+    /// Options to search for `pattern`, with every other option at its default
+    pub fn new(pattern: &str) -> Result<Self, RunErr> {
+        Self::from_args(["--", pattern])
+    }
+
+    /// Options parsed from command line arguments (without the program name), exactly as the
+    /// jrep program would parse them, e.g. `["-i", "--cell-type", "code", "numpy"]`. Only the
+    /// options that affect how a notebook is searched matter; paths, output formats, and modes
+    /// such as `--toc` are ignored.
+    pub fn from_args<I, T>(args: I) -> Result<Self, RunErr>
+    where I: IntoIterator<Item = T>, T: Into<std::ffi::OsString> + Clone
+    {
+        let yml = clap::load_yaml!("clargs.yml");
+        let args = std::iter::once(std::ffi::OsString::from("jrep")).chain(args.into_iter().map(Into::into));
+        // The message goes into a RunErr rather than to a terminal, so should have no color codes,
+        // but clap colors its suggestions regardless of the setting
+        let matches = clap::App::from_yaml(yml).setting(clap::AppSettings::ColorNever).get_matches_from_safe(args)
+            .map_err(|e| RunErr{msg: ansi::strip_ansi(&e.message).into_owned()})?;
+        Self::from_arg_matches(&matches)
+    }
+
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, RunErr> {
+        let ignore_case = matches.occurrences_of("case") > 0;
+        // --errors-only takes no pattern, so the first positional argument is a path
+        let errors_only = matches.occurrences_of("errors_only") > 0;
+        let invert_match = matches.occurrences_of("invert") > 0;
+        let max_depth = match matches.value_of("max_depth") {
+            Some(n) => Some(n.parse::<usize>().ok().filter(|&n| n > 0)
+                .ok_or_else(|| RunErr::from("The value for '--max-depth' must be a positive integer"))?),
+            None => None
+        };
+        let recursive = matches.occurrences_of("recursive") > 0 || max_depth.is_some();
+        let remote = match matches.value_of("remote") {
+            Some(_) if matches.occurrences_of("paths") > 0 => {
+                return Err(RunErr::from("Paths cannot be given with '--remote', whose path is searched instead"));
+            },
+            Some(spec) => Some(RemoteTarget::parse(spec)?),
+            None => None
+        };
+        // Lowercase so that they match file names without regard to case
+        let extensions: Vec<String> = matches.values_of("ext")
+            .map(|exts| exts.map(|e| e.trim_start_matches('.').to_lowercase()).collect())
+            .unwrap_or_else(|| vec![String::from("ipynb")]);
+        if extensions.iter().any(String::is_empty) {
+            return Err(RunErr::from("The values for '--ext' cannot be empty"));
+        }
+
+        let normalize = match matches.value_of("normalize") {
+            Some(name) => Some(NormalForm::from_name(name).ok_or_else(|| RunErr::from("Unexpected value for '--normalize'"))?),
+            None => None
+        };
+
+        // A preset or --entropy replaces the pattern given on the command line
+        let preset = matches.value_of("preset");
+        let entropy = matches.value_of("entropy");
+        // With --fulltext, the pattern is a query for tantivy, not a regex
+        let fulltext = matches.occurrences_of("fulltext") > 0;
+        let raw_pattern = if errors_only || fulltext || preset.is_some() || entropy.is_some() {
+            String::new()
+        }else{
+            // Modes that take no pattern (e.g. --toc) may have been given no positional arguments at all
+            String::from(matches.value_of("pattern").unwrap_or_default())
+        };
+        // The pattern must be normalized the same way as the text it will be compared against
+        let raw_pattern = match normalize {
+            Some(form) => form.normalize(&raw_pattern),
+            None => raw_pattern
+        };
+        let re = if ignore_case {
+            // In both cases the ?m (multi-line mode) flag is included
+            // so that newlines at the end do not need to be included in
+            // the regex to match with $ at the end. For example, the line
+            // "Subsetting ci" will not match the regex "Subsetting [a-z]{2}$"
+            // without the ?m flag because technically it is "Subsetting ci\n".
+            format!("(?i)(?m){}", raw_pattern)
+        }else{
+            format!("(?m){}", raw_pattern)
+        };
+
+        let engine = matches.value_of("engine").unwrap();
+        let matcher = if let Some(threshold) = entropy {
+            let min_length = matches.value_of("entropy_min_length").unwrap().parse::<usize>()
+                .map_err(|_| RunErr::from("The value for '--entropy-min-length' must be a non-negative integer"))?;
+            let charset = entropy::Charset::from_name(matches.value_of("entropy_charset").unwrap())
+                .ok_or_else(|| RunErr::from("Unexpected value for '--entropy-charset'"))?;
+            Matcher::Entropy(EntropyScanner::new(threshold, min_length, charset)?)
+        }else if let Some(name) = preset {
+            Matcher::Preset(PresetMatcher::new(name, ignore_case)?)
+        }else if let Some(n) = matches.value_of("fuzzy") {
+            let max_errors = n.parse::<usize>()
+                .map_err(|_| RunErr::from("The value for '--fuzzy' must be a non-negative integer"))?;
+            Matcher::Fuzzy(FuzzyPattern::new(&raw_pattern, max_errors, ignore_case))
+        }else{
+            Matcher::new(&re, engine)?
+        };
+
+        let languages: Vec<String> = matches.values_of("lang")
+            .map(|langs| langs.map(String::from).collect())
+            .unwrap_or_default();
+        let include_tags: Vec<String> = matches.values_of("tag")
+            .map(|tags| tags.map(String::from).collect())
+            .unwrap_or_default();
+        let exclude_tags: Vec<String> = matches.values_of("exclude_tag")
+            .map(|tags| tags.map(String::from).collect())
+            .unwrap_or_default();
+        let exec_count = match matches.value_of("exec_count") {
+            Some(spec) => Some(ExecCountFilter::parse(spec)?),
+            None => None
+        };
+        let cell_range = match matches.value_of("cells") {
+            Some(spec) => Some(CellRange::parse(spec)?),
+            None => None
+        };
+        let metadata_keys: Vec<String> = matches.values_of("metadata_key")
+            .map(|keys| keys.map(String::from).collect())
+            .unwrap_or_default();
+
+        // Because incl_src and no_incl_src override each other, and we want the default to be
+        // include cell source text, we only need to check that there are no non-overridden
+        // occurences of no_incl_src. Just checking "is_present" won't work - it's `true` even
+        // if overridden.
+        let n_skip_src = matches.occurrences_of("no_incl_src");
+        let incl_src = n_skip_src == 0 && !errors_only;
+
+        // Which cell types we search. Default is all (markdown, raw, code)
+        let cell_types = if let Some(vals) = matches.values_of("cell_types") {
+            let mut tmp = Vec::new();
+            for ct in vals {
+                tmp.push(String::from(ct));
+            }
+            tmp
+        }else{
+            vec![String::from("markdown"), String::from("code"), String::from("raw")]
+        };
+
+        // Which output types to include
+        let prelim_output_types = if let Some(vals) = matches.values_of("output_types") {
+            let mut tmp = Vec::new();
+            for ot in vals {
+                tmp.push(String::from(ot));
+            }
+            tmp
+        }else{
+            to_string_vec(&DEFAULT_OUTPUTS)
+        };
+
+        let mut output_types = if matches.occurrences_of("incl_output") > 0 {
+            to_string_vec(&DEFAULT_OUTPUTS)
+        }else if matches.occurrences_of("no_incl_output") > 0 {
+            Vec::new()
+        }else{
+            prelim_output_types
+        };
+        let output_kinds: Vec<String> = matches.values_of("output_kind")
+            .map(|kinds| kinds.map(String::from).collect())
+            .unwrap_or_default();
+        // Error outputs have no data of their own, so they are searched through the special 'error' type
+        if output_kinds.iter().any(|k| k == "error") && !output_types.iter().any(|t| t == "error") {
+            output_types.push(String::from("error"));
+        }
+
+        let max_filesize = match matches.value_of("max_filesize") {
+            Some(spec) => Some(parse_file_size(spec)?),
+            None => None
+        };
+        let timeout_per_file = match matches.value_of("timeout_per_file") {
+            Some(secs) => Some(secs.parse::<f64>().ok().filter(|s| s.is_finite() && *s > 0.0).map(Duration::from_secs_f64)
+                .ok_or_else(|| RunErr::from("The value for '--timeout-per-file' must be a positive number of seconds"))?),
+            None => None
+        };
+        let include_globs: Vec<&str> = matches.values_of("include").map(Iterator::collect).unwrap_or_default();
+        let exclude_globs: Vec<&str> = matches.values_of("exclude").map(Iterator::collect).unwrap_or_default();
+        let path_filter = walk::PathFilter::new(&include_globs, &exclude_globs)?;
+        let threads = matches.value_of("threads").unwrap().parse::<usize>().ok().filter(|&n| n > 0)
+            .ok_or_else(|| RunErr::from("The value for '--threads' must be a positive integer"))?;
+
+        let index = if matches.occurrences_of("use_index") > 0 {
+            let index_file = matches.value_of_os("index_file").unwrap_or(std::ffi::OsStr::new(index::DEFAULT_INDEX_FILE));
+            Some(Arc::new(NotebookIndex::load(Path::new(index_file))?))
+        }else{
+            None
+        };
+        let cache = if matches.occurrences_of("cache") > 0 {
+            cache::cache_dir().map(cache::NotebookCache::new)
+        }else{
+            None
+        };
+
+        let json_query = match matches.value_of("json_query") {
+            Some(expr) => Some(JsonQuery::parse(expr)?),
+            None => None
+        };
+        // A query is pointless unless JSON outputs are searched
+        if json_query.is_some() && !output_types.iter().any(|t| crate::is_json(t)) {
+            output_types.push(String::from("application/json"));
+        }
+
+        // Unlike the options above, this adds to the other output types rather than replacing them.
+        // Presets and --entropy look for things that could be printed in any output, so they imply it.
+        if matches.occurrences_of("all_text_outputs") > 0 || preset.is_some() || entropy.is_some() {
+            for &t in TEXT_OUTPUT_DATA_TYPES.iter() {
+                if !output_types.iter().any(|ot| ot == t) {
+                    output_types.push(String::from(t));
+                }
+            }
+        }
+
+        // --sort and --sortr override each other, so at most one will be present
+        let (sort_by, sort_reverse) = match (matches.value_of("sort"), matches.value_of("sortr")) {
+            (Some(key), _) => (Some(SortKey::from_name(key).ok_or_else(|| RunErr::from("Unexpected value for '--sort'"))?), false),
+            (None, Some(key)) => (Some(SortKey::from_name(key).ok_or_else(|| RunErr::from("Unexpected value for '--sortr'"))?), true),
+            (None, None) => (None, false)
+        };
+
+        let opts = SearchOptions{
+            pattern: match (preset, entropy) {
+                (Some(name), _) => format!("--preset {}", name),
+                (None, Some(threshold)) => format!("--entropy {}", threshold),
+                (None, None) if errors_only => String::new(),
+                (None, None) => String::from(matches.value_of("pattern").unwrap_or_default())
+            },
+            matcher,
+            normalize,
+            include_source: incl_src,
+            include_cell_types: cell_types,//vec![String::from("markdown"), String::from("code")],
+            include_output_types: output_types,
+            // Like --include-source, stripping is on unless the last of these is --no-strip-ansi
+            lenient: matches.occurrences_of("lenient") > 0,
+            no_messages: matches.occurrences_of("no_messages") > 0,
+            strip_ansi: matches.occurrences_of("no_strip_ansi") == 0,
+            json_query,
+            output_kinds,
+            include_streams: match matches.values_of("stream") {
+                Some(vals) => vals.map(String::from).collect(),
+                None => vec![String::from("stdout"), String::from("stderr")]
+            },
+            languages,
+            include_tags,
+            exclude_tags,
+            exec_count,
+            cell_range,
+            // --has-output and --no-output override each other, so at most one will be present
+            has_output: if matches.occurrences_of("has_output") > 0 {
+                Some(true)
+            }else if matches.occurrences_of("no_output") > 0 {
+                Some(false)
+            }else{
+                None
+            },
+            show_section: matches.occurrences_of("show_section") > 0,
+            search_metadata: (matches.occurrences_of("search_metadata") > 0 || !metadata_keys.is_empty()) && !errors_only,
+            search_notebook_metadata: matches.occurrences_of("search_notebook_metadata") > 0 && !errors_only,
+            errors_only,
+            metadata_keys,
+            invert_match,
+            file_lines: matches.occurrences_of("file_lines") > 0,
+            index,
+            cache,
+            sort_by,
+            sort_reverse,
+            threads,
+            max_filesize,
+            timeout_per_file,
+            recursive,
+            max_depth,
+            search_archives: matches.occurrences_of("search_archives") > 0,
+            extensions,
+            // --follow and --no-follow override each other, so at most one will be present
+            no_follow: matches.occurrences_of("no_follow") > 0,
+            one_file_system: matches.occurrences_of("one_file_system") > 0,
+            hidden: matches.occurrences_of("hidden") > 0,
+            include_checkpoints: matches.occurrences_of("include_checkpoints") > 0,
+            no_ignore: matches.occurrences_of("no_ignore") > 0,
+            path_filter,
+            remote,
+            changed: if matches.occurrences_of("changed") > 0 { Some(matches.value_of("changed").map(String::from)) } else { None },
+            rev: matches.value_of("rev").map(String::from)
+        };
+
+        Ok(opts)
+    }
+
+    /// The pattern as it was given, or e.g. "--preset secrets" if a preset takes its place
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// How many notebooks to search at once (--threads)
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The size above which notebooks are skipped without being read (--max-filesize)
+    pub fn max_filesize(&self) -> Option<u64> {
+        self.max_filesize
+    }
+
+    /// How long to spend on one notebook before giving up on it (--timeout-per-file)
+    pub fn timeout_per_file(&self) -> Option<Duration> {
+        self.timeout_per_file
+    }
+
+    /// The other computer whose notebooks are searched (--remote)
+    pub fn remote(&self) -> Option<&RemoteTarget> {
+        self.remote.as_ref()
+    }
+
+    /// Find the line in the notebook file of each match (as --file-line does), e.g. for an
+    /// output format that needs it
+    pub fn set_file_lines(&mut self, file_lines: bool) {
+        self.file_lines = file_lines;
+    }
+
+    /// The index of --use-index, if there is one, taken out of the options so that it can be
+    /// updated
+    pub fn take_index(&mut self) -> Option<Arc<NotebookIndex>> {
+        self.index.take()
+    }
+
+    /// Look the notebooks up in `index` instead of parsing them, as with --use-index
+    pub fn set_index(&mut self, index: Option<Arc<NotebookIndex>>) {
+        self.index = index;
+    }
+
+    /// Whether we need to find the line in the notebook file of each match
+    pub(crate) fn needs_file_lines(&self) -> bool {
+        self.file_lines
+    }
+
+    /// Whether `cell` passes the cell filters (--cell-type, --tag, --exclude-tag, --exec-count,
+    /// --has-output, --no-output)
+    pub(crate) fn searches_cell(&self, cell: &Cell) -> bool {
+        let tags = &cell.metadata.tags;
+        let cell_has_output = cell.outputs.iter().any(|o| !o.is_empty());
+        self.include_cell_types.contains(&cell.cell_type)
+            && (self.include_tags.is_empty() || self.include_tags.iter().any(|t| tags.contains(t)))
+            && !self.exclude_tags.iter().any(|t| tags.contains(t))
+            && self.exec_count.iter().all(|f| f.matches(cell))
+            && self.has_output.iter().all(|&h| h == cell_has_output)
+    }
+
+    /// The indices of the cells of `nb` that would be searched: those in the range of --cells
+    /// that pass the cell filters (--cell-type, --tag, etc.)
+    pub fn searched_cells(&self, nb: &Notebook) -> Vec<usize> {
+        let cell_indices = match &self.cell_range {
+            Some(range) => range.indices(nb.cells.len()),
+            None => 0..nb.cells.len()
+        };
+        nb.cells.iter().enumerate()
+            .filter(|(icell, cell)| cell_indices.contains(icell) && self.searches_cell(cell))
+            .map(|(icell, _)| icell)
+            .collect()
+    }
+
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        match self.normalize {
+            None => self.matcher.is_match(text),
+            Some(form) => self.matcher.is_match(&form.normalize(text))
+        }
+    }
+
+    /// Replace every match in `text` with `template` (see `Matcher::replacements`), returning
+    /// the new text (borrowed if there were no matches) and the number of matches. The matches
+    /// are found as a search would find them, after removing escape sequences if `strip_ansi`
+    /// is true and normalizing with --normalize, and the parts of the original text that they
+    /// cover are replaced.
+    pub(crate) fn replace_all<'t>(&self, text: &'t str, template: &str, expand: bool, strip_ansi: bool) -> (Cow<'t, str>, usize) {
+        let stripped = if strip_ansi { ansi::StrippedLine::new(text) } else { ansi::StrippedLine::unchanged(text) };
+        let norm_line = self.normalize.map(|form| NormalizedLine::new(stripped.text(), form));
+        let searched = norm_line.as_ref().map_or(stripped.text(), NormalizedLine::text);
+        let replacements = self.matcher.replacements(searched, template, expand);
+        if replacements.is_empty() {
+            return (Cow::Borrowed(text), 0);
+        }
+
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, stop, replacement) in replacements.iter() {
+            let span = norm_line.as_ref().map_or((*start, *stop), |n| n.to_original((*start, *stop)));
+            let (start, stop) = stripped.to_original(span);
+            // Widening a span to whole normalized pieces can make it overlap the one before
+            let start = start.max(last);
+            out.push_str(&text[last..start]);
+            out.push_str(replacement);
+            last = stop.max(start);
+        }
+        out.push_str(&text[last..]);
+        (Cow::Owned(out), replacements.len())
+    }
+
+    /// Find the byte offsets of all matches in `line`. If normalizing, the offsets
+    /// are mapped back to the original (un-normalized) line.
+    pub(crate) fn find_positions(&self, line: &str) -> Vec<(usize, usize)> {
+        match self.normalize {
+            None => self.matcher.find_positions(line),
+            Some(form) => {
+                let norm_line = NormalizedLine::new(line, form);
+                self.matcher.find_positions(norm_line.text())
+                    .into_iter()
+                    .map(|span| norm_line.to_original(span))
+                    .collect()
+            }
+        }
+    }
+}
+
+
+/// Which execution counts to search (--exec-count). Only code cells have an execution
+/// count, so other cells never pass this filter.
+enum ExecCountFilter {
+    /// Code cells that have not been run since the notebook was last cleared
+    NotExecuted,
+    /// Code cells whose execution count is in this range
+    Range(std::ops::RangeInclusive<usize>)
+}
+
+impl ExecCountFilter {
+    /// Parse "none", "N", "N-M", or a comparison such as ">10" or "<=5".
+    fn parse(spec: &str) -> Result<Self, RunErr> {
+        let invalid = || RunErr{msg: format!("Invalid --exec-count '{}': expected 'none', N, N-M, or a comparison like '>N'", spec)};
+        let number = |s: &str| s.trim().parse::<usize>().map_err(|_| invalid());
+
+        let spec = spec.trim();
+        let range = if spec == "none" {
+            return Ok(ExecCountFilter::NotExecuted);
+        }else if let Some(n) = spec.strip_prefix(">=") {
+            number(n)?..=usize::MAX
+        }else if let Some(n) = spec.strip_prefix("<=") {
+            0..=number(n)?
+        }else if let Some(n) = spec.strip_prefix('>') {
+            number(n)?.checked_add(1).ok_or_else(invalid)?..=usize::MAX
+        }else if let Some(n) = spec.strip_prefix('<') {
+            0..=number(n)?.checked_sub(1).ok_or_else(invalid)?
+        }else if let Some((lo, hi)) = spec.split_once('-') {
+            number(lo)?..=number(hi)?
+        }else{
+            let n = number(spec)?;
+            n..=n
+        };
+        Ok(ExecCountFilter::Range(range))
+    }
+
+    fn matches(&self, cell: &Cell) -> bool {
+        if cell.cell_type != "code" {
+            return false;
+        }
+        match (self, cell.execution_count) {
+            (ExecCountFilter::NotExecuted, count) => count.is_none(),
+            (ExecCountFilter::Range(range), Some(count)) => range.contains(&count),
+            (ExecCountFilter::Range(_), None) => false
+        }
+    }
+}
+
+/// Which cells to search by index (--cells). Either end may be left out, and negative
+/// indices count back from the end of the notebook, as in Python slices.
+pub(crate) struct CellRange {
+    start: Option<isize>,
+    end: Option<isize>
+}
+
+impl CellRange {
+    /// Parse "START..END" (end exclusive), "START..", "..END", or a single index "N".
+    fn parse(spec: &str) -> Result<Self, RunErr> {
+        let invalid = || RunErr{msg: format!("Invalid --cells '{}': expected START..END, START.., ..END, or a single cell index", spec)};
+        let index = |s: &str| -> Result<Option<isize>, RunErr> {
+            let s = s.trim();
+            if s.is_empty() { Ok(None) } else { s.parse::<isize>().map(Some).map_err(|_| invalid()) }
+        };
+
+        match spec.split_once("..") {
+            Some((start, end)) => Ok(Self{start: index(start)?, end: index(end)?}),
+            None => {
+                let i = index(spec)?.ok_or_else(invalid)?;
+                // A single negative index has no exclusive end to point to if it is the last cell
+                let end = if i == -1 { None } else { Some(i + 1) };
+                Ok(Self{start: Some(i), end})
+            }
+        }
+    }
+
+    /// The range of cell indices selected in a notebook with `n_cells` cells
+    pub(crate) fn indices(&self, n_cells: usize) -> std::ops::Range<usize> {
+        let resolve = |i: isize| if i < 0 {
+            n_cells.saturating_sub(i.unsigned_abs())
+        }else{
+            (i as usize).min(n_cells)
+        };
+        let start = self.start.map_or(0, resolve);
+        let end = self.end.map_or(n_cells, resolve);
+        start..end
+    }
+}
+
+
+/// What to sort the notebooks to search by (--sort/--sortr)
+pub(crate) enum SortKey {
+    Path,
+    Mtime,
+    Size
+}
+
+impl SortKey {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(SortKey::Path),
+            "mtime" => Some(SortKey::Mtime),
+            "size" => Some(SortKey::Size),
+            _ => None
+        }
+    }
+}
+
+
+fn to_string_vec(a: &[&str]) -> Vec<String> {
+    let mut tmp = Vec::new();
+    for &el in a {
+        tmp.push(String::from(el));
+    }
+    tmp
+}
+
+
+/// Parse a file size for --max-filesize: a number of bytes, optionally followed by K, M, or G
+/// (powers of 1024, in either case, with or without a trailing B), e.g. "50M" or "1.5G".
+fn parse_file_size(spec: &str) -> Result<u64, RunErr> {
+    let invalid = || RunErr{msg: format!("Invalid --max-filesize '{}': expected a size such as 500K, 50M, or 2G", spec)};
+    let upper = spec.trim().to_ascii_uppercase();
+    let digits = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, scale) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1u64 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1)
+    };
+    let size = number.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0).ok_or_else(invalid)?;
+    Ok((size * scale as f64) as u64)
+}
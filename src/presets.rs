@@ -0,0 +1,118 @@
+//! Built-in sets of patterns for `--preset`
+//!
+//! A preset is a list of named regular expressions that are searched for together, as one
+//! alternation. Some patterns cannot say everything with a regex alone, so a pattern may
+//! also have a check that each of its matches must pass (e.g. the Luhn checksum for credit
+//! card numbers); matches that fail it are dropped. Patterns must work with the default
+//! `regex` engine and must not have capture groups of their own, since each pattern is
+//! wrapped in a group to tell which one matched.
+
+use regex::Regex;
+use crate::RunErr;
+
+pub struct PresetPattern {
+    /// What the pattern is meant to catch
+    pub name: &'static str,
+    regex: &'static str,
+    check: Option<fn(&str) -> bool>
+}
+
+const fn pattern(name: &'static str, regex: &'static str) -> PresetPattern {
+    PresetPattern{name, regex, check: None}
+}
+
+const fn checked_pattern(name: &'static str, regex: &'static str, check: fn(&str) -> bool) -> PresetPattern {
+    PresetPattern{name, regex, check: Some(check)}
+}
+
+/// Credentials that are commonly pasted into notebooks
+pub const SECRETS: [PresetPattern; 11] = [
+    pattern("aws-access-key-id", r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|ANPA|ANVA|AIPA)[0-9A-Z]{16}\b"),
+    pattern("aws-secret-access-key", r#"(?i:aws_?secret_?(?:access_?)?key)['"]?\s*[:=]\s*['"]?[A-Za-z0-9/+=]{40}\b"#),
+    pattern("slack-token", r"\bxox[abposr]-[0-9A-Za-z-]{10,}"),
+    pattern("slack-webhook", r"https://hooks\.slack\.com/services/T[0-9A-Z]+/B[0-9A-Z]+/[0-9A-Za-z]+"),
+    pattern("github-token", r"\b(?:gh[pousr]_[0-9A-Za-z]{36,}|github_pat_[0-9A-Za-z_]{60,})"),
+    pattern("google-api-key", r"\bAIza[0-9A-Za-z_-]{35}"),
+    pattern("stripe-key", r"\b[rs]k_live_[0-9A-Za-z]{24,}"),
+    pattern("private-key", r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY(?: BLOCK)?-----"),
+    pattern("connection-string", r#"\b[A-Za-z][A-Za-z0-9+.-]*://[^\s:/@'"]+:[^\s/@'"]+@[^\s/'"]+"#),
+    pattern("password-assignment", r#"(?i:password|passwd|pwd)['"]?\s*[:=]\s*['"][^'"\s]{4,}['"]"#),
+    pattern("bearer-token", r#"(?i:authorization['"]?\s*[:=]\s*['"]?bearer)\s+[A-Za-z0-9._~+/-]{20,}=*"#),
+];
+
+/// Personal information. Card numbers come before phone numbers so that the longer match wins.
+pub const PII: [PresetPattern; 5] = [
+    pattern("email", r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b"),
+    checked_pattern("credit-card", r"\b[0-9](?:[ -]?[0-9]){12,18}\b", is_luhn_valid),
+    checked_pattern("ssn", r"\b[0-9]{3}-[0-9]{2}-[0-9]{4}\b", is_possible_ssn),
+    // North American numbers, e.g. (555) 234-5678 or +1 555.234.5678
+    pattern("phone", r"(?:\+1[-. ]?)?(?:\([2-9][0-9]{2}\)|\b[2-9][0-9]{2})[-. ]?[2-9][0-9]{2}[-. ][0-9]{4}\b"),
+    // Other numbers in international format, e.g. +44 20 7946 0958
+    pattern("international-phone", r"\+[2-9][0-9]{0,2}[-. ]?(?:[0-9][-. ]?){6,12}[0-9]\b"),
+];
+
+/// The names that `--preset` accepts
+pub const PRESET_NAMES: [&str; 2] = ["secrets", "pii"];
+
+pub struct PresetMatcher {
+    re: Regex,
+    patterns: &'static [PresetPattern]
+}
+
+impl PresetMatcher {
+    pub fn new(name: &str, ignore_case: bool) -> Result<Self, RunErr> {
+        let patterns: &'static [PresetPattern] = match name {
+            "secrets" => &SECRETS,
+            "pii" => &PII,
+            _ => return Err(RunErr{msg: format!("Unknown preset '{}' (the presets are: {})", name, PRESET_NAMES.join(", "))})
+        };
+        let alternatives: Vec<String> = patterns.iter().map(|p| format!("({})", p.regex)).collect();
+        let flags = if ignore_case { "(?i)(?m)" } else { "(?m)" };
+        let re = Regex::new(&format!("{}{}", flags, alternatives.join("|"))).map_err(|e| {
+            // Say which pattern is at fault, if it is one on its own and not how they were combined
+            let culprit = patterns.iter().find(|p| Regex::new(p.regex).is_err()).map_or("", |p| p.name);
+            RunErr{msg: format!("The '{}' preset is not valid ({}): {}", name, culprit, e)}
+        })?;
+        Ok(Self{re, patterns})
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find_positions(text).is_empty()
+    }
+
+    /// Return the (start, stop) byte offsets of every match in `text` that passes its pattern's check
+    pub fn find_positions(&self, text: &str) -> Vec<(usize, usize)> {
+        self.re.captures_iter(text).filter_map(|caps| {
+            // Group 0 is the whole match; group i+1 is pattern i
+            let (ipat, m) = (1..caps.len()).find_map(|i| caps.get(i).map(|m| (i - 1, m)))?;
+            match self.patterns[ipat].check {
+                Some(check) if !check(m.as_str()) => None,
+                _ => Some((m.start(), m.end()))
+            }
+        }).collect()
+    }
+}
+
+/// Whether the digits in `number` pass the Luhn checksum used by payment card numbers
+fn is_luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits.iter().rev().enumerate().map(|(i, &d)| {
+        if i % 2 == 1 {
+            if d * 2 > 9 { d * 2 - 9 } else { d * 2 }
+        }else{
+            d
+        }
+    }).sum();
+    sum.is_multiple_of(10)
+}
+
+/// Social Security numbers are never issued with area 000, 666, or 900-999, group 00, or serial 0000
+fn is_possible_ssn(ssn: &str) -> bool {
+    let mut parts = ssn.split('-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(area), Some(group), Some(serial)) => {
+            area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+        },
+        _ => false
+    }
+}
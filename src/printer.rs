@@ -0,0 +1,54 @@
+//! What receives the matches as a notebook is searched
+//!
+//! Everything that does something with the matches goes through the [`Printer`] trait, so that
+//! `search_notebook` does not need to know what is done with them. The jrep program's output
+//! formats are printers, and so is the [`Searcher`](crate::Searcher) that collects them.
+
+use std::io;
+use termcolor::WriteColor;
+use crate::{Cell, MatchedLine, SearchOptions};
+
+/// Where in the notebook a match was found, as passed to [`Printer::print_match`]. The match
+/// itself (line, positions, etc.) is described by the [`MatchedLine`].
+pub struct MatchContext<'a> {
+    /// The notebook, named as it was found (see [`crate::search_notebook`])
+    pub filename: &'a std::ffi::OsString,
+    /// The cell the match is in
+    pub cell: &'a Cell,
+    /// The index of the cell, or `None` for the notebook metadata (in which case `cell`
+    /// is a placeholder with the "notebook" cell type)
+    pub icell: Option<usize>,
+    /// What part of the cell the match is in: "source", "metadata", or an output, e.g.
+    /// "output/stdout", "output/stderr", "output/error", "output/text", or "output/data"
+    pub piece: &'a str,
+    /// The notebook's kernel language, if known
+    #[cfg_attr(not(feature = "syntect"), allow(dead_code))]
+    pub language: Option<&'a str>,
+    /// The nearest markdown heading before the match, if --show-section was given
+    pub section: Option<&'a str>
+}
+
+/// Receives the matches as a notebook is searched. jrep's own output formats are printers, and
+/// implementing this trait is how a program using jrep as a library handles each match as it is
+/// found, without copying it out of the notebook (see [`crate::search_notebook`]).
+///
+/// Only `print_match` and `writer` have to be implemented.
+pub trait Printer {
+    /// Called for each match, in the order they are found.
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, opts: &SearchOptions) -> io::Result<()>;
+
+    /// Called after each file has been searched, with the number of matches in it.
+    fn end_file(&mut self, _filename: &std::ffi::OsString, _n_matches: usize, _opts: &SearchOptions) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once after all files have been searched. Printers must flush their output here.
+    fn finish(&mut self, _opts: &SearchOptions) -> io::Result<()> {
+        self.writer().flush()
+    }
+
+    /// Where this printer writes to, for anything else that belongs with the results (e.g.
+    /// the totals of --stats). A printer that does not write anything can return a
+    /// `termcolor::NoColor` wrapping `std::io::sink()`.
+    fn writer(&mut self) -> &mut dyn WriteColor;
+}
@@ -0,0 +1,141 @@
+//! Searching the notebooks on another computer over SSH (--remote)
+//!
+//! If jrep is installed on the remote host, the whole command is run there instead, with the
+//! same arguments, so that only the results cross the network. Otherwise (or with
+//! --search-locally) the notebooks are listed with `find` on the remote host and each one is
+//! read with `cat` when it is searched, just like a notebook piped to stdin. Every `ssh`
+//! command shares one connection, so only the first has to log in.
+//!
+//! The remote host must have a POSIX shell. The `ssh` program can be replaced by setting the
+//! JREP_SSH environment variable, e.g. to the path of another client.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use crate::{FoundNotebook, RunErr, SearchOptions, Source};
+
+/// A directory or notebook on another computer, given to --remote as `[USER@]HOST:PATH`
+#[derive(Debug, Clone)]
+pub struct RemoteTarget {
+    host: String,
+    path: String
+}
+
+/// Where a notebook on the --remote host is
+#[derive(Debug)]
+pub struct RemoteFile {
+    host: String,
+    path: String
+}
+
+impl RemoteTarget {
+        pub(crate) fn parse(spec: &str) -> Result<Self, RunErr> {
+        match spec.split_once(':') {
+            Some((host, path)) if !host.is_empty() => {
+                // Like scp, an empty path is the home directory
+                let path = if path.is_empty() { "." } else { path };
+                Ok(Self{host: host.to_string(), path: path.to_string()})
+            },
+            _ => Err(RunErr{msg: format!("The value for '--remote' must have the form [USER@]HOST:PATH, not '{}'", spec)})
+        }
+    }
+
+    /// Whether jrep is installed (and on the PATH) on the remote host
+        pub fn has_jrep(&self) -> bool {
+        ssh(&self.host).arg("command -v jrep").stdout(Stdio::null()).stderr(Stdio::null())
+            .status().is_ok_and(|s| s.success())
+    }
+
+    /// The notebooks at the target, named "HOST:PATH", chosen as if it were a local directory
+    /// (or notebook). Ignore files are not read.
+        pub(crate) fn list_notebooks(&self, opts: &SearchOptions) -> Result<Vec<FoundNotebook>, RunErr> {
+        let mut find = String::from("find");
+        if !opts.no_follow {
+            find.push_str(" -L");
+        }
+        find.push(' ');
+        find.push_str(&quote(&self.path));
+        let max_depth = opts.max_depth.or(if opts.recursive { None } else { Some(1) });
+        if let Some(d) = max_depth {
+            find.push_str(&format!(" -maxdepth {}", d));
+        }
+        if opts.one_file_system {
+            find.push_str(" -xdev");
+        }
+        let names: Vec<String> = opts.extensions.iter().map(|ext| format!("-iname {}", quote(&format!("*.{}", ext)))).collect();
+        find.push_str(&format!(" -type f \\( {} \\) -print0", names.join(" -o ")));
+
+        let output = ssh(&self.host).arg(&find).stderr(Stdio::inherit()).output()
+            .map_err(|e| RunErr{msg: format!("Could not run ssh: {}", e)})?;
+        // find also fails if only some directories could not be read, so its output is still used
+        if output.stdout.is_empty() && !output.status.success() {
+            return Err(RunErr{msg: format!("Could not list the notebooks in {}:{}", self.host, self.path)});
+        }
+        let mut found: Vec<FoundNotebook> = output.stdout.split(|&b| b == b'\0')
+            .filter(|p| !p.is_empty())
+            .map(|p| String::from_utf8_lossy(p).into_owned())
+            .filter(|p| {
+                // As for a directory, the path within the target is what the filters apply to
+                let rel_path = Path::new(p).strip_prefix(&self.path).ok().filter(|r| !r.as_os_str().is_empty())
+                    .or_else(|| Path::new(p).file_name().map(Path::new))
+                    .unwrap_or(Path::new(p));
+                crate::walk::keeps_member(rel_path, opts)
+            })
+            .map(|p| FoundNotebook{
+                name: OsString::from(format!("{}:{}", self.host, p)),
+                source: Source::Remote(RemoteFile{host: self.host.clone(), path: p})
+            })
+            .collect();
+        found.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(found)
+    }
+
+    /// Run jrep on the remote host with `args`, which should be the arguments this jrep was
+    /// given less --remote, searching the target there. Returns its exit code.
+    pub fn run_jrep(&self, mut args: Vec<OsString>) -> exitcode::ExitCode {
+        if !args.iter().any(|a| a == "--") {
+            args.push(OsString::from("--"));
+        }
+        args.push(OsString::from(&self.path));
+
+        let command = std::iter::once(String::from("jrep"))
+            .chain(args.iter().map(|a| quote(&a.to_string_lossy())))
+            .collect::<Vec<_>>().join(" ");
+        match ssh(&self.host).arg(command).status() {
+            Ok(status) => status.code().unwrap_or(exitcode::SOFTWARE),
+            Err(e) => {
+                eprintln!("Could not run ssh: {}", e);
+                exitcode::UNAVAILABLE
+            }
+        }
+    }
+}
+
+/// The text of a notebook on the --remote host
+pub fn read_file(file: &RemoteFile) -> io::Result<String> {
+    let output = ssh(&file.host).arg(format!("cat {}", quote(&file.path))).output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(message.trim().to_string()));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// An `ssh` command to run a command on `host`, sharing a connection with the others
+fn ssh(host: &str) -> Command {
+    let program = std::env::var_os("JREP_SSH").filter(|p| !p.is_empty()).unwrap_or_else(|| OsString::from("ssh"));
+    let mut command = Command::new(program);
+    // Reading one notebook at a time would otherwise log in once per notebook. Windows'
+    // OpenSSH cannot share connections.
+    #[cfg(unix)]
+    command.args(["-o", "ControlMaster=auto", "-o", "ControlPersist=60"])
+        .arg("-o").arg(format!("ControlPath={}", std::env::temp_dir().join("jrep-ssh-%C").display()));
+    command.arg("--").arg(host).stdin(Stdio::null());
+    command
+}
+
+/// `s` quoted for a POSIX shell
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
@@ -0,0 +1,210 @@
+//! The `--replace` and `--redact` modes, which edit notebooks instead of just searching them
+//!
+//! Every match in the source of the searched cells and in the metadata searched with
+//! `--search-metadata` or `--search-notebook-metadata` (and, with `--replace-outputs` or
+//! `--redact`, in their searched outputs, including errors) is replaced, and the notebook is
+//! written with only those strings changed (see `rewrite`): back to the same file for
+//! `--replace`, or as a copy in another directory for `--redact`. Replacement works line by
+//! line, just as searching does, so a match cannot span lines. The matches are also found the
+//! same way, i.e. after normalizing with `--normalize` and removing escape sequences from
+//! outputs, and the parts of the original text they cover are what is replaced. Output data
+//! that is searched as text extracted from it (JSON and SVG) has each string in it replaced
+//! instead, and metadata values that are not strings become strings if they match.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use crate::rewrite;
+use crate::{Notebook, RunErr, SearchOptions};
+
+/// What to put in place of each match
+pub struct Replacement<'t> {
+    pub template: &'t str,
+    /// Whether `$1`, `${name}`, etc. in the template refer to capture groups (if false, the
+    /// template is used as it is)
+    pub expand: bool,
+    /// Whether text outputs are changed as well as cell source
+    pub outputs: bool
+}
+
+/// Where the copy of `filename` goes in `out_dir`: the same relative path, so that notebooks
+/// from different directories do not collide. For absolute paths the root is left off, and
+/// "." and ".." are left out so that the copy cannot end up outside `out_dir`.
+pub fn copy_path(out_dir: &Path, filename: &Path) -> PathBuf {
+    let relative: PathBuf = filename.components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part),
+            _ => None
+        })
+        .collect();
+    out_dir.join(relative)
+}
+
+/// Apply `rep` to the notebook text `data` (already parsed into `nb`). Returns the new text
+/// and the number of matches replaced.
+pub fn replace_in_notebook(data: &str, nb: &Notebook, rep: &Replacement, opts: &SearchOptions) -> Result<(String, usize), RunErr> {
+    let cell_indices = match &opts.cell_range {
+        Some(range) => range.indices(nb.cells.len()),
+        None => 0..nb.cells.len()
+    };
+    // The metadata values that are searched, by their JSON Pointers (see `metadata_lines`)
+    let mut metadata_pointers = HashSet::new();
+    if opts.search_notebook_metadata {
+        let lines = crate::search::metadata_lines(&nb.metadata, &opts.metadata_keys);
+        metadata_pointers.extend(lines.into_iter().map(|line| format!("/metadata{}", line.pointer)));
+    }
+    if opts.search_metadata {
+        for (icell, cell) in nb.cells.iter().enumerate() {
+            if cell_indices.contains(&icell) && opts.searches_cell(cell) {
+                let lines = crate::search::metadata_lines(&cell.metadata, &opts.metadata_keys);
+                metadata_pointers.extend(lines.into_iter().map(|line| format!("/cells/{}/metadata{}", icell, line.pointer)));
+            }
+        }
+    }
+    let replaced = |pointer: &str| -> bool {
+        if metadata_pointers.contains(pointer) {
+            return true;
+        }
+        let tokens = rewrite::pointer_tokens(pointer);
+        let icell = match tokens.get(1).and_then(|i| i.parse::<usize>().ok()) {
+            Some(i) if tokens[0] == "cells" => i,
+            _ => return false
+        };
+        let cell = match nb.cells.get(icell) {
+            Some(cell) if cell_indices.contains(&icell) && opts.searches_cell(cell) => cell,
+            _ => return false
+        };
+        match &tokens[2..] {
+            [key] | [key, _] if key == "source" => opts.include_source,
+            [key, k, rest @ ..] if key == "outputs" && rep.outputs => {
+                let outp = match k.parse::<usize>().ok().and_then(|k| cell.outputs.as_ref()?.get(k)) {
+                    Some(outp) => outp,
+                    None => return false
+                };
+                if !opts.output_kinds.is_empty() && !opts.output_kinds.contains(&outp.output_type) {
+                    return false;
+                }
+                match rest {
+                    [text] | [text, _] if text == "text" => {
+                        outp.name.iter().all(|name| opts.include_streams.contains(name))
+                    },
+                    // Anywhere in the data, since JSON data is searched as a whole
+                    [data, mime, ..] if data == "data" => opts.include_output_types.contains(mime),
+                    [field, ..] if outp.output_type == "error" && ["ename", "evalue", "traceback"].contains(&field.as_str()) => {
+                        opts.include_output_types.iter().any(|t| t == "error")
+                    },
+                    _ => false
+                }
+            },
+            _ => false
+        }
+    };
+
+    // Outputs are searched without their escape sequences (unless --no-strip-ansi), so are
+    // matched the same way here
+    let in_outputs = |pointer: &str| pointer.starts_with("/cells/") && pointer.split('/').nth(3) == Some("outputs");
+    let source_spans = rewrite::value_spans(data, &|pointer| replaced(pointer) && !in_outputs(pointer))?;
+    let output_spans = rewrite::value_spans(data, &|pointer| replaced(pointer) && in_outputs(pointer))?;
+
+    let mut edits = Vec::new();
+    let mut n_replaced = 0;
+    let spans = source_spans.into_iter().map(|range| (range, false))
+        .chain(output_spans.into_iter().map(|range| (range, opts.strip_ansi)));
+    for (range, strip_ansi) in spans {
+        let raw = &data[range.clone()];
+        if raw.starts_with(['[', '{']) {
+            // The list or object that holds the values, rather than one of them
+            continue;
+        }
+        // Numbers, booleans, and nulls in metadata are searched as they are written
+        let text = match serde_json::from_str(raw)? {
+            serde_json::Value::String(s) => s,
+            _ => String::from(raw)
+        };
+        let (new_text, n) = opts.replace_all(&text, rep.template, rep.expand, strip_ansi);
+        if let Cow::Owned(new_text) = new_text {
+            edits.push((range, serde_json::to_string(&new_text)?));
+            n_replaced += n;
+        }
+    }
+    Ok((rewrite::apply_edits(data, edits), n_replaced))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "id": "a1",
+   "metadata": {"tags": ["uses-numpy"], "owner": {"host": "numpy.example.com", "port": 8080}},
+   "outputs": [
+    {
+     "ename": "ModuleNotFoundError",
+     "evalue": "No module named 'numpy'",
+     "output_type": "error",
+     "traceback": ["\u001b[0;31mModuleNotFoundError\u001b[0m: No module named 'numpy'\n  at import numpy"]
+    },
+    {
+     "data": {"application/json": {"module": "numpy", "nested": ["numpy", 1]}, "text/plain": ["'numpy'"]},
+     "execution_count": 1,
+     "metadata": {},
+     "output_type": "execute_result"
+    }
+   ],
+   "source": ["import numpy\n"]
+  }
+ ],
+ "metadata": {"title": "numpy demo"},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"#;
+
+    fn redact(args: &[&str]) -> (String, usize) {
+        let opts = SearchOptions::from_args(args.iter().copied()).unwrap();
+        let nb = crate::parse_notebook(NOTEBOOK).unwrap();
+        let rep = Replacement{template: "[REDACTED]", expand: false, outputs: true};
+        replace_in_notebook(NOTEBOOK, &nb, &rep, &opts).unwrap()
+    }
+
+    #[test]
+    fn redacts_errors() {
+        let (redacted, n) = redact(&["-X", "-O", "error", "numpy"]);
+        // The message and traceback, but not the source or the output data, which are not searched
+        assert_eq!(n, 3);
+        assert!(redacted.contains(r#""evalue": "No module named '[REDACTED]'""#));
+        assert!(redacted.contains(r#"No module named '[REDACTED]'\n  at import [REDACTED]"]"#));
+        assert!(redacted.contains(r#""source": ["import numpy\n"]"#));
+        assert!(redacted.contains(r#""text/plain": ["'numpy'"]"#));
+    }
+
+    #[test]
+    fn redacts_json_data() {
+        let (redacted, n) = redact(&["-X", "-O", "application/json", "numpy"]);
+        assert_eq!(n, 2);
+        assert!(redacted.contains(r#"{"module": "[REDACTED]", "nested": ["[REDACTED]", 1]}"#));
+    }
+
+    #[test]
+    fn redacts_searched_metadata() {
+        let (redacted, _) = redact(&["-X", "--no-include-output", "--search-metadata", "--search-notebook-metadata", "numpy|8080"]);
+        assert!(redacted.contains(r#""tags": ["uses-[REDACTED]"]"#));
+        assert!(redacted.contains(r#"{"host": "[REDACTED].example.com", "port": "[REDACTED]"}"#));
+        assert!(redacted.contains(r#""title": "[REDACTED] demo""#));
+
+        // Only under the keys given
+        let (redacted, _) = redact(&["-X", "--no-include-output", "--metadata-key", "owner", "numpy"]);
+        assert!(redacted.contains(r#""tags": ["uses-numpy"]"#));
+        assert!(redacted.contains(r#""host": "[REDACTED].example.com""#));
+    }
+
+    #[test]
+    fn redacts_everything_a_search_finds() {
+        let args = ["-O", "error", "--all-text-outputs", "-O", "application/json", "--search-metadata", "--search-notebook-metadata", "numpy"];
+        let (redacted, _) = redact(&args);
+        assert!(!redacted.contains("numpy"));
+    }
+}
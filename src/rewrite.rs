@@ -0,0 +1,226 @@
+//! Changing notebook files without disturbing the rest of their JSON
+//!
+//! Parsing a notebook and writing it back out would change its formatting (indentation,
+//! key order, escapes) and so make for a noisy diff of every file touched. Instead, this
+//! finds the byte range of each value in the original text, so that only the values that
+//! actually change are replaced and every other byte of the file stays as it was.
+
+use std::ops::Range;
+use std::path::Path;
+use std::{fs, io};
+use crate::RunErr;
+
+/// Find the byte ranges of the values in the JSON `text` whose JSON Pointers pass `wanted`,
+/// in the order they appear. The text is assumed to be valid JSON (it should already have
+/// been parsed), but truncated text is still an error.
+pub fn value_spans(text: &str, wanted: &dyn Fn(&str) -> bool) -> Result<Vec<Range<usize>>, RunErr> {
+    let mut scanner = Scanner{text, pos: 0, wanted, spans: Vec::new()};
+    scanner.value(&mut String::new())?;
+    Ok(scanner.spans)
+}
+
+/// The reference tokens of a JSON Pointer, with "~1" and "~0" decoded
+pub fn pointer_tokens(pointer: &str) -> Vec<String> {
+    pointer.split('/').skip(1).map(|t| t.replace("~1", "/").replace("~0", "~")).collect()
+}
+
+/// Replace each range of `text` with its new value. The ranges must not overlap.
+pub fn apply_edits(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, new_value) in edits {
+        out.push_str(&text[last..range.start]);
+        out.push_str(&new_value);
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Write `contents` to a temporary file next to `path`, then move it over `path`, so that an
+/// error part way through cannot leave a half-written notebook
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".jrep-tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, contents)?;
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(&tmp_path, meta.permissions())?;
+    }
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+    wanted: &'a dyn Fn(&str) -> bool,
+    spans: Vec<Range<usize>>
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Result<u8, RunErr> {
+        self.text.as_bytes().get(self.pos).copied().ok_or_else(|| RunErr::from("Unexpected end of the notebook JSON"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.text.as_bytes().get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek()? {
+            b'{' => self.object(pointer)?,
+            b'[' => self.array(pointer)?,
+            b'"' => self.string()?,
+            _ => {
+                // A number, true, false, or null
+                while !matches!(self.peek()?, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r') {
+                    self.pos += 1;
+                }
+            }
+        }
+        if (self.wanted)(pointer) {
+            self.spans.push(start..self.pos);
+        }
+        Ok(())
+    }
+
+    fn object(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key_start = self.pos;
+            self.string()?;
+            let key: String = serde_json::from_str(&self.text[key_start..self.pos])?;
+            self.skip_whitespace();
+            if self.peek()? != b':' {
+                return Err(RunErr::from("Expected ':' after an object key in the notebook JSON"));
+            }
+            self.pos += 1;
+
+            let parent_len = pointer.len();
+            pointer.push('/');
+            pointer.push_str(&crate::escape_pointer_token(&key));
+            self.value(pointer)?;
+            pointer.truncate(parent_len);
+
+            if self.end_of_container(b'}')? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn array(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek()? == b']' {
+            self.pos += 1;
+            return Ok(());
+        }
+        for i in 0.. {
+            let parent_len = pointer.len();
+            pointer.push('/');
+            pointer.push_str(&i.to_string());
+            self.value(pointer)?;
+            pointer.truncate(parent_len);
+
+            if self.end_of_container(b']')? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// After an element: true if `close` ends the container, false if a comma means there is more
+    fn end_of_container(&mut self, close: u8) -> Result<bool, RunErr> {
+        self.skip_whitespace();
+        let c = self.peek()?;
+        self.pos += 1;
+        match c {
+            b',' => Ok(false),
+            c if c == close => Ok(true),
+            _ => Err(RunErr::from("Expected ',' or the end of a list or object in the notebook JSON"))
+        }
+    }
+
+    fn string(&mut self) -> Result<(), RunErr> {
+        if self.peek()? != b'"' {
+            return Err(RunErr::from("Expected a string in the notebook JSON"));
+        }
+        self.pos += 1;
+        loop {
+            match self.peek()? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Ok(());
+                },
+                _ => self.pos += 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans_of<'a>(text: &'a str, wanted: &dyn Fn(&str) -> bool) -> Vec<&'a str> {
+        value_spans(text, wanted).unwrap().into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn escaped_strings() {
+        let text = r#"{"cells": [{"source": ["say \"hi\"\n", "C:\\path\\", "tab\there"], "k\"ey": "}]"}]}"#;
+        let lines = spans_of(text, &|p| p.starts_with("/cells/0/source/"));
+        assert_eq!(lines, [r#""say \"hi\"\n""#, r#""C:\\path\\""#, r#""tab\there""#]);
+        // An escaped quote or bracket in a key or value does not end anything early
+        assert_eq!(spans_of(text, &|p| p == "/cells/0/k\"ey"), [r#""}]""#]);
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        let text = "{\"source\": [\"caf\\u00e9 \\ud83d\\ude00\", \"na\u{ef}ve \u{1f600}\"]}";
+        let lines = spans_of(text, &|p| p.starts_with("/source/"));
+        assert_eq!(lines, ["\"caf\\u00e9 \\ud83d\\ude00\"", "\"na\u{ef}ve \u{1f600}\""]);
+        let decoded: Vec<String> = lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(decoded, ["caf\u{e9} \u{1f600}", "na\u{ef}ve \u{1f600}"]);
+    }
+
+    #[test]
+    fn source_as_string_or_list() {
+        let as_list = r#"{"cells": [{"source": ["a = 1\n", "b = 2"]}]}"#;
+        assert_eq!(spans_of(as_list, &|p| p.starts_with("/cells/0/source")), [r#""a = 1\n""#, r#""b = 2""#, r#"["a = 1\n", "b = 2"]"#]);
+        let as_string = r#"{"cells": [{"source": "a = 1\nb = 2"}]}"#;
+        assert_eq!(spans_of(as_string, &|p| p.starts_with("/cells/0/source")), [r#""a = 1\nb = 2""#]);
+    }
+
+    #[test]
+    fn edits_leave_the_rest_unchanged() {
+        let text = "{\n  \"cells\" : [ {\"source\":[\"x = 1\\n\",  \"y = \\\"\u{e9}\\\"\"],\n\t\"id\": \"\\u0041\"} ],\r\n \"n\": 1.50e2 }";
+        let spans = value_spans(text, &|p| p.starts_with("/cells/0/source/")).unwrap();
+        assert_eq!(spans.len(), 2);
+        let edits = vec![(spans[1].clone(), String::from("\"z\"")), (spans[0].clone(), String::from("\"w = 2\\n\""))];
+        let edited = apply_edits(text, edits);
+        let expected = text.replacen("\"x = 1\\n\"", "\"w = 2\\n\"", 1).replacen("\"y = \\\"\u{e9}\\\"\"", "\"z\"", 1);
+        assert_eq!(edited, expected);
+        assert_eq!(&edited[..spans[0].start], &text[..spans[0].start]);
+        assert_eq!(&edited[edited.len() - (text.len() - spans[1].end)..], &text[spans[1].end..]);
+    }
+
+    #[test]
+    fn truncated_text_is_an_error() {
+        assert!(value_spans(r#"{"source": ["a", "b"#, &|_| true).is_err());
+    }
+}
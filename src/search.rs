@@ -0,0 +1,554 @@
+//! Searching a notebook and handing each match to a printer
+
+use std::fs;
+use std::time::Instant;
+use serde::Serialize;
+use crate::notebook::{convert_output_nontext_data, convert_output_text_data, has_converted_data, parse_notebook_for_search};
+use crate::printer::{MatchContext, Printer};
+use crate::spans::FileLines;
+use crate::{escape_pointer_token, is_text, jupytext, zeppelin, Cell, FoundNotebook, Notebook, Output, RunErr, SearchOptions, Source};
+
+/// One line that matched, as passed to a [`Printer`] along with the [`MatchContext`] saying where it is
+pub struct MatchedLine<'a> {
+    /// The whole line, including the newline at its end if it has one
+    pub line: &'a str,
+    /// The 0-based index of the line in the cell source or output
+    pub line_number: usize,
+    /// The byte ranges of the line that matched
+    pub match_positions: Vec<(usize, usize)>,
+    /// False for a match in output data that is not text (e.g. an image), where `line` is the
+    /// whole encoded data and there are no match positions
+    pub is_text: bool,
+    /// JSON Pointer (RFC 6901) to the matched string in the notebook file, e.g. "/cells/3/source/0".
+    /// For text saved as one string rather than a list of lines, e.g. "/cells/3/source", in which
+    /// case `line_number` says which line of the string matched.
+    pub pointer: String,
+    /// Line in the notebook file itself where the matched string is, if requested
+    pub file_line: Option<usize>,
+    /// The MIME type of the output data the line is in, e.g. "text/plain", if it is in output data
+    pub mime_type: Option<&'a str>
+}
+
+impl MatchedLine<'_> {
+    /// The 1-based byte column of the first match on this line, if there is one.
+    /// (Inverted matches and non-text data have no match positions.)
+    pub fn first_column(&self) -> Option<usize> {
+        self.match_positions.iter().map(|&(start, _stop)| start + 1).min()
+    }
+}
+
+impl Clone for MatchedLine<'_> {
+    fn clone(&self) -> Self{
+        Self{
+            line: self.line,
+            line_number: self.line_number,
+            match_positions: self.match_positions.to_vec(),
+            is_text: self.is_text,
+            pointer: self.pointer.clone(),
+            file_line: self.file_line,
+            mime_type: self.mime_type
+        }
+    }
+}
+
+/// What became of a notebook that could be read
+pub enum Searched<T> {
+    /// It was searched; this is the number of matches (or, from the worker threads of
+    /// --threads, the recorded matches)
+    Done(T),
+    /// It does not pass the notebook-level filters (e.g. --lang)
+    Filtered,
+    /// It is larger than --max-filesize, so it was not even read
+    TooLarge,
+    /// Reading and searching it took longer than --timeout-per-file, so it was abandoned
+    TimedOut
+}
+
+impl<T> Searched<T> {
+    /// Apply `f` to the result of a notebook that was searched
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Searched<U> {
+        match self {
+            Searched::Done(x) => Searched::Done(f(x)),
+            Searched::Filtered => Searched::Filtered,
+            Searched::TooLarge => Searched::TooLarge,
+            Searched::TimedOut => Searched::TimedOut
+        }
+    }
+}
+
+/// Search one notebook, passing each match to `printer` as it is found and returning the
+/// number of matches, unless it was skipped. The matches are given the notebook's name.
+///
+/// With --timeout-per-file, the time is checked after reading the notebook and before parsing
+/// it, then between lines as it is searched, and the search stops as soon as it has run out; the
+/// caller must then ignore the matches printed so far. Parsing and matching a single line are
+/// never interrupted, so a notebook can still run over by that much.
+pub fn search_notebook(notebook: &FoundNotebook, opts: &SearchOptions, printer: &mut dyn Printer) -> Result<Searched<usize>, RunErr> {
+    let deadline = opts.timeout_per_file.map(|timeout| Instant::now() + timeout);
+    let filename = &notebook.name;
+    // A notebook piped in, in an archive, in cloud storage, on a --remote host, or at a --rev
+    // is neither in the index nor cached, and has no file size to check
+    let path = match &notebook.source {
+        Source::Local(path) => path,
+        _ => {
+            let data = notebook.read()?;
+            return search_text_until(filename, &data, deadline, opts, printer);
+        }
+    };
+    if let Some(max_size) = opts.max_filesize {
+        if fs::metadata(path)?.len() > max_size {
+            return Ok(Searched::TooLarge);
+        }
+    }
+    let indexed = opts.index.as_ref().and_then(|index| index.notebook(path, opts));
+    let cached = match indexed {
+        Some(nb) => Some(Ok(nb)),
+        None => opts.cache.as_ref().and_then(|cache| cache.notebook(filename, path, opts))
+    };
+    match cached {
+        Some(nb) => search_parsed_notebook(filename, None, nb?, deadline, opts, printer),
+        None => {
+            let data = notebook.read()?;
+            if past(deadline) {
+                return Ok(Searched::TimedOut);
+            }
+            let nb = parse_notebook_for_search(&data, filename, opts)?;
+            search_parsed_notebook(filename, Some(&data), nb, deadline, opts, printer)
+        }
+    }
+}
+
+/// Search a notebook that is already in memory, given the JSON text of the notebook file, like
+/// `search_notebook` would search it if it were the file `filename`. The file is never read;
+/// `filename` is only what is passed on to the printer.
+pub fn search_notebook_text(filename: &std::ffi::OsString, data: &str, opts: &SearchOptions, printer: &mut dyn Printer) -> Result<Searched<usize>, RunErr> {
+    let deadline = opts.timeout_per_file.map(|timeout| Instant::now() + timeout);
+    search_text_until(filename, data, deadline, opts, printer)
+}
+
+/// `search_notebook_text`, giving up at `deadline`, which may have been set before the notebook was read
+fn search_text_until(filename: &std::ffi::OsString, data: &str, deadline: Option<Instant>, opts: &SearchOptions, printer: &mut dyn Printer) -> Result<Searched<usize>, RunErr> {
+    if opts.max_filesize.is_some_and(|max_size| data.len() as u64 > max_size) {
+        return Ok(Searched::TooLarge);
+    }
+    if past(deadline) {
+        return Ok(Searched::TimedOut);
+    }
+    let nb = parse_notebook_for_search(data, filename, opts)?;
+    search_parsed_notebook(filename, Some(data), nb, deadline, opts, printer)
+}
+
+/// Whether the --timeout-per-file `deadline` has passed
+fn past(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| Instant::now() > d)
+}
+
+/// The search itself, once the notebook has been parsed. `data` is the text of the file, if
+/// it was read; it is needed for --file-line.
+fn search_parsed_notebook(
+    filename: &std::ffi::OsString, data: Option<&str>, mut nb: Notebook, deadline: Option<Instant>,
+    opts: &SearchOptions, printer: &mut dyn Printer
+) -> Result<Searched<usize>, RunErr> {
+    let timed_out = || past(deadline);
+    if opts.strip_ansi {
+        nb.cells.iter_mut().flat_map(|c| c.outputs.iter_mut().flatten()).for_each(Output::strip_ansi);
+    }
+    if !opts.include_output_types.is_empty() {
+        nb.cells.iter_mut().flat_map(|c| c.outputs.iter_mut().flatten()).for_each(|o| o.convert_data(opts));
+    }
+    if !opts.languages.is_empty() && !nb.has_language(&opts.languages) {
+        return Ok(Searched::Filtered);
+    }
+    if timed_out() {
+        return Ok(Searched::TimedOut);
+    }
+    // The index and cache are never used when this is needed (see `index::has_what_search_needs`),
+    // so `data` is there
+    let file_lines = match data {
+        Some(data) if opts.needs_file_lines() => match jupytext::Format::of(filename) {
+            Some(format) => Some(jupytext::file_lines(data, format)),
+            // The cells of a Zeppelin note are not where the pointers say
+            None if zeppelin::is_note(filename) => None,
+            None => Some(FileLines::from_json(data)?)
+        },
+        _ => None
+    };
+    let find_file_line = |m: &mut MatchedLine| {
+        if let Some(fl) = &file_lines {
+            m.file_line = fl.line_of(&m.pointer);
+        }
+    };
+    let mut n_matches = 0;
+
+    if opts.search_notebook_metadata {
+        let lines = metadata_lines(&nb.metadata, &opts.metadata_keys);
+        let matches = search_metadata_lines(&lines, "/metadata", opts);
+        // Matches outside any cell still need a cell to describe them; this one has no ID, tags, etc.
+        let nb_cell = Cell{cell_type: String::from("notebook"), ..Cell::default()};
+        let ctx = MatchContext{filename, cell: &nb_cell, icell: None, piece: "metadata", language: nb.language(), section: None};
+        for mut m in matches {
+            find_file_line(&mut m);
+            printer.print_match(&ctx, &m, opts)?;
+            n_matches += 1;
+        }
+    }
+
+    // The most recent markdown heading before the current cell, for --show-section
+    let mut section: Option<&str> = None;
+    let cell_indices = match &opts.cell_range {
+        Some(range) => range.indices(nb.cells.len()),
+        None => 0..nb.cells.len()
+    };
+
+    for (icell, cell) in nb.cells.iter().enumerate() {
+        if timed_out() {
+            return Ok(Searched::TimedOut);
+        }
+        // Headings must be tracked even in cells that are not searched
+        let headings = if opts.show_section && cell.cell_type == "markdown" {
+            markdown_headings(&cell.source)
+        }else{
+            Vec::new()
+        };
+        let cell_section = section;
+        if let Some(&(_, heading)) = headings.last() {
+            section = Some(heading);
+        }
+        let section_at = |iline: usize| {
+            headings.iter().rev().find(|&&(i, _)| i <= iline).map(|&(_, h)| h).or(cell_section)
+        };
+
+        if !cell_indices.contains(&icell) || !opts.searches_cell(cell) {
+            continue;
+        }
+
+        if opts.include_source {
+            let lines = build_src_ref(&cell.source);
+            let matches = search_saved_lines(lines, &format!("/cells/{}/source", icell), cell.source_is_string, deadline, opts);
+            for mut m in matches {
+                find_file_line(&mut m);
+                let ctx = MatchContext{filename, cell, icell: Some(icell), piece: "source", language: nb.language(), section: section_at(m.line_number)};
+                printer.print_match(&ctx, &m, opts)?;
+                n_matches += 1;
+            }
+        }
+
+        if opts.search_metadata {
+            let lines = metadata_lines(&cell.metadata, &opts.metadata_keys);
+            let matches = search_metadata_lines(&lines, &format!("/cells/{}/metadata", icell), opts);
+            let ctx = MatchContext{filename, cell, icell: Some(icell), piece: "metadata", language: nb.language(), section: cell_section};
+            for mut m in matches {
+                find_file_line(&mut m);
+                printer.print_match(&ctx, &m, opts)?;
+                n_matches += 1;
+            }
+        }
+
+        if let Some(outputs) = &cell.outputs {
+            for (ioutp, outp) in outputs.iter().enumerate() {
+                if timed_out() {
+                    return Ok(Searched::TimedOut);
+                }
+                if !opts.output_kinds.is_empty() && !opts.output_kinds.contains(&outp.output_type) {
+                    continue;
+                }
+                if opts.errors_only {
+                    if let Some((summary, name_len)) = error_summary(outp) {
+                        let mut m = MatchedLine{
+                            line: &summary,
+                            line_number: 0,
+                            match_positions: vec![(0, name_len)],
+                            is_text: true,
+                            pointer: format!("/cells/{}/outputs/{}", icell, ioutp),
+                            file_line: None,
+                            mime_type: None
+                        };
+                        find_file_line(&mut m);
+                        let ctx = MatchContext{filename, cell, icell: Some(icell), piece: "output/error", language: nb.language(), section: cell_section};
+                        printer.print_match(&ctx, &m, opts)?;
+                        n_matches += 1;
+                    }
+                    continue;
+                }
+
+                let matches = search_output(outp, &format!("/cells/{}/outputs/{}", icell, ioutp), deadline, opts)?;
+                // TODO: gracefully handle unexpected notebook format?
+                for mut m in matches {
+                    find_file_line(&mut m);
+                    let piece = if outp.output_type == "error" {
+                        "output/error"
+                    }else if outp.output_type == "stream" && outp.name.as_deref() == Some("stdout") {
+                        "output/stdout"
+                    }else if outp.output_type == "stream" && outp.name.as_deref() == Some("stderr") {
+                        "output/stderr"
+                    }else if m.is_text {
+                        "output/text"
+                    }else{
+                        "output/data"
+                    };
+                    let ctx = MatchContext{filename, cell, icell: Some(icell), piece, language: nb.language(), section: cell_section};
+                    printer.print_match(&ctx, &m, opts)?;
+                    n_matches += 1;
+                }
+            }
+        }
+    }
+
+    // The last lines searched may have been cut short
+    if timed_out() {
+        return Ok(Searched::TimedOut);
+    }
+    Ok(Searched::Done(n_matches))
+}
+
+/// Find the ATX headings (lines starting with 1 to 6 '#' characters) in the source of a
+/// markdown cell, skipping fenced code blocks. Returns the line index and text of each,
+/// with the '#' characters kept so that the heading level is still visible.
+pub fn markdown_headings(source: &[String]) -> Vec<(usize, &str)> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+    for (iline, line) in source.iter().enumerate() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        let level = line.chars().take_while(|&c| c == '#').count();
+        let rest = &line[level..];
+        if !in_fence && (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+            // Closing '#'s are optional decoration, e.g. "## Heading ##"
+            let heading = line.trim_end_matches('#').trim_end();
+            headings.push((iline, if heading.len() > level { heading } else { line }));
+        }
+    }
+    headings
+}
+
+fn build_src_ref(source: &[String]) -> Vec<&str> {
+    let mut v = Vec::with_capacity(source.len());
+    for el in source.iter() {
+        v.push(el.as_ref());
+    }
+    v
+}
+
+
+/// Search each line of `text`, whose JSON Pointers are `pointer` followed by the line's index.
+/// Stops early once `deadline` has passed, in which case the caller must give up on the notebook.
+fn search_text_lines<'a>(text: Vec<&'a str>, pointer: &str, deadline: Option<Instant>, opts: &SearchOptions) -> Vec<MatchedLine<'a>> {
+    let mut matched_lines: Vec<MatchedLine> = Vec::new();
+    for (i, line) in text.iter().enumerate() {
+        if past(deadline) {
+            break;
+        }
+        let inds = opts.find_positions(line);
+        if inds.is_empty() != opts.invert_match {
+            continue;
+        }
+
+        let ml = MatchedLine{
+            line,
+            line_number: i,
+            match_positions: inds,
+            is_text: true,
+            pointer: format!("{}/{}", pointer, i),
+            file_line: None,
+            mime_type: None
+        };
+        matched_lines.push(ml);
+    }
+
+    matched_lines
+}
+
+fn search_nontext_data<'a>(data: &'a str, pointer: &str, opts: &SearchOptions) -> Option<MatchedLine<'a>> {
+    if opts.is_match(data) == opts.invert_match {
+        return None;
+    };
+
+    Some(MatchedLine{line: data, line_number: 0, match_positions: Vec::new(), is_text: false, pointer: String::from(pointer), file_line: None, mime_type: None})
+
+}
+
+
+fn search_output<'a>(outp: &'a Output, pointer: &str, deadline: Option<Instant>, opts: &SearchOptions) -> Result<Vec<MatchedLine<'a>>, RunErr> {
+    let mut matched_lines = Vec::new();
+
+    if let Some(output_data) = &outp.data {
+        for (dtype, val) in output_data.iter(){
+            let n_before = matched_lines.len();
+            let data_pointer = format!("{}/data/{}", pointer, escape_pointer_token(dtype));
+            if !opts.include_output_types.contains(dtype) {
+                // skip
+            }else if is_text(dtype){
+                let lines = convert_output_text_data(val)?;
+                for m in search_text_lines(lines, &data_pointer, deadline, opts) {
+                    matched_lines.push(m);
+                }
+                
+            }else if has_converted_data(dtype) {
+                // This is the text from `Output::convert_data`, whose lines do not exist
+                // in the notebook to point to
+                let text = convert_output_nontext_data(val)?;
+                matched_lines.extend(search_multiline_string(text, &data_pointer, 0, deadline, opts));
+            }else{
+                let data = convert_output_nontext_data(val)?;
+                if let Some(m) = search_nontext_data(data, &data_pointer, opts) {
+                    matched_lines.push(m);
+                }
+            }
+            for m in matched_lines[n_before..].iter_mut() {
+                m.mime_type = Some(dtype);
+            }
+        }
+    }
+
+    let stream_searched = match &outp.name {
+        Some(name) if outp.output_type == "stream" => opts.include_streams.contains(name),
+        _ => true
+    };
+    if let (Some(text_lines), true) = (&outp.text, stream_searched) {
+        // This I think is the best way to do this. outp.text has to be a Vec<String>
+        // because it holds the original instance of the strings read from the JSON file.
+        // I tried making `search_text_lines` take a Vec<AsRef<str>> but didn't see a way
+        // to indicate that the reference would stay valid long enough. This method 
+        // creates refs that have lifetime 'a so we know they are okay to return from 
+        // this function.
+        let ref_lines: Vec<&str> = text_lines.iter().map(|x| x.as_ref()).collect();
+        for m in search_saved_lines(ref_lines, &format!("{}/text", pointer), outp.text_is_string, deadline, opts) {
+            matched_lines.push(m);
+        }
+    }
+
+    if outp.output_type == "error" && opts.include_output_types.iter().any(|t| t == "error") {
+        let fields = [("ename", &outp.ename), ("evalue", &outp.evalue)];
+        for (key, value) in fields.iter() {
+            if let Some(value) = value {
+                matched_lines.extend(search_multiline_string(value, &format!("{}/{}", pointer, key), 0, deadline, opts));
+            }
+        }
+
+        if let Some(frames) = &outp.traceback {
+            // Number the lines across all the frames so that they read as one block of text
+            let mut line_number = 0;
+            for (iframe, frame) in frames.iter().enumerate() {
+                matched_lines.extend(search_multiline_string(frame, &format!("{}/traceback/{}", pointer, iframe), line_number, deadline, opts));
+                line_number += frame.split_inclusive('\n').count();
+            }
+        }
+    }
+
+    Ok(matched_lines)
+}
+
+/// For --errors-only: the "{ename}: {evalue}" line describing an error output, and the length
+/// of the exception name at its start. Only the first line of multi-line messages is kept.
+fn error_summary(outp: &Output) -> Option<(String, usize)> {
+    if outp.output_type != "error" {
+        return None;
+    }
+    let ename = outp.ename.as_deref().unwrap_or("");
+    let summary = match outp.evalue.as_deref().and_then(|v| v.lines().next()) {
+        Some(evalue) if !evalue.is_empty() => format!("{}: {}", ename, evalue),
+        _ => String::from(ename)
+    };
+    Some((summary, ename.len()))
+}
+
+/// Search lines that were saved at `pointer` either as a list, or as one string if `one_string`
+/// is true. JSON Pointers cannot point inside a string, so in that case every match gets
+/// `pointer` itself, and only its line number says where in the string it is.
+fn search_saved_lines<'a>(lines: Vec<&'a str>, pointer: &str, one_string: bool, deadline: Option<Instant>, opts: &SearchOptions) -> Vec<MatchedLine<'a>> {
+    let mut matched_lines = search_text_lines(lines, pointer, deadline, opts);
+    if one_string {
+        for m in matched_lines.iter_mut() {
+            m.pointer = String::from(pointer);
+        }
+    }
+    matched_lines
+}
+
+/// Search each line of a string that may contain newlines. JSON pointers cannot point inside
+/// a string, so every match gets `pointer`; the lines are numbered starting from `first_line`.
+fn search_multiline_string<'a>(text: &'a str, pointer: &str, first_line: usize, deadline: Option<Instant>, opts: &SearchOptions) -> Vec<MatchedLine<'a>> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut matched_lines = search_text_lines(lines, pointer, deadline, opts);
+    for m in matched_lines.iter_mut() {
+        m.line_number += first_line;
+        m.pointer = String::from(pointer);
+    }
+    matched_lines
+}
+
+/// One value in a cell's metadata, as searched by --search-metadata
+pub(crate) struct MetadataLine {
+    // The key and value, e.g. "slideshow.slide_type: slide"
+    text: String,
+    // Where the value starts in `text`; only the value is searched
+    value_start: usize,
+    // JSON Pointer to the value, relative to the cell metadata
+    pub(crate) pointer: String
+}
+
+/// Flatten cell or notebook metadata into one line per value. Keys of nested objects
+/// are joined with dots; the values of an array all get the key of the array (so each
+/// tag is its own "tags: ..." line). If `keys` is not empty, only values under those keys are kept.
+pub(crate) fn metadata_lines<T: Serialize>(metadata: &T, keys: &[String]) -> Vec<MetadataLine> {
+    fn flatten(value: &serde_json::Value, key: &str, pointer: String, keys: &[String], lines: &mut Vec<MetadataLine>) {
+        let value_str = match value {
+            serde_json::Value::Object(map) => {
+                for (k, v) in map.iter() {
+                    let child_key = if key.is_empty() { k.clone() } else { format!("{}.{}", key, k) };
+                    let child_pointer = format!("{}/{}", pointer, k.replace('~', "~0").replace('/', "~1"));
+                    flatten(v, &child_key, child_pointer, keys, lines);
+                }
+                return;
+            },
+            serde_json::Value::Array(arr) => {
+                for (i, v) in arr.iter().enumerate() {
+                    flatten(v, key, format!("{}/{}", pointer, i), keys, lines);
+                }
+                return;
+            },
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string()
+        };
+
+        let wanted = keys.is_empty() || keys.iter().any(|k| key == k || key.starts_with(&format!("{}.", k)));
+        if wanted {
+            lines.push(MetadataLine{text: format!("{}: {}", key, value_str), value_start: key.len() + 2, pointer});
+        }
+    }
+
+    let mut lines = Vec::new();
+    // Serializing the metadata puts the keys we parse back together with the other keys
+    if let Ok(value) = serde_json::to_value(metadata) {
+        flatten(&value, "", String::new(), keys, &mut lines);
+    }
+    lines
+}
+
+/// Like `search_text_lines`, but only the value part of each metadata line is searched.
+fn search_metadata_lines<'a>(lines: &'a [MetadataLine], pointer: &str, opts: &SearchOptions) -> Vec<MatchedLine<'a>> {
+    let mut matched_lines = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let inds: Vec<(usize, usize)> = opts.find_positions(&line.text[line.value_start..])
+            .into_iter()
+            .map(|(start, stop)| (start + line.value_start, stop + line.value_start))
+            .collect();
+        if inds.is_empty() != opts.invert_match {
+            continue;
+        }
+
+        matched_lines.push(MatchedLine{
+            line: &line.text,
+            line_number: i,
+            match_positions: inds,
+            is_text: true,
+            pointer: format!("{}{}", pointer, line.pointer),
+            file_line: None,
+            mime_type: None
+        });
+    }
+    matched_lines
+}
@@ -0,0 +1,136 @@
+//! Searching notebooks as an iterator of matches, for programs that use jrep as a library
+//!
+//! [`search_notebook`](crate::search_notebook) hands each match to a [`Printer`] while the
+//! notebook is still borrowed. A [`Searcher`] instead gives owned [`Match`]es through an
+//! iterator, searching each notebook only once the matches from the ones before it have been
+//! taken, so stopping early (e.g. with `take`) skips the rest of the notebooks entirely.
+
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use termcolor::{NoColor, WriteColor};
+use crate::printer::{MatchContext, Printer};
+use crate::{FoundNotebook, MatchedLine, RunErr, SearchOptions, Searched};
+
+/// One match, with everything about it copied out of the notebook
+#[derive(Debug, Clone)]
+pub struct Match {
+    /// The notebook the match is in
+    pub path: OsString,
+    /// The index of the cell, or `None` for a match in the notebook metadata
+    pub cell: Option<usize>,
+    pub cell_id: Option<String>,
+    pub cell_type: String,
+    pub execution_count: Option<usize>,
+    /// The cell's tags
+    pub tags: Vec<String>,
+    /// Which part of the cell the match is in: "source", "metadata", or the output type
+    pub piece: String,
+    /// The nearest markdown heading before the match, if --show-section was given
+    pub section: Option<String>,
+    /// The whole line, including the newline at its end if it has one
+    pub line: String,
+    /// The 0-based index of the line in the cell source or output
+    pub line_number: usize,
+    /// The 1-based line in the notebook file itself where the matched string is, if the
+    /// options include `--file-lines`
+    pub file_line: Option<usize>,
+    /// The byte ranges of `line` that matched
+    pub match_positions: Vec<(usize, usize)>,
+    /// False for a match in output data that is not text (e.g. an image)
+    pub is_text: bool,
+    /// JSON Pointer (RFC 6901) to the matched string in the notebook file
+    pub pointer: String,
+    /// The MIME type of the output data the line is in, if it is in output data
+    pub mime_type: Option<String>
+}
+
+/// Copies each match into a `Match`
+struct Collector {
+    matches: VecDeque<Match>,
+    sink: NoColor<io::Sink>
+}
+
+impl Printer for Collector {
+    fn print_match(&mut self, ctx: &MatchContext, m: &MatchedLine, _opts: &SearchOptions) -> io::Result<()> {
+        self.matches.push_back(Match{
+            path: ctx.filename.clone(),
+            cell: ctx.icell,
+            cell_id: ctx.cell.id.clone(),
+            cell_type: ctx.cell.cell_type.clone(),
+            execution_count: ctx.cell.execution_count,
+            tags: ctx.cell.metadata.tags.clone(),
+            piece: String::from(ctx.piece),
+            section: ctx.section.map(String::from),
+            line: String::from(m.line),
+            line_number: m.line_number,
+            file_line: m.file_line,
+            match_positions: m.match_positions.clone(),
+            is_text: m.is_text,
+            pointer: m.pointer.clone(),
+            mime_type: m.mime_type.map(String::from)
+        });
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn WriteColor {
+        &mut self.sink
+    }
+}
+
+/// Searches notebooks with a set of options
+pub struct Searcher {
+    opts: SearchOptions
+}
+
+impl Searcher {
+    pub fn new(opts: SearchOptions) -> Self {
+        Self{opts}
+    }
+
+    /// The matches in `paths`: notebooks, and directories whose notebooks are searched (with
+    /// their subdirectories, if the options include `--recursive`). Fails only if no notebooks
+    /// are found; a notebook that cannot be read or parsed is an `Err` item instead, and the
+    /// search goes on with the next one.
+    pub fn search<P: AsRef<OsStr>>(&self, paths: &[P]) -> Result<Matches<'_>, RunErr> {
+        let paths: Vec<&OsStr> = paths.iter().map(AsRef::as_ref).collect();
+        let notebooks = crate::find_notebooks(&paths, &self.opts)?;
+        Ok(Matches{opts: &self.opts, notebooks: notebooks.into_iter(), pending: VecDeque::new()})
+    }
+
+    /// The matches in a notebook that is already in memory, given the JSON text of its file.
+    /// `name` is used as the path of the matches.
+    pub fn search_text(&self, name: &OsStr, data: &str) -> Result<Vec<Match>, RunErr> {
+        let mut collector = Collector{matches: VecDeque::new(), sink: NoColor::new(io::sink())};
+        match crate::search_notebook_text(&name.to_os_string(), data, &self.opts, &mut collector)? {
+            Searched::Done(_) => Ok(collector.matches.into()),
+            _ => Ok(Vec::new())
+        }
+    }
+}
+
+/// An iterator over the matches from [`Searcher::search`], in the order of the notebooks
+pub struct Matches<'a> {
+    opts: &'a SearchOptions,
+    notebooks: std::vec::IntoIter<FoundNotebook>,
+    /// The rest of the matches from the last notebook searched
+    pending: VecDeque<Match>
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Result<Match, RunErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let notebook = self.notebooks.next()?;
+            let mut collector = Collector{matches: VecDeque::new(), sink: NoColor::new(io::sink())};
+            match crate::search_notebook(&notebook, self.opts, &mut collector) {
+                Ok(Searched::Done(_)) => self.pending = collector.matches,
+                // Matches from a notebook that timed out are incomplete, so are dropped too
+                Ok(_) => {},
+                Err(e) => return Some(Err(RunErr{msg: format!("Error in file {:?}: {}", notebook.name, e)}))
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
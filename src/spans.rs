@@ -0,0 +1,145 @@
+//! Locating strings in the raw notebook file for the `--file-lines` option
+//!
+//! serde throws away where in the file each value came from, so this is a second,
+//! much simpler pass over the raw JSON. It does not build any values; it just walks
+//! the structure, keeping track of the JSON Pointer to the current value and the
+//! current line in the file, and records the line that each string value starts on.
+//! Since JSON strings cannot contain literal newlines, that is the only line the
+//! string occupies. Syntax errors are not checked carefully here, since serde has
+//! already validated the file by the time this is called.
+
+use std::collections::HashMap;
+use crate::RunErr;
+
+pub struct FileLines {
+    lines: HashMap<String, usize>
+}
+
+impl FileLines {
+    /// Lines already found some other way, keyed by JSON Pointer
+    pub fn new(lines: HashMap<String, usize>) -> Self {
+        Self{lines}
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, RunErr> {
+        let mut scanner = Scanner{bytes: data.as_bytes(), pos: 0, line: 1, lines: HashMap::new()};
+        let mut pointer = String::new();
+        scanner.value(&mut pointer)?;
+        Ok(Self{lines: scanner.lines})
+    }
+
+    /// The 1-based line number in the file of the string at `pointer`, if it is a string.
+    pub fn line_of(&self, pointer: &str) -> Option<usize> {
+        self.lines.get(pointer).copied()
+    }
+}
+
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    lines: HashMap<String, usize>
+}
+
+impl Scanner<'_> {
+    fn value(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.object(pointer),
+            b'[' => self.array(pointer),
+            b'"' => {
+                self.lines.insert(pointer.clone(), self.line);
+                self.string()?;
+                Ok(())
+            },
+            _ => {
+                // number, true, false, or null
+                while self.pos < self.bytes.len() && !b",]} \t\r\n".contains(&self.bytes[self.pos]) {
+                    self.pos += 1;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn object(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.pos += 1;
+        loop {
+            self.skip_whitespace();
+            match self.peek()? {
+                b'}' => { self.pos += 1; return Ok(()) },
+                b',' => { self.pos += 1; continue },
+                _ => {}
+            }
+
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+
+            let parent_len = pointer.len();
+            pointer.push('/');
+            pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+            self.value(pointer)?;
+            pointer.truncate(parent_len);
+        }
+    }
+
+    fn array(&mut self, pointer: &mut String) -> Result<(), RunErr> {
+        self.pos += 1;
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek()? {
+                b']' => { self.pos += 1; return Ok(()) },
+                b',' => { self.pos += 1; continue },
+                _ => {}
+            }
+
+            let parent_len = pointer.len();
+            pointer.push_str(&format!("/{}", index));
+            self.value(pointer)?;
+            pointer.truncate(parent_len);
+            index += 1;
+        }
+    }
+
+    /// Consume a string token and return its decoded value.
+    fn string(&mut self) -> Result<String, RunErr> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        loop {
+            match self.peek()? {
+                b'\\' => self.pos += 2,
+                b'"' => { self.pos += 1; break },
+                _ => self.pos += 1
+            }
+        }
+
+        // Let serde_json handle unescaping rather than reimplementing it
+        let token = std::str::from_utf8(&self.bytes[start..self.pos])
+            .map_err(|_| RunErr::from("Invalid UTF-8 in JSON string"))?;
+        Ok(serde_json::from_str(token)?)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            if self.bytes[self.pos] == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8, RunErr> {
+        self.bytes.get(self.pos).copied().ok_or_else(|| RunErr::from("Unexpected end of JSON data"))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), RunErr> {
+        if self.peek()? != byte {
+            return Err(RunErr{msg: format!("Expected '{}' at byte {} of the JSON data", byte as char, self.pos)});
+        }
+        self.pos += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+//! The `--strip-outputs` mode, which clears the outputs of some cells and writes the notebook back
+//!
+//! Only the cells whose source matches the pattern (or, with `--all`, every searched cell)
+//! lose their outputs and execution count, so that e.g. one cell's enormous plot can be
+//! dropped without throwing away the rest of the results. As with `--replace`, only those
+//! values are rewritten and the rest of the file is left as it was.
+
+use std::collections::HashSet;
+use crate::rewrite;
+use crate::{Notebook, RunErr, SearchOptions};
+
+/// Clear the outputs and execution counts of the chosen code cells in the notebook text
+/// `data` (already parsed into `nb`). Returns the new text and the number of cells changed;
+/// cells that already had no outputs and no execution count are not counted.
+pub fn strip_outputs(data: &str, nb: &Notebook, all: bool, opts: &SearchOptions) -> Result<(String, usize), RunErr> {
+    let cell_indices = match &opts.cell_range {
+        Some(range) => range.indices(nb.cells.len()),
+        None => 0..nb.cells.len()
+    };
+    let stripped: HashSet<usize> = nb.cells.iter().enumerate()
+        .filter(|(icell, cell)| cell_indices.contains(icell) && opts.searches_cell(cell))
+        .filter(|(_, cell)| cell.cell_type == "code")
+        .filter(|(_, cell)| cell.execution_count.is_some() || cell.outputs.iter().any(|o| !o.is_empty()))
+        .filter(|(_, cell)| all || cell.source.iter().any(|line| opts.is_match(line)))
+        .map(|(icell, _)| icell)
+        .collect();
+    if stripped.is_empty() {
+        return Ok((String::from(data), 0));
+    }
+
+    let wanted = |pointer: &str| -> bool {
+        match rewrite::pointer_tokens(pointer).as_slice() {
+            [cells, icell, key] if cells == "cells" && (key == "outputs" || key == "execution_count") => {
+                icell.parse::<usize>().is_ok_and(|i| stripped.contains(&i))
+            },
+            _ => false
+        }
+    };
+    let edits = rewrite::value_spans(data, &wanted)?.into_iter().map(|range| {
+        let empty = if data[range.clone()].starts_with('[') { "[]" } else { "null" };
+        (range, String::from(empty))
+    }).collect();
+    Ok((rewrite::apply_edits(data, edits), stripped.len()))
+}
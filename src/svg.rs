@@ -0,0 +1,110 @@
+//! Pulling the readable text out of `image/svg+xml` outputs
+//!
+//! SVG plots keep their labels as text: either in `<text>` elements or, for matplotlib
+//! (which draws the glyphs as paths by default), in a comment before each label such as
+//! `<!-- Temperature (K) -->`. This is not a full XML parser; it just walks the tags and
+//! collects the character data and comments between them, skipping `<style>` and
+//! `<script>` elements, which are code rather than text.
+
+/// The text nodes and comments in `svg`, one per line, with surrounding whitespace removed
+/// and the standard entities decoded. Empty nodes are left out.
+pub fn text_lines(svg: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut rest = svg;
+    // The element whose content is being skipped, if any
+    let mut skipping: Option<&str> = None;
+
+    while let Some(start) = rest.find('<') {
+        if skipping.is_none() {
+            push_text(&rest[..start], &mut lines);
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").unwrap_or(comment.len());
+            if skipping.is_none() {
+                push_text(&comment[..end], &mut lines);
+            }
+            rest = comment.get(end+3..).unwrap_or("");
+        }else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            if skipping.is_none() {
+                // CDATA is literal text, so it is not decoded
+                let text = cdata[..end].trim();
+                if !text.is_empty() {
+                    lines.push(String::from(text));
+                }
+            }
+            rest = cdata.get(end+3..).unwrap_or("");
+        }else{
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            let tag = &rest[..end];
+            let name = tag_name(tag);
+            match skipping {
+                Some(skipped) if tag.starts_with("</") && name == skipped => skipping = None,
+                None if (name == "style" || name == "script") && !tag.ends_with("/>") && !tag.starts_with("</") => skipping = Some(name),
+                _ => {}
+            }
+            rest = &rest[end..];
+        }
+    }
+    if skipping.is_none() {
+        push_text(rest, &mut lines);
+    }
+    lines
+}
+
+/// The element name of a tag like "<text x='1'>" or "</text>", without any namespace prefix
+fn tag_name(tag: &str) -> &str {
+    let name = tag.trim_start_matches('<').trim_start_matches('/');
+    let end = name.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(name.len());
+    let name = &name[..end];
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn push_text(text: &str, lines: &mut Vec<String>) {
+    for line in text.lines() {
+        let line = line.trim();
+        if !line.is_empty() {
+            lines.push(decode_entities(line));
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return String::from(text);
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest.find(';').map(|semi| (&rest[1..semi], semi));
+        let ch = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => name.strip_prefix('#').and_then(|dec| dec.parse().ok()).and_then(char::from_u32)
+            }
+        });
+        match (ch, entity) {
+            (Some(ch), Some((_, semi))) => {
+                decoded.push(ch);
+                rest = &rest[semi+1..];
+            },
+            // Not an entity we know, so keep the '&' as it is
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
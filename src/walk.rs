@@ -0,0 +1,212 @@
+//! Finding the notebooks in the directories given as paths
+//!
+//! Directories are listed with the `ignore` crate's parallel walker, so that a big tree (or
+//! one on a slow network drive) does not take longer to list than to search. Of its filters,
+//! only the ones for ignore files (unless --no-ignore is given: git's, and jrep's own
+//! `.jrepignore` files) and hidden files (unless --hidden is given) are used. jrep decides
+//! which other files to keep itself.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{WalkBuilder, WalkState};
+use crate::{archive, FoundNotebook, RunErr, SearchOptions};
+
+/// The name of jrep's own ignore files, which are read from each directory listed and from
+/// the home directory. They have the same syntax as .gitignore files, but apply whether or
+/// not the notebooks are in a git repository, and take precedence over git's ignore files.
+const IGNORE_FILENAME: &str = ".jrepignore";
+
+/// The directory in which Jupyter keeps checkpoints of the notebooks in its parent
+const CHECKPOINTS_DIR: &str = ".ipynb_checkpoints";
+
+/// Which notebooks to keep by their paths (--include and --exclude)
+#[derive(Default)]
+pub struct PathFilter {
+    include: Option<Globs>,
+    exclude: Option<Globs>
+}
+
+impl PathFilter {
+    pub fn new(include: &[&str], exclude: &[&str]) -> Result<Self, RunErr> {
+        Ok(Self{include: Globs::new(include, "--include")?, exclude: Globs::new(exclude, "--exclude")?})
+    }
+
+    /// Whether to search the notebook at `path`, which is relative to the directory it was
+    /// found in, or as given on the command line
+    pub fn keeps_file(&self, path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|g| g.is_match(path)) && !self.exclude.as_ref().is_some_and(|g| g.is_match(path))
+    }
+
+    /// Whether to look for notebooks in the directory at `path`, relative to the directory
+    /// being listed. Only --exclude applies, since --include globs are meant for files.
+    fn keeps_dir(&self, path: &Path) -> bool {
+        !self.exclude.as_ref().is_some_and(|g| g.is_match(path))
+    }
+}
+
+/// The globs given to --include or --exclude. As in a .gitignore file, a glob with no '/'
+/// matches any one part of the path (e.g. '*checkpoint*' matches both
+/// '.ipynb_checkpoints/a-checkpoint.ipynb' and the directory itself), while one with a '/'
+/// must match the whole path, and its '*'s do not match a '/'.
+struct Globs {
+    names: GlobSet,
+    paths: GlobSet
+}
+
+impl Globs {
+    fn new(globs: &[&str], flag: &str) -> Result<Option<Self>, RunErr> {
+        if globs.is_empty() {
+            return Ok(None);
+        }
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for &glob in globs {
+            let trimmed = glob.trim_start_matches("./");
+            let compiled = GlobBuilder::new(trimmed).literal_separator(true).build()
+                .map_err(|e| RunErr{msg: format!("Invalid glob '{}' for '{}': {}", glob, flag, e.kind())})?;
+            if trimmed.contains('/') {
+                paths.add(compiled);
+            }else{
+                names.add(compiled);
+            }
+        }
+        let build = |set: GlobSetBuilder| set.build().map_err(|e| RunErr{msg: format!("Invalid glob for '{}': {}", flag, e)});
+        Ok(Some(Self{names: build(names)?, paths: build(paths)?}))
+    }
+
+    fn is_match(&self, path: &Path) -> bool {
+        // Leading "./"s would keep "a/*.ipynb" from matching "./a/b.ipynb"
+        let path: PathBuf = path.components().filter(|c| !matches!(c, Component::CurDir)).collect();
+        self.paths.is_match(&path) || path.components().any(|c| self.names.is_match(c.as_os_str()))
+    }
+}
+
+/// Whether the file name of `path` ends in one of the extensions that notebooks have (.ipynb,
+/// or those given with --ext), ignoring case. Extensions may have several parts, e.g. "ipynb.json".
+pub fn has_notebook_extension(path: &Path, opts: &SearchOptions) -> bool {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_lowercase(),
+        None => return false
+    };
+    opts.extensions.iter().any(|ext| {
+        name.len() > ext.len() + 1 && name.ends_with(ext.as_str()) && name[..name.len() - ext.len()].ends_with('.')
+    })
+}
+
+/// Whether to search the notebook at `inner` in an archive or under a cloud storage prefix,
+/// applying the same rules as to the files found when listing a directory
+pub fn keeps_member(inner: &Path, opts: &SearchOptions) -> bool {
+    let skipped_part = |part: &OsStr| {
+        let part = part.to_string_lossy();
+        (part.starts_with('.') && !opts.hidden) || (part == CHECKPOINTS_DIR && !opts.include_checkpoints)
+    };
+    let parts_kept = inner.components().all(|c| match c {
+        Component::Normal(part) => !skipped_part(part),
+        _ => true
+    });
+    parts_kept && has_notebook_extension(inner, opts) && opts.path_filter.keeps_file(inner)
+}
+
+/// Add the notebooks in `dirpath` (and, with --recursive, its subdirectories, down to
+/// --max-depth) to `file_list`, sorted by path so that the order does not depend on which
+/// thread found them first.
+pub fn get_notebooks_in_dir(dirpath: &Path, file_list: &mut Vec<FoundNotebook>, opts: &SearchOptions) -> Result<(), RunErr> {
+    // Unreadable subdirectories are skipped, but an unreadable top directory is an error
+    dirpath.read_dir()?;
+
+    // The notebooks in `dirpath` itself are at depth 1
+    let max_depth = opts.max_depth.or(if opts.recursive { None } else { Some(1) });
+
+    let mut builder = WalkBuilder::new(dirpath);
+    // Like git, .gitignore files only apply inside a git repository, including those in the
+    // directories above `dirpath`. `parents` also applies to .jrepignore files.
+    let use_git_ignores = !opts.no_ignore;
+    builder.standard_filters(false)
+        .git_ignore(use_git_ignores)
+        .git_exclude(use_git_ignores)
+        .git_global(use_git_ignores)
+        .parents(use_git_ignores)
+        .hidden(!opts.hidden)
+        .follow_links(!opts.no_follow)
+        .same_file_system(opts.one_file_system)
+        .threads(opts.threads)
+        .max_depth(max_depth);
+    if !opts.no_ignore {
+        builder.add_custom_ignore_filename(IGNORE_FILENAME);
+        let home_ignore = std::env::var_os("HOME").filter(|d| !d.is_empty())
+            .map(|d| Path::new(&d).join(IGNORE_FILENAME))
+            .filter(|p| p.is_file());
+        if let Some(home_ignore) = home_ignore {
+            if let Some(e) = builder.add_ignore(&home_ignore) {
+                return Err(RunErr{msg: format!("Error in {}: {}", home_ignore.display(), e)});
+            }
+        }
+    }
+
+    // When followed, symbolic links can lead to the same directory more than once, or to a
+    // directory that contains the link. Each directory is only listed the first time any thread reaches it
+    // by its canonical path, which both prevents infinite loops and avoids searching the same
+    // notebooks twice.
+    let visited_dirs: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+    let found: Mutex<Vec<FoundNotebook>> = Mutex::new(Vec::new());
+    let archives: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+    let first_error: Mutex<Option<RunErr>> = Mutex::new(None);
+
+    builder.build_parallel().run(|| Box::new(|entry| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // E.g. broken links or directories we may not read
+            Err(_) => return WalkState::Continue
+        };
+        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+        // The path within `dirpath` is what --include and --exclude globs are matched against
+        let rel_path = entry.path().strip_prefix(dirpath).unwrap_or(entry.path());
+        if is_dir && max_depth.is_none_or(|d| entry.depth() < d) {
+            if !opts.path_filter.keeps_dir(rel_path) {
+                return WalkState::Skip;
+            }
+            // Jupyter's autosaved copies of the notebooks next to them, which would only repeat
+            // (possibly stale) matches. The directory given is searched even if it is one.
+            if entry.depth() > 0 && entry.file_name() == CHECKPOINTS_DIR && !opts.include_checkpoints {
+                return WalkState::Skip;
+            }
+            let canon_path = match entry.path().canonicalize() {
+                Ok(p) => p,
+                Err(e) => {
+                    first_error.lock().unwrap().get_or_insert(RunErr::from(e));
+                    return WalkState::Quit;
+                }
+            };
+            if !visited_dirs.lock().unwrap().insert(canon_path) {
+                return WalkState::Skip;
+            }
+        // With --no-follow, links are neither directories nor files, so are skipped
+        }else if entry.file_type().is_some_and(|ft| ft.is_file()) && has_notebook_extension(entry.path(), opts)
+            && opts.path_filter.keeps_file(rel_path) {
+            found.lock().unwrap().push(FoundNotebook::local(entry.path()));
+        }else if opts.search_archives && entry.file_type().is_some_and(|ft| ft.is_file()) && archive::is_archive(entry.path()) {
+            archives.lock().unwrap().push(entry.into_path());
+        }
+        WalkState::Continue
+    }));
+
+    if let Some(e) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    let mut found = found.into_inner().unwrap();
+    // An archive that cannot be read is not worth stopping the search for, unlike one
+    // that was listed as a path
+    for path in archives.into_inner().unwrap() {
+        match archive::list_notebooks(&path, opts) {
+            Ok(mut notebooks) => found.append(&mut notebooks),
+            Err(e) if !opts.no_messages => eprintln!("{}", e),
+            Err(_) => {}
+        }
+    }
+    found.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    file_list.append(&mut found);
+    Ok(())
+}
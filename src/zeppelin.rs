@@ -0,0 +1,147 @@
+//! Reading Apache Zeppelin notes (.zpln files, or note.json in Zeppelin before 0.9)
+//!
+//! Each paragraph of a note becomes a cell: a markdown cell if its interpreter is "%md",
+//! otherwise a code cell. The interpreter line is taken off the source and kept in the cell
+//! metadata as "interpreter", along with the paragraph's title. The paragraph's results become
+//! outputs, with TEXT and TABLE results (tab-separated rows) as "text/plain", HTML as
+//! "text/html", and images as "image/png"; any other kind is kept as a vendor type, e.g.
+//! "application/vnd.zeppelin.angular".
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use serde::Deserialize;
+use crate::{Cell, CellMetadata, KernelSpec, Notebook, NotebookMetadata, Output, RunErr};
+
+#[derive(Deserialize)]
+struct Note {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    paragraphs: Vec<Paragraph>
+}
+
+#[derive(Deserialize)]
+struct Paragraph {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    config: serde_json::Value,
+    // "results" since Zeppelin 0.7, "result" before
+    #[serde(default, alias = "result")]
+    results: Option<Results>
+}
+
+#[derive(Deserialize)]
+struct Results {
+    // Only before 0.7; since then, each message has its own type
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+    #[serde(default)]
+    msg: Messages
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Messages {
+    List(Vec<Message>),
+    // Before 0.7, there was only one result, whose type was next to its text
+    Single(String)
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages::List(Vec::new())
+    }
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    data: String
+}
+
+/// Whether `filename` is a Zeppelin note, by its name
+pub fn is_note(filename: &OsStr) -> bool {
+    let path = Path::new(filename);
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zpln")) || path.file_name().is_some_and(|name| name == "note.json")
+}
+
+/// Parse the JSON text of a note, keeping only the output data types in `kept_types`, or all
+/// of them if that is `None`
+pub fn parse(data: &str, kept_types: Option<&[String]>) -> Result<Notebook, RunErr> {
+    let note: Note = serde_json::from_str(data)?;
+    let mut language = None;
+    let cells = note.paragraphs.into_iter().map(|para| {
+        let text = para.text.unwrap_or_default();
+        let (interpreter, source) = split_interpreter(&text);
+        let is_markdown = interpreter.is_some_and(|i| i == "md" || i == "markdown");
+        if !is_markdown && language.is_none() {
+            language = para.config.pointer("/editorSetting/language").and_then(|l| l.as_str()).map(String::from);
+        }
+
+        let mut other = serde_json::Map::new();
+        if let Some(interpreter) = interpreter {
+            other.insert(String::from("interpreter"), serde_json::Value::from(interpreter));
+        }
+        if let Some(title) = para.title {
+            other.insert(String::from("title"), serde_json::Value::from(title));
+        }
+        let outputs: Vec<Output> = match para.results {
+            Some(Results{msg: Messages::List(messages), ..}) => messages.into_iter().map(|m| output(&m.kind, m.data, kept_types)).collect(),
+            Some(Results{msg: Messages::Single(data), kind}) => vec![output(kind.as_deref().unwrap_or("TEXT"), data, kept_types)],
+            None => Vec::new()
+        };
+        Cell{
+            id: para.id,
+            cell_type: String::from(if is_markdown { "markdown" } else { "code" }),
+            metadata: CellMetadata{tags: Vec::new(), other},
+            source: source.split_inclusive('\n').map(String::from).collect(),
+            outputs: if is_markdown { None } else { Some(outputs) },
+            ..Cell::default()
+        }
+    }).collect();
+
+    let mut other = serde_json::Map::new();
+    if let Some(name) = note.name {
+        other.insert(String::from("name"), serde_json::Value::from(name));
+    }
+    let kernelspec = language.map(|language| KernelSpec{language: Some(language), other: serde_json::Map::new()});
+    Ok(Notebook{cells, metadata: NotebookMetadata{kernelspec, language_info: None, other}})
+}
+
+/// The interpreter named at the start of a paragraph's text (e.g. "pyspark" for "%pyspark"),
+/// if any, and the rest of the text
+fn split_interpreter(text: &str) -> (Option<&str>, &str) {
+    let trimmed = text.trim_start();
+    match trimmed.strip_prefix('%') {
+        Some(rest) => {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let source = rest[end..].strip_prefix([' ', '\t']).unwrap_or(&rest[end..]);
+            let source = source.strip_prefix("\r\n").or_else(|| source.strip_prefix('\n')).unwrap_or(source);
+            (Some(&rest[..end]), source)
+        },
+        None => (None, text)
+    }
+}
+
+/// An output with the data of a result of type `kind`
+fn output(kind: &str, data: String, kept_types: Option<&[String]>) -> Output {
+    let dtype = match kind {
+        "TEXT" | "TABLE" | "" => String::from("text/plain"),
+        "HTML" => String::from("text/html"),
+        "IMG" => String::from("image/png"),
+        other => format!("application/vnd.zeppelin.{}", other.to_lowercase())
+    };
+    let mut output_data = BTreeMap::new();
+    if kept_types.is_none_or(|types| types.contains(&dtype)) {
+        output_data.insert(dtype, serde_json::Value::from(data));
+    }
+    Output{output_type: String::from("display_data"), data: Some(output_data), ..Output::default()}
+}